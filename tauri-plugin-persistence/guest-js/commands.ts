@@ -69,6 +69,166 @@ async databaseRollbackTransaction(context: ContextSpecifier, database: DatabaseS
     else return { status: "error", error: e  as any };
 }
 },
+async databaseBackup(context: ContextSpecifier, database: DatabaseSpecifier, path: string, overwrite: boolean) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_backup", { context, database, path, overwrite }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseRestore(context: ContextSpecifier, database: DatabaseSpecifier, path: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_restore", { context, database, path }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databasePersist(context: ContextSpecifier, database: DatabaseSpecifier, path: string, backend: StorageBackendKind) : Promise<Result<DatabaseInfo, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_persist", { context, database, path, backend }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseSnapshot(context: ContextSpecifier, database: DatabaseSpecifier, directory: string, keep: number | null) : Promise<Result<SnapshotInfo, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_snapshot", { context, database, directory, keep }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseListSnapshots(context: ContextSpecifier, database: DatabaseSpecifier, directory: string) : Promise<Result<SnapshotInfo[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_list_snapshots", { context, database, directory }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseScheduleSnapshots(context: ContextSpecifier, database: DatabaseSpecifier, schedule: SnapshotSchedule | null) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_schedule_snapshots", { context, database, schedule }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseDump(context: ContextSpecifier, database: DatabaseSpecifier, directory: string, format: DumpFormat) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_dump", { context, database, directory, format }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseRestoreDump(context: ContextSpecifier, database: DatabaseSpecifier, directory: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_restore_dump", { context, database, directory }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseExecuteSql(context: ContextSpecifier, database: DatabaseSpecifier, sql: string, params: SqlValue[]) : Promise<Result<number, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_execute_sql", { context, database, sql, params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async databaseQuerySql(context: ContextSpecifier, database: DatabaseSpecifier, sql: string, params: SqlValue[]) : Promise<Result<SqlRows, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|database_query_sql", { context, database, sql, params }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvStore(context: ContextSpecifier, store: KvSpecifier) : Promise<Result<KvStoreInfo, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_store", { context, store }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvClose(context: ContextSpecifier, store: KvSpecifier) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_close", { context, store }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvGet(context: ContextSpecifier, store: KvSpecifier, key: string, transaction: string | null) : Promise<Result<JsonValue | null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_get", { context, store, key, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvSet(context: ContextSpecifier, store: KvSpecifier, key: string, value: JsonValue, transaction: string | null) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_set", { context, store, key, value, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvDelete(context: ContextSpecifier, store: KvSpecifier, key: string, transaction: string | null) : Promise<Result<boolean, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_delete", { context, store, key, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvList(context: ContextSpecifier, store: KvSpecifier, prefix: string, transaction: string | null) : Promise<Result<KvEntry<JsonValue>[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_list", { context, store, prefix, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvCompareAndSwap(context: ContextSpecifier, store: KvSpecifier, key: string, swap: KvSwap, transaction: string | null) : Promise<Result<boolean, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_compare_and_swap", { context, store, key, swap, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvStartTransaction(context: ContextSpecifier, store: KvSpecifier) : Promise<Result<string, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_start_transaction", { context, store }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvCommitTransaction(context: ContextSpecifier, store: KvSpecifier, transaction: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_commit_transaction", { context, store, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async kvRollbackTransaction(context: ContextSpecifier, store: KvSpecifier, transaction: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|kv_rollback_transaction", { context, store, transaction }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async collectionCountDocuments(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier) : Promise<Result<number, Error>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_count_documents", { context, database, collection }) };
@@ -77,9 +237,9 @@ async collectionCountDocuments(context: ContextSpecifier, database: DatabaseSpec
     else return { status: "error", error: e  as any };
 }
 },
-async collectionUpdateDocuments(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, query: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, update: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, operations: OperationCount, upsert: boolean) : Promise<Result<UpdateResult, Error>> {
+async collectionUpdateDocuments(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, query: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, changes: DocumentUpdate) : Promise<Result<UpdateResult, Error>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_update_documents", { context, database, collection, query, update, operations, upsert }) };
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_update_documents", { context, database, collection, query, changes }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -93,9 +253,9 @@ async collectionDeleteDocuments(context: ContextSpecifier, database: DatabaseSpe
     else return { status: "error", error: e  as any };
 }
 },
-async collectionCreateIndex(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, keys: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, name: string | null, unique: boolean | null) : Promise<Result<null, Error>> {
+async collectionCreateIndex(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, keys: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, options: CreateIndexOptions) : Promise<Result<null, Error>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_create_index", { context, database, collection, keys, name, unique }) };
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_create_index", { context, database, collection, keys, options }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -125,9 +285,9 @@ async collectionInsertDocuments(context: ContextSpecifier, database: DatabaseSpe
     else return { status: "error", error: e  as any };
 }
 },
-async collectionFindManyDocuments(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, filter: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, skip: number | null, limit: number | null, sort: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }> | null) : Promise<Result<(null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>)[], Error>> {
+async collectionFindManyDocuments(context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier, filter: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>, options: FindDocumentsOptions) : Promise<Result<(null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>)[], Error>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_find_many_documents", { context, database, collection, filter, skip, limit, sort }) };
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|collection_find_many_documents", { context, database, collection, filter, options }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -181,6 +341,62 @@ async fileReadBytes(context: ContextSpecifier, fileHandle: FileHandleSpecifier,
     else return { status: "error", error: e  as any };
 }
 },
+async fileFlush(context: ContextSpecifier, fileHandle: FileHandleSpecifier) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|file_flush", { context, fileHandle }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async fileSync(context: ContextSpecifier, fileHandle: FileHandleSpecifier) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|file_sync", { context, fileHandle }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async fileLock(context: ContextSpecifier, fileHandle: FileHandleSpecifier, exclusive: boolean) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|file_lock", { context, fileHandle, exclusive }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async fileUnlock(context: ContextSpecifier, fileHandle: FileHandleSpecifier) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|file_unlock", { context, fileHandle }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async tempFile(context: ContextSpecifier) : Promise<Result<FileHandleInfo, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|temp_file", { context }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async listFileHandles(context: ContextSpecifier) : Promise<Result<FileHandleInfo[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|list_file_handles", { context }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async closeAllFileHandles(context: ContextSpecifier) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|close_all_file_handles", { context }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
 async getContextBasePath(context: ContextSpecifier) : Promise<Result<string, Error>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|get_context_base_path", { context }) };
@@ -252,12 +468,177 @@ async cleanup() : Promise<Result<null, Error>> {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
 }
+},
+async readDocument(context: ContextSpecifier, path: string, format: DocumentFormat | null) : Promise<Result<JsonValue, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|read_document", { context, path, format }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async writeDocument(context: ContextSpecifier, path: string, document: JsonValue, format: DocumentFormat | null) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|write_document", { context, path, document, format }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async tempDir(context: ContextSpecifier) : Promise<Result<string, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|temp_dir", { context }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async copyPath(context: ContextSpecifier, from: string, to: string, recursive: boolean, overwrite: boolean) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|copy_path", { context, from, to, recursive, overwrite }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async renamePath(context: ContextSpecifier, from: string, to: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|rename_path", { context, from, to }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async walk(context: ContextSpecifier, path: string, options: WalkOptions | null) : Promise<Result<WalkEntry[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|walk", { context, path, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async walkStream(context: ContextSpecifier, path: string, options: WalkOptions | null, channel: TAURI_CHANNEL<WalkEntry>) : Promise<Result<number, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|walk_stream", { context, path, options, channel }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async watch(context: ContextSpecifier, path: string, recursive: boolean) : Promise<Result<string, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|watch", { context, path, recursive }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async unwatch(context: ContextSpecifier, id: string) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|unwatch", { context, id }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exists(context: ContextSpecifier, path: string) : Promise<Result<boolean, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|exists", { context, path }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async isFile(context: ContextSpecifier, path: string) : Promise<Result<boolean, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|is_file", { context, path }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async isDir(context: ContextSpecifier, path: string) : Promise<Result<boolean, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|is_dir", { context, path }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async statMany(context: ContextSpecifier, paths: string[]) : Promise<Result<(PathMetadata | null)[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|stat_many", { context, paths }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setPermissions(context: ContextSpecifier, path: string, mode: number) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|set_permissions", { context, path, mode }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setReadonly(context: ContextSpecifier, path: string, readonly: boolean) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|set_readonly", { context, path, readonly }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async hashFile(context: ContextSpecifier, path: string, algorithm: HashAlgorithm, cache: string | null) : Promise<Result<FileHash, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|hash_file", { context, path, algorithm, cache }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async hashDirectory(context: ContextSpecifier, path: string, algorithm: HashAlgorithm, options: WalkOptions | null, cache: string | null) : Promise<Result<FileHash[], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|hash_directory", { context, path, algorithm, options, cache }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async findDuplicates(context: ContextSpecifier, path: string, algorithm: HashAlgorithm, options: WalkOptions | null, cache: string | null) : Promise<Result<FileHash[][], Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|find_duplicates", { context, path, algorithm, options, cache }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async exportContext(context: ContextSpecifier, destination: string, format: ArchiveFormat) : Promise<Result<null, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|export_context", { context, destination, format }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async importContext(archive: string, path: string, alias: string) : Promise<Result<ContextInfo, Error>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("plugin:persistence|import_context", { archive, path, alias }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
 }
 }
 
 /** user-defined events **/
 
 
+export const events = __makeEvents__<{
+contextWatchEvent: ContextWatchEvent
+}>({
+contextWatchEvent: "plugin:persistence:context-watch-event"
+})
 
 /** user-defined constants **/
 
@@ -265,6 +646,14 @@ async cleanup() : Promise<Result<null, Error>> {
 
 /** user-defined types **/
 
+/**
+ * Archive format used to export & import whole contexts
+ */
+export type ArchiveFormat = "zip" | 
+/**
+ * Gzip-compressed tarball
+ */
+"tar_gz"
 /**
  * A model used to specify a collection
  */
@@ -300,19 +689,103 @@ name: string;
 /**
  * Context path
  */
-path: string }
+path: string; 
+/**
+ * Lock owner, if the context was opened with a lock
+ */
+lock: ContextLockOwner | null; 
+/**
+ * How symlinks are treated when resolving paths in this context
+ */
+resolution: PathResolution }
+/**
+ * Owner information written to the lockfile of a locked context
+ */
+export type ContextLockOwner = { 
+/**
+ * Process ID of the owner
+ */
+pid: number; 
+/**
+ * Hostname of the machine the owner is running on
+ */
+hostname: string; 
+/**
+ * When the lock was acquired
+ */
+acquired: string }
 /**
  * A model used to specify an existing or closed context
  */
 export type ContextSpecifier = 
 /**
- * Open a new context
+ * Open a new context, optionally taking an exclusive lock on its root directory
  */
-{ alias: string; path: string } | 
+{ alias: string; path: string; lock?: boolean; resolution?: PathResolution } | 
 /**
  * Return an existing context
  */
 { alias: string }
+/**
+ * Emitted (debounced) when paths inside a watched context directory change
+ */
+export type ContextWatchEvent = { 
+/**
+ * Name of the context the watcher belongs to
+ */
+context: string; 
+/**
+ * ID of the watcher that observed the change
+ */
+watcher: string; 
+/**
+ * Kind of change
+ */
+kind: WatchEventKind; 
+/**
+ * Affected paths, relative to the context root
+ */
+paths: string[] }
+/**
+ * Options for a new index
+ */
+export type CreateIndexOptions = { 
+/**
+ * Defaults to a name derived from the index's keys
+ */
+name?: string | null; unique?: boolean | null }
+/**
+ * Stable code for the cause of a database failure, the same across storage backends
+ */
+export type DatabaseErrorCode = 
+/**
+ * A unique index or `_id` already has the key
+ */
+"duplicate_key" | 
+/**
+ * A field or operand has the wrong type for the operation
+ */
+"type_mismatch" | 
+/**
+ * The filter, update or index definition is malformed or unsupported
+ */
+"invalid_query" | 
+/**
+ * The collection or index doesn't exist
+ */
+"not_found" | 
+/**
+ * A transaction conflicts with changes committed after it started
+ */
+"transaction_conflict" | 
+/**
+ * The database or store is in use by another connection or transaction
+ */
+"busy" | "closed" | "corrupted" | 
+/**
+ * The backend doesn't support the operation
+ */
+"unsupported" | "io" | "other"
 /**
  * A model containing serializable information about a [crate::Database]
  */
@@ -324,20 +797,60 @@ name: string;
 /**
  * Database path
  */
-path: string }
+path: string; 
+/**
+ * Storage backend the database was opened with
+ */
+backend: StorageBackendKind }
 /**
  * A model used to specify an existing or closed database
  */
 export type DatabaseSpecifier = 
 /**
- * Open a new database
+ * Open a new database, optionally with a storage backend other than PoloDB
  */
-{ alias: string; path: string } | 
+{ alias: string; path: string; backend?: StorageBackendKind } | 
+/**
+ * Open a new in-memory database. `memory` must be `true` (`false` is rejected), it only distinguishes this from [DatabaseSpecifier::Aliased].
+ */
+{ alias: string; memory: boolean } | 
 /**
  * Return an existing database
  */
 { alias: string }
-export type Error = { kind: "unknown"; reason: string } | { kind: "open_context"; name: string; path: string; reason: string } | { kind: "open_database"; name: string; context: string; path: string; reason: string } | { kind: "open_file_handle"; path: string; context: string; reason: string } | { kind: "unknown_context"; reason: string } | { kind: "unknown_database"; reason: string } | { kind: "unknown_file_handle"; reason: string } | { kind: "unknown_transaction"; reason: string } | { kind: "invalid_path"; reason: string } | { kind: "no_absolute_paths"; reason: string } | { kind: "path_escapes_context"; reason: string } | { kind: "database_error"; reason: string } | { kind: "serialization_error"; reason: string } | { kind: "deserialization_error"; reason: string } | { kind: "io_error"; reason: string } | { kind: "string_encoding_error"; reason: string } | { kind: "filesystem_error"; operation: string; reason: string }
+/**
+ * Serialization format of a structured document file
+ */
+export type DocumentFormat = "json" | "toml" | "yaml" | "bson"
+/**
+ * The changes applied by an update, & how many documents they apply to
+ */
+export type DocumentUpdate = { 
+/**
+ * Update operators (ie `{"$set": {...}}`)
+ */
+update: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>; operations: OperationCount; 
+/**
+ * Insert a document if none match the query
+ */
+upsert?: boolean }
+/**
+ * File format of a logical database dump
+ */
+export type DumpFormat = 
+/**
+ * One relaxed Extended JSON document per line (`.ndjson`)
+ */
+"json" | 
+/**
+ * Concatenated raw BSON documents (`.bson`)
+ */
+"bson"
+export type Error = { kind: "unknown"; reason: string } | { kind: "open_context"; name: string; path: string; reason: string } | { kind: "open_database"; name: string; context: string; path: string; reason: string } | { kind: "open_kv_store"; name: string; context: string; path: string; reason: string } | { kind: "open_file_handle"; path: string; context: string; reason: string } | { kind: "unknown_context"; reason: string } | { kind: "unknown_database"; reason: string } | { kind: "unknown_kv_store"; reason: string } | { kind: "unknown_file_handle"; reason: string } | { kind: "unknown_transaction"; reason: string } | { kind: "unknown_watcher"; reason: string } | { kind: "invalid_path"; reason: string } | { kind: "no_absolute_paths"; reason: string } | { kind: "path_escapes_context"; reason: string } | { kind: "symlink_denied"; reason: string } | { kind: "database_error"; reason: string; code?: DatabaseErrorCode; collection?: string | null; transaction?: string | null } | { kind: "serialization_error"; reason: string } | { kind: "deserialization_error"; reason: string } | { kind: "io_error"; reason: string; io_kind?: IoErrorKind } | { kind: "string_encoding_error"; reason: string } | { kind: "filesystem_error"; operation: string; reason: string; path?: string | null; io_kind?: IoErrorKind | null } | { kind: "parse_error"; path: string; format: string; line: number | null; column: number | null; reason: string } | { kind: "unknown_document_format"; reason: string } | { kind: "context_locked"; path: string; pid: number | null; hostname: string | null } | { kind: "file_locked"; reason: string } | { kind: "archive_error"; reason: string } | { kind: "path_in_use"; path: string; reason: string } | { kind: "encryption_error"; reason: string } | { kind: "wrong_key"; reason: string } | { kind: "scope_denied"; alias: string; path: string }
+/**
+ * Compression applied to data written through a file handle
+ */
+export type FileCompression = "gzip" | "zstd"
 /**
  * A model containing serializable information about a [crate::FileHandle]
  */
@@ -353,8 +866,32 @@ path: string;
 /**
  * Open mode
  */
-mode: FileHandleMode }
-export type FileHandleMode = { mode: "create"; new: boolean; overwrite: boolean } | { mode: "write"; overwrite: boolean } | { mode: "read" }
+mode: FileHandleMode; 
+/**
+ * Current cursor position, if it could be determined
+ */
+position: number | null; 
+/**
+ * Current file size, if it could be determined
+ */
+size: number | null; 
+/**
+ * When the handle was opened
+ */
+opened: string; 
+/**
+ * Whether the file will be deleted when the handle is closed
+ */
+temporary: boolean; 
+/**
+ * Compression applied by the handle. For handles that decompress, this is only known once the first bytes are read.
+ */
+compression: FileCompression | null }
+/**
+ * How a file handle is opened. `read` handles detect gzip & zstd files by their magic bytes & decompress them,
+ * unless `raw` is set.
+ */
+export type FileHandleMode = { mode: "create"; new: boolean; overwrite: boolean; sync?: boolean; encrypted?: boolean; compression?: FileCompression | null } | { mode: "write"; overwrite: boolean; sync?: boolean; encrypted?: boolean; compression?: FileCompression | null } | { mode: "read"; encrypted?: boolean; raw?: boolean }
 /**
  * A model used to specify an existing or closed file handle
  */
@@ -367,7 +904,83 @@ export type FileHandleSpecifier =
  * Open a new file handle
  */
 { path: string; mode: FileHandleMode }
+/**
+ * Content hash of a single file
+ */
+export type FileHash = { 
+/**
+ * Path of the file, relative to the context root
+ */
+path: string; 
+/**
+ * Algorithm used to compute the digest
+ */
+algorithm: HashAlgorithm; 
+/**
+ * Lowercase hex digest
+ */
+digest: string; 
+/**
+ * Size of the file when it was hashed
+ */
+size: number; 
+/**
+ * Whether the digest came from a hash index rather than being recomputed
+ */
+cached: boolean }
+/**
+ * Paging & ordering of a document search
+ */
+export type FindDocumentsOptions = { skip?: number | null; limit?: number | null; sort?: null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }> | null }
+/**
+ * Algorithm used to hash file contents
+ */
+export type HashAlgorithm = "sha_256" | "blake_3"
+/**
+ * Stable code for the kind of an IO failure, mirroring [std::io::ErrorKind]
+ */
+export type IoErrorKind = "not_found" | "permission_denied" | "already_exists" | "not_a_directory" | "is_a_directory" | "directory_not_empty" | "read_only_filesystem" | "storage_full" | "resource_busy" | "crosses_devices" | "invalid_input" | "invalid_data" | "unexpected_eof" | "timed_out" | "interrupted" | "would_block" | "unsupported" | "out_of_memory" | "other"
 export type JsonValue = null | boolean | number | string | JsonValue[] | Partial<{ [key in string]: JsonValue }>
+/**
+ * How values in a key-value store are serialized. Fixed when the store is created.
+ */
+export type KvEncoding = "json" | "bson"
+/**
+ * A single key & its value, as returned by prefix listings
+ */
+export type KvEntry<T> = { key: string; value: T }
+/**
+ * A model used to specify an existing or closed key-value store
+ */
+export type KvSpecifier = 
+/**
+ * Open a new key-value store
+ */
+{ alias: string; path: string; encoding?: KvEncoding } | 
+/**
+ * Return an existing key-value store
+ */
+{ alias: string }
+/**
+ * A model containing serializable information about a [crate::KvStore]
+ */
+export type KvStoreInfo = { 
+/**
+ * Store name
+ */
+name: string; 
+/**
+ * Store path
+ */
+path: string; 
+/**
+ * Value encoding of the store
+ */
+encoding: KvEncoding }
+/**
+ * A compare-and-swap on a key-value entry. `None` means the key is absent, or is to be deleted.
+ */
+export type KvSwap = { expected: JsonValue | null; value: JsonValue | null }
 /**
  * Whether to do one operation or multiple (in a database context)
  */
@@ -375,15 +988,140 @@ export type OperationCount = "one" | "many"
 /**
  * Description of the type of a file/directory/symlink
  */
-export type PathFileType = "directory" | "file" | "symlink"
+export type PathFileType = "directory" | "file" | "symlink" | 
+/**
+ * Sockets, FIFOs, and device files
+ */
+"other"
 /**
  * General info about a path
  */
-export type PathInformation = { file_name: string; absolute_path: string; media_type: string }
+export type PathInformation = { file_name: string; absolute_path: string; 
+/**
+ * Path relative to the context root
+ */
+relative_path: string; media_type: string }
 /**
  * File or folder metadata
  */
-export type PathMetadata = { file_type: PathFileType; size: number; last_modified: string | null; last_accessed: string | null; created: string | null }
+export type PathMetadata = { file_type: PathFileType; size: number; last_modified: string | null; last_accessed: string | null; created: string | null; 
+/**
+ * Whether the path is marked read-only
+ */
+readonly: boolean; 
+/**
+ * Unix permission bits (ie `0o644`), if available on this platform
+ */
+mode: number | null; 
+/**
+ * Target of the path, if the path itself is a symlink
+ */
+symlink_target: string | null; 
+/**
+ * Whether the path is hidden (dotfiles on Unix, the hidden attribute on Windows)
+ */
+hidden: boolean; 
+/**
+ * Inode number, on Unix
+ */
+inode: number | null; 
+/**
+ * ID of the containing device, on Unix
+ */
+device: number | null }
+/**
+ * How symlinks are treated when resolving paths inside a context
+ */
+export type PathResolution = 
+/**
+ * Any symlink in a path is rejected with [crate::Error::SymlinkDenied]
+ */
+"deny" | 
+/**
+ * Symlinks are resolved, but the final path must remain inside the context root
+ */
+"within_root" | 
+/**
+ * Symlinks are resolved and may point anywhere. `..` components still can't climb out of the root.
+ * Contexts opened from JS can only use this if [crate::PluginBuilder::follow_symlinks] was set.
+ */
+"follow"
+/**
+ * A point-in-time copy of a database, created by [crate::Database::snapshot]
+ */
+export type SnapshotInfo = { 
+/**
+ * Path of the snapshot, relative to the context root
+ */
+path: string; 
+/**
+ * When the snapshot was taken
+ */
+created: string }
+/**
+ * Configuration for periodic database snapshots
+ */
+export type SnapshotSchedule = { 
+/**
+ * Directory (relative to the context root) that snapshots are written to
+ */
+directory: string; 
+/**
+ * Seconds between snapshots
+ */
+interval: number; 
+/**
+ * Number of snapshots to keep. Older snapshots are removed after each new one is taken.
+ */
+keep: number }
+/**
+ * Result of a SQL query
+ */
+export type SqlRows = { 
+/**
+ * Column names, in order
+ */
+columns: string[]; 
+/**
+ * Rows, each holding one value per column
+ */
+rows: SqlValue[][] }
+/**
+ * A typed SQL value, used for both statement parameters & result rows
+ */
+export type SqlValue = { type: "null" } | { type: "integer"; value: number } | { type: "real"; value: number } | { type: "text"; value: string } | { type: "blob"; value: number[] }
+/**
+ * Storage engine backing a database
+ */
+export type StorageBackendKind = "polodb" | 
+/**
+ * SQLite, with collections emulated as tables of JSON documents. Requires the `sqlite` feature.
+ */
+"sqlite" | 
+/**
+ * Kept in memory & discarded on close. Opened with [DatabaseSpecifier::Memory] rather than a path.
+ */
+"memory" | 
+/**
+ * Kept in memory & written to a single encrypted file after every change, using a key from the plugin's keyring
+ */
+"encrypted"
+/**
+ * How symbolic links are treated while walking a directory
+ */
+export type SymlinkPolicy = 
+/**
+ * Symlinks are left out of the results entirely
+ */
+"skip" | 
+/**
+ * Symlinks are reported with their own metadata, but never descended into
+ */
+"include" | 
+/**
+ * Symlinks are resolved and descended into, as long as their targets stay inside the context
+ */
+"follow"
 /**
  * Serializable version of [polodb_core::results::UpdateResult]
  */
@@ -396,6 +1134,58 @@ matched: number;
  * How many documents were updated
  */
 modified: number }
+/**
+ * A single entry found while walking a directory
+ */
+export type WalkEntry = { 
+/**
+ * Path of the entry, relative to the walked directory
+ */
+path: string; 
+/**
+ * Depth of the entry, where direct children of the walked directory have a depth of 1
+ */
+depth: number; 
+/**
+ * General info about the entry
+ */
+info: PathInformation; 
+/**
+ * Metadata of the entry
+ */
+metadata: PathMetadata }
+/**
+ * Options controlling a recursive directory walk
+ */
+export type WalkOptions = { 
+/**
+ * Maximum depth to descend to, where direct children of the walked directory have a depth of 1
+ */
+max_depth?: number | null; 
+/**
+ * Glob patterns (relative to the walked directory) that entries must match to be reported. If empty, all entries are reported.
+ */
+include?: string[]; 
+/**
+ * Glob patterns (relative to the walked directory) of entries to skip. Excluded directories are not descended into.
+ */
+exclude?: string[]; 
+/**
+ * How to treat symbolic links
+ */
+symlinks?: SymlinkPolicy }
+/**
+ * Kind of change reported by a context watcher
+ */
+export type WatchEventKind = "create" | "modify" | "remove" | 
+/**
+ * Paths are `[from, to]` when both ends of the rename were observed
+ */
+"rename" | 
+/**
+ * Any other change (permissions, access, or an unclassified event)
+ */
+"other"
 
 /** tauri-specta globals **/
 
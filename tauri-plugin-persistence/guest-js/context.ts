@@ -1,34 +1,107 @@
 import {
+    ArchiveFormat,
     CollectionSpecifier,
     commands,
+    ContextLockOwner,
+    ContextWatchEvent,
     ContextSpecifier,
     DatabaseSpecifier,
+    DocumentFormat,
+    DumpFormat,
+    events,
+    FileHash,
+    FileHandleInfo,
     FileHandleMode,
     FileHandleSpecifier,
+    HashAlgorithm,
     JsonValue,
+    KvEncoding,
+    KvEntry,
+    KvSpecifier,
     OperationCount,
     PathInformation,
     PathMetadata,
+    PathResolution,
+    SnapshotInfo,
+    SnapshotSchedule,
+    StorageBackendKind,
+    DatabaseInfo,
+    SqlValue,
+    SqlRows,
     UpdateResult,
+    WalkEntry,
+    WalkOptions,
 } from "./commands";
 import { Res, Result } from "./util";
+import { Channel } from "@tauri-apps/api/core";
+import { UnlistenFn } from "@tauri-apps/api/event";
+
+function walkOptions(options?: Partial<WalkOptions>): WalkOptions {
+    return {
+        max_depth: options?.max_depth ?? null,
+        include: options?.include ?? [],
+        exclude: options?.exclude ?? [],
+        symlinks: options?.symlinks ?? "skip",
+    };
+}
 
 export class Context {
-    public constructor(public name: string, public path: string) {}
+    public constructor(
+        public name: string,
+        public path: string,
+        public lock: ContextLockOwner | null = null,
+        public resolution: PathResolution = "within_root"
+    ) {}
 
     public get specifier(): ContextSpecifier {
         return { alias: this.name };
     }
 
-    public static async open(name: string, path: string): Res<Context> {
+    public static async open(
+        name: string,
+        path: string,
+        lock?: boolean,
+        resolution?: PathResolution
+    ): Res<Context> {
         return Result.wrap(
-            await commands.context({ alias: name, path })
-        ).and_then((info) => new Context(info.name, info.path));
+            await commands.context({
+                alias: name,
+                path,
+                lock: lock ?? false,
+                resolution: resolution ?? "within_root",
+            })
+        ).and_then(
+            (info) =>
+                new Context(info.name, info.path, info.lock, info.resolution)
+        );
     }
 
     public static async get(name: string): Res<Context> {
         return Result.wrap(await commands.context({ alias: name })).and_then(
-            (info) => new Context(info.name, info.path)
+            (info) =>
+                new Context(info.name, info.path, info.lock, info.resolution)
+        );
+    }
+
+    public static async import(
+        archive: string,
+        path: string,
+        alias: string
+    ): Res<Context> {
+        return Result.wrap(
+            await commands.importContext(archive, path, alias)
+        ).and_then(
+            (info) =>
+                new Context(info.name, info.path, info.lock, info.resolution)
+        );
+    }
+
+    public async export(
+        destination: string,
+        format: ArchiveFormat
+    ): Res<null> {
+        return Result.wrap(
+            await commands.exportContext(this.specifier, destination, format)
         );
     }
 
@@ -58,14 +131,55 @@ export class Context {
         return Result.wrap(await commands.removeFile(this.specifier, path));
     }
 
-    public async database(name: string, path?: string): Res<Database> {
+    public async copy(
+        from: string,
+        to: string,
+        recursive?: boolean,
+        overwrite?: boolean
+    ): Res<null> {
+        return Result.wrap(
+            await commands.copyPath(
+                this.specifier,
+                from,
+                to,
+                recursive ?? false,
+                overwrite ?? false
+            )
+        );
+    }
+
+    public async rename(from: string, to: string): Res<null> {
+        return Result.wrap(await commands.renamePath(this.specifier, from, to));
+    }
+
+    public async database(
+        name: string,
+        path?: string,
+        backend?: StorageBackendKind
+    ): Res<Database> {
         if (path) {
-            return await Database.open(this, name, path);
+            return await Database.open(this, name, path, backend);
         } else {
             return await Database.get(this, name);
         }
     }
 
+    public async memory_database(name: string): Res<Database> {
+        return await Database.memory(this, name);
+    }
+
+    public async kv_store(
+        name: string,
+        path?: string,
+        encoding?: KvEncoding
+    ): Res<KvStore> {
+        if (path) {
+            return await KvStore.open(this, name, path, encoding);
+        } else {
+            return await KvStore.get(this, name);
+        }
+    }
+
     public async open_file(
         path: string,
         mode: FileHandleMode
@@ -77,14 +191,183 @@ export class Context {
         return await FileHandle.get(this, id);
     }
 
+    public async temp_file(): Res<FileHandle> {
+        return Result.wrap(await commands.tempFile(this.specifier)).and_then(
+            (info) => new FileHandle(this, info.id, info.path, info.mode)
+        );
+    }
+
+    public async temp_dir(): Res<string> {
+        return Result.wrap(await commands.tempDir(this.specifier));
+    }
+
+    public async file_handles(): Res<FileHandleInfo[]> {
+        return Result.wrap(await commands.listFileHandles(this.specifier));
+    }
+
+    public async close_all_file_handles(): Res<null> {
+        return Result.wrap(await commands.closeAllFileHandles(this.specifier));
+    }
+
+    public async watch(path: string, recursive?: boolean): Res<string> {
+        return Result.wrap(
+            await commands.watch(this.specifier, path, recursive ?? true)
+        );
+    }
+
+    public async unwatch(id: string): Res<null> {
+        return Result.wrap(await commands.unwatch(this.specifier, id));
+    }
+
+    public async on_change(
+        handler: (event: ContextWatchEvent) => void,
+        watcher?: string
+    ): Promise<UnlistenFn> {
+        return await events.contextWatchEvent.listen(({ payload }) => {
+            if (
+                payload.context === this.name &&
+                (watcher === undefined || payload.watcher === watcher)
+            ) {
+                handler(payload);
+            }
+        });
+    }
+
     public async get_file_metadata(path: string): Res<PathMetadata> {
         return Result.wrap(await commands.fileMetadata(this.specifier, path));
     }
 
+    public async set_permissions(path: string, mode: number): Res<null> {
+        return Result.wrap(
+            await commands.setPermissions(this.specifier, path, mode)
+        );
+    }
+
+    public async set_readonly(path: string, readonly: boolean): Res<null> {
+        return Result.wrap(
+            await commands.setReadonly(this.specifier, path, readonly)
+        );
+    }
+
+    public async hash_file(
+        path: string,
+        algorithm: HashAlgorithm,
+        cache?: Database
+    ): Res<FileHash> {
+        return Result.wrap(
+            await commands.hashFile(
+                this.specifier,
+                path,
+                algorithm,
+                cache?.name ?? null
+            )
+        );
+    }
+
+    public async hash_directory(
+        path: string,
+        algorithm: HashAlgorithm,
+        options?: Partial<WalkOptions>,
+        cache?: Database
+    ): Res<FileHash[]> {
+        return Result.wrap(
+            await commands.hashDirectory(
+                this.specifier,
+                path,
+                algorithm,
+                walkOptions(options),
+                cache?.name ?? null
+            )
+        );
+    }
+
+    public async find_duplicates(
+        path: string,
+        algorithm: HashAlgorithm,
+        options?: Partial<WalkOptions>,
+        cache?: Database
+    ): Res<FileHash[][]> {
+        return Result.wrap(
+            await commands.findDuplicates(
+                this.specifier,
+                path,
+                algorithm,
+                walkOptions(options),
+                cache?.name ?? null
+            )
+        );
+    }
+
+    public async exists(path: string): Res<boolean> {
+        return Result.wrap(await commands.exists(this.specifier, path));
+    }
+
+    public async is_file(path: string): Res<boolean> {
+        return Result.wrap(await commands.isFile(this.specifier, path));
+    }
+
+    public async is_dir(path: string): Res<boolean> {
+        return Result.wrap(await commands.isDir(this.specifier, path));
+    }
+
+    public async stat_many(paths: string[]): Res<(PathMetadata | null)[]> {
+        return Result.wrap(await commands.statMany(this.specifier, paths));
+    }
+
     public async list_directory(path: string): Res<PathInformation[]> {
         return Result.wrap(await commands.listDirectory(this.specifier, path));
     }
 
+    public async walk(
+        path: string,
+        options?: Partial<WalkOptions>
+    ): Res<WalkEntry[]> {
+        return Result.wrap(
+            await commands.walk(this.specifier, path, walkOptions(options))
+        );
+    }
+
+    public async walk_stream(
+        path: string,
+        onEntry: (entry: WalkEntry) => void,
+        options?: Partial<WalkOptions>
+    ): Res<number> {
+        const channel = new Channel<WalkEntry>();
+        channel.onmessage = onEntry;
+        return Result.wrap(
+            await commands.walkStream(
+                this.specifier,
+                path,
+                walkOptions(options),
+                channel
+            )
+        );
+    }
+
+    public async read_document<T = JsonValue>(
+        path: string,
+        format?: DocumentFormat
+    ): Res<T> {
+        return Result.wrap(
+            await commands.readDocument(this.specifier, path, format ?? null)
+        ).and_then((d) => d as T);
+    }
+
+    public async write_document(
+        path: string,
+        document: JsonValue,
+        format?: DocumentFormat
+    ): Res<null> {
+        return Result.wrap(
+            await commands.writeDocument(
+                this.specifier,
+                path,
+                document,
+                format ?? null
+            )
+        );
+    }
+
     public async close(): Res<null> {
         return Result.wrap(await commands.closeContext(this.specifier));
     }
@@ -157,13 +440,11 @@ export class Collection<T extends object> {
         upsert: boolean
     ): Res<UpdateResult> {
         return Result.wrap(
-            await commands.collectionUpdateDocuments(
-                ...this.specifiers,
-                query,
+            await commands.collectionUpdateDocuments(...this.specifiers, query, {
                 update,
                 operations,
-                upsert
-            )
+                upsert,
+            })
         );
     }
 
@@ -222,12 +503,10 @@ export class Collection<T extends object> {
         unique?: boolean
     ): Res<null> {
         return Result.wrap(
-            await commands.collectionCreateIndex(
-                ...this.specifiers,
-                keys,
+            await commands.collectionCreateIndex(...this.specifiers, keys, {
                 name,
-                unique ?? null
-            )
+                unique: unique ?? null,
+            })
         );
     }
 
@@ -265,13 +544,11 @@ export class Collection<T extends object> {
         sort?: JsonValue | null
     ): Res<T[]> {
         return Result.wrap(
-            await commands.collectionFindManyDocuments(
-                ...this.specifiers,
-                filter,
-                skip ?? null,
-                limit ?? null,
-                sort ?? null
-            )
+            await commands.collectionFindManyDocuments(...this.specifiers, filter, {
+                skip: skip ?? null,
+                limit: limit ?? null,
+                sort: sort ?? null,
+            })
         ).and_then((r) => r as T[]);
     }
 }
@@ -280,7 +557,8 @@ export class Database {
     public constructor(
         public parent: Context,
         public name: string,
-        public path: string
+        public path: string,
+        public backend: StorageBackendKind = "polodb"
     ) {}
 
     public get specifier(): DatabaseSpecifier {
@@ -290,17 +568,37 @@ export class Database {
     public static async open(
         context: Context,
         name: string,
-        path: string
+        path: string,
+        backend?: StorageBackendKind
     ): Res<Database> {
         return Result.wrap(
-            await commands.database(context.specifier, { alias: name, path })
-        ).and_then((info) => new Database(context, name, path));
+            await commands.database(context.specifier, {
+                alias: name,
+                path,
+                backend: backend ?? "polodb",
+            })
+        ).and_then(
+            (info) => new Database(context, name, path, info.backend)
+        );
     }
 
     public static async get(context: Context, name: string): Res<Database> {
         return Result.wrap(
             await commands.database(context.specifier, { alias: name })
-        ).and_then((info) => new Database(context, info.name, info.path));
+        ).and_then(
+            (info) => new Database(context, info.name, info.path, info.backend)
+        );
+    }
+
+    public static async memory(context: Context, name: string): Res<Database> {
+        return Result.wrap(
+            await commands.database(context.specifier, {
+                alias: name,
+                memory: true,
+            })
+        ).and_then(
+            (info) => new Database(context, info.name, info.path, info.backend)
+        );
     }
 
     public collection<T extends object>(name: string): Collection<T> {
@@ -330,6 +628,263 @@ export class Database {
     public transaction(id: string): Transaction {
         return new Transaction(this, this.parent, id);
     }
+
+    public async backup(path: string, overwrite?: boolean): Res<null> {
+        return Result.wrap(
+            await commands.databaseBackup(
+                this.parent.specifier,
+                this.specifier,
+                path,
+                overwrite ?? false
+            )
+        );
+    }
+
+    public async restore(path: string): Res<null> {
+        return Result.wrap(
+            await commands.databaseRestore(
+                this.parent.specifier,
+                this.specifier,
+                path
+            )
+        );
+    }
+
+    public async persist(
+        path: string,
+        backend?: StorageBackendKind
+    ): Res<Database> {
+        return Result.wrap(
+            await commands.databasePersist(
+                this.parent.specifier,
+                this.specifier,
+                path,
+                backend ?? "polodb"
+            )
+        ).and_then(((info: DatabaseInfo) => {
+            this.path = info.path;
+            this.backend = info.backend;
+            return this;
+        }).bind(this));
+    }
+
+    public async snapshot(directory: string, keep?: number): Res<SnapshotInfo> {
+        return Result.wrap(
+            await commands.databaseSnapshot(
+                this.parent.specifier,
+                this.specifier,
+                directory,
+                keep ?? null
+            )
+        );
+    }
+
+    public async snapshots(directory: string): Res<SnapshotInfo[]> {
+        return Result.wrap(
+            await commands.databaseListSnapshots(
+                this.parent.specifier,
+                this.specifier,
+                directory
+            )
+        );
+    }
+
+    public async schedule_snapshots(
+        schedule: SnapshotSchedule | null
+    ): Res<null> {
+        return Result.wrap(
+            await commands.databaseScheduleSnapshots(
+                this.parent.specifier,
+                this.specifier,
+                schedule
+            )
+        );
+    }
+
+    public async dump(directory: string, format?: DumpFormat): Res<null> {
+        return Result.wrap(
+            await commands.databaseDump(
+                this.parent.specifier,
+                this.specifier,
+                directory,
+                format ?? "json"
+            )
+        );
+    }
+
+    public async restore_dump(directory: string): Res<null> {
+        return Result.wrap(
+            await commands.databaseRestoreDump(
+                this.parent.specifier,
+                this.specifier,
+                directory
+            )
+        );
+    }
+
+    public async execute_sql(sql: string, params?: SqlValue[]): Res<number> {
+        return Result.wrap(
+            await commands.databaseExecuteSql(
+                this.parent.specifier,
+                this.specifier,
+                sql,
+                params ?? []
+            )
+        );
+    }
+
+    public async query_sql(sql: string, params?: SqlValue[]): Res<SqlRows> {
+        return Result.wrap(
+            await commands.databaseQuerySql(
+                this.parent.specifier,
+                this.specifier,
+                sql,
+                params ?? []
+            )
+        );
+    }
+}
+
+export class KvStore {
+    public constructor(
+        public parent: Context,
+        public name: string,
+        public path: string,
+        public encoding: KvEncoding = "json",
+        public transaction: string | null = null
+    ) {}
+
+    public get specifier(): KvSpecifier {
+        return { alias: this.name };
+    }
+
+    public static async open(
+        context: Context,
+        name: string,
+        path: string,
+        encoding?: KvEncoding
+    ): Res<KvStore> {
+        return Result.wrap(
+            await commands.kvStore(context.specifier, {
+                alias: name,
+                path,
+                encoding: encoding ?? "json",
+            })
+        ).and_then((info) => new KvStore(context, name, path, info.encoding));
+    }
+
+    public static async get(context: Context, name: string): Res<KvStore> {
+        return Result.wrap(
+            await commands.kvStore(context.specifier, { alias: name })
+        ).and_then(
+            (info) => new KvStore(context, info.name, info.path, info.encoding)
+        );
+    }
+
+    public async close(): Res<null> {
+        return Result.wrap(
+            await commands.kvClose(this.parent.specifier, this.specifier)
+        );
+    }
+
+    public async get<T = JsonValue>(key: string): Res<T | null> {
+        return Result.wrap(
+            await commands.kvGet(
+                this.parent.specifier,
+                this.specifier,
+                key,
+                this.transaction
+            )
+        ).and_then((value) => value as T | null);
+    }
+
+    public async set<T = JsonValue>(key: string, value: T): Res<null> {
+        return Result.wrap(
+            await commands.kvSet(
+                this.parent.specifier,
+                this.specifier,
+                key,
+                value as JsonValue,
+                this.transaction
+            )
+        );
+    }
+
+    public async delete(key: string): Res<boolean> {
+        return Result.wrap(
+            await commands.kvDelete(
+                this.parent.specifier,
+                this.specifier,
+                key,
+                this.transaction
+            )
+        );
+    }
+
+    public async list<T = JsonValue>(prefix?: string): Res<KvEntry<T>[]> {
+        return Result.wrap(
+            await commands.kvList(
+                this.parent.specifier,
+                this.specifier,
+                prefix ?? "",
+                this.transaction
+            )
+        ).and_then((entries) => entries as KvEntry<T>[]);
+    }
+
+    public async compare_and_swap<T = JsonValue>(
+        key: string,
+        expected: T | null,
+        value: T | null
+    ): Res<boolean> {
+        return Result.wrap(
+            await commands.kvCompareAndSwap(
+                this.parent.specifier,
+                this.specifier,
+                key,
+                {
+                    expected: expected as JsonValue | null,
+                    value: value as JsonValue | null,
+                },
+                this.transaction
+            )
+        );
+    }
+
+    public async start_transaction(): Res<KvTransaction> {
+        return Result.wrap(
+            await commands.kvStartTransaction(
+                this.parent.specifier,
+                this.specifier
+            )
+        ).and_then(((id: string) => new KvTransaction(this, id)).bind(this));
+    }
+}
+
+export class KvTransaction extends KvStore {
+    public constructor(store: KvStore, public id: string) {
+        super(store.parent, store.name, store.path, store.encoding, id);
+    }
+
+    public async commit(): Res<null> {
+        return Result.wrap(
+            await commands.kvCommitTransaction(
+                this.parent.specifier,
+                this.specifier,
+                this.id
+            )
+        );
+    }
+
+    public async rollback(): Res<null> {
+        return Result.wrap(
+            await commands.kvRollbackTransaction(
+                this.parent.specifier,
+                this.specifier,
+                this.id
+            )
+        );
+    }
 }
 
 export class FileHandle {
@@ -405,4 +960,34 @@ export class FileHandle {
             )
         );
     }
+
+    public async flush(): Res<null> {
+        return Result.wrap(
+            await commands.fileFlush(this.parent.specifier, this.specifier)
+        );
+    }
+
+    public async sync(): Res<null> {
+        return Result.wrap(
+            await commands.fileSync(this.parent.specifier, this.specifier)
+        );
+    }
+
+    public async lock_shared(): Res<null> {
+        return Result.wrap(
+            await commands.fileLock(this.parent.specifier, this.specifier, false)
+        );
+    }
+
+    public async lock_exclusive(): Res<null> {
+        return Result.wrap(
+            await commands.fileLock(this.parent.specifier, this.specifier, true)
+        );
+    }
+
+    public async unlock(): Res<null> {
+        return Result.wrap(
+            await commands.fileUnlock(this.parent.specifier, this.specifier)
+        );
+    }
 }
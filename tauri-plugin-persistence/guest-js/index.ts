@@ -5,25 +5,84 @@ import {
     Collection,
     Transaction,
     FileHandle,
+    KvStore,
+    KvTransaction,
 } from "./context";
 import {
     JsonValue,
     FileHandleMode,
     UpdateResult,
     Error,
+    DatabaseErrorCode,
+    IoErrorKind,
     PathFileType,
     PathInformation,
     PathMetadata,
+    DocumentFormat,
+    ContextLockOwner,
+    FileHandleInfo,
+    SymlinkPolicy,
+    WalkOptions,
+    WalkEntry,
+    WatchEventKind,
+    ContextWatchEvent,
+    PathResolution,
+    HashAlgorithm,
+    FileCompression,
+    FileHash,
+    ArchiveFormat,
+    SnapshotInfo,
+    SnapshotSchedule,
+    DumpFormat,
+    StorageBackendKind,
+    SqlValue,
+    SqlRows,
+    KvEncoding,
+    KvEntry,
+    KvStoreInfo,
 } from "./commands";
 
-export { Result, Context, Database, Collection, Transaction, FileHandle };
+export {
+    Result,
+    Context,
+    Database,
+    Collection,
+    Transaction,
+    FileHandle,
+    KvStore,
+    KvTransaction,
+};
 export type {
     Res,
     JsonValue,
     FileHandleMode,
     UpdateResult,
     Error,
+    DatabaseErrorCode,
+    IoErrorKind,
     PathFileType,
     PathInformation,
     PathMetadata,
+    DocumentFormat,
+    ContextLockOwner,
+    FileHandleInfo,
+    SymlinkPolicy,
+    WalkOptions,
+    WalkEntry,
+    WatchEventKind,
+    ContextWatchEvent,
+    PathResolution,
+    HashAlgorithm,
+    FileCompression,
+    FileHash,
+    ArchiveFormat,
+    SnapshotInfo,
+    SnapshotSchedule,
+    DumpFormat,
+    StorageBackendKind,
+    SqlValue,
+    SqlRows,
+    KvEncoding,
+    KvEntry,
+    KvStoreInfo,
 };
@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{resolve::relative_path, types::ArchiveFormat};
+
+impl ArchiveFormat {
+    /// Detects the format of an existing archive from its magic bytes.
+    pub fn detect(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let mut magic = [0u8; 4];
+        let mut file = File::open(path.as_ref()).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        let read = file.read(&mut magic).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        match &magic[..read] {
+            [b'P', b'K', 3, 4] | [b'P', b'K', 5, 6] => Ok(Self::Zip),
+            [0x1f, 0x8b, ..] => Ok(Self::TarGz),
+            _ => Err(crate::Error::archive(format!("Unrecognized archive format: {}", path.as_ref().to_string_lossy()))),
+        }
+    }
+}
+
+/// Packs every regular file & directory under `root` into `destination`. Symlinks, and any entries rejected by `include`, are skipped.
+pub(crate) fn pack(root: &Path, destination: &Path, format: ArchiveFormat, include: impl Fn(&str) -> bool) -> crate::Result<()> {
+    let entries = collect_entries(root, &include).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+    let file = File::create(destination).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+
+    let result = match format {
+        ArchiveFormat::Zip => pack_zip(file, &entries),
+        ArchiveFormat::TarGz => pack_tar_gz(file, &entries),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(destination);
+    }
+
+    result
+}
+
+/// Unpacks `archive` entry by entry. Every entry name is passed through `resolve` (which should perform the context's path
+/// validation) before anything is written, so entries like `../../evil` or absolute paths are rejected. Links & special files are skipped.
+pub(crate) fn unpack(archive: &Path, resolve: impl Fn(&str) -> crate::Result<PathBuf>) -> crate::Result<()> {
+    match ArchiveFormat::detect(archive)? {
+        ArchiveFormat::Zip => unpack_zip(archive, resolve),
+        ArchiveFormat::TarGz => unpack_tar_gz(archive, resolve),
+    }
+}
+
+struct PackEntry {
+    name: String,
+    path: PathBuf,
+    directory: bool,
+}
+
+fn collect_entries(root: &Path, include: &impl Fn(&str) -> bool) -> std::io::Result<Vec<PackEntry>> {
+    let mut entries: Vec<PackEntry> = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        for entry in std::fs::read_dir(&directory)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(name) = relative_path(&path, root) else {
+                continue;
+            };
+            if !include(&name) {
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path.clone());
+                entries.push(PackEntry { name, path, directory: true });
+            } else if file_type.is_file() {
+                entries.push(PackEntry { name, path, directory: false });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn pack_zip(file: File, entries: &[PackEntry]) -> crate::Result<()> {
+    let mut writer = ZipWriter::new(file);
+    for entry in entries {
+        let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated).large_file(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(meta) = std::fs::metadata(&entry.path) {
+                options = options.unix_permissions(meta.permissions().mode() & 0o777);
+            }
+        }
+
+        if entry.directory {
+            writer.add_directory(format!("{}/", entry.name), options).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        } else {
+            writer.start_file(entry.name.clone(), options).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+            let mut source = File::open(&entry.path).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+            std::io::copy(&mut source, &mut writer).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        }
+    }
+
+    writer
+        .finish()
+        .and_then(|mut file| Ok(file.flush()?))
+        .or_else(|e| Err(crate::Error::archive(e.to_string())))
+}
+
+fn pack_tar_gz(file: File, entries: &[PackEntry]) -> crate::Result<()> {
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.follow_symlinks(false);
+    for entry in entries {
+        let appended = if entry.directory {
+            builder.append_dir(&entry.name, &entry.path)
+        } else {
+            builder.append_path_with_name(&entry.path, &entry.name)
+        };
+        appended.or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut file| file.flush())
+        .or_else(|e| Err(crate::Error::archive(e.to_string())))
+}
+
+fn unpack_zip(archive: &Path, resolve: impl Fn(&str) -> crate::Result<PathBuf>) -> crate::Result<()> {
+    let file = File::open(archive).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+    let mut zip = ZipArchive::new(file).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        if entry.is_symlink() {
+            continue;
+        }
+
+        let target = resolve(entry.name())?;
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        }
+        let mut output = File::create(&target).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        std::io::copy(&mut entry, &mut output).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o777));
+        }
+    }
+
+    Ok(())
+}
+
+fn unpack_tar_gz(archive: &Path, resolve: impl Fn(&str) -> crate::Result<PathBuf>) -> crate::Result<()> {
+    let file = File::open(archive).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    for entry in tar.entries().or_else(|e| Err(crate::Error::archive(e.to_string())))? {
+        let mut entry = entry.or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        let name = entry.path().or_else(|e| Err(crate::Error::archive(e.to_string())))?.to_string_lossy().to_string();
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_dir() {
+            std::fs::create_dir_all(resolve(&name)?).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        } else if entry_type.is_file() {
+            let target = resolve(&name)?;
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+            }
+            entry.unpack(&target).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        }
+    }
+
+    Ok(())
+}
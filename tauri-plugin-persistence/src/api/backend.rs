@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+
+use bson::{Bson, Document};
+use polodb_core::{
+    options::UpdateOptions,
+    results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult},
+    CollectionT, IndexModel,
+};
+
+use super::{crypto::{EncryptedBackend, EncryptionKey}, types::{SqlRows, SqlValue, StorageBackendKind}};
+
+/// Options applied to [DocumentCollection::find]
+#[derive(Clone, Debug, Default)]
+pub struct FindOptions {
+    pub skip: Option<u64>,
+    pub limit: Option<u64>,
+    pub sort: Option<Document>,
+}
+
+/// Name an index is stored under: its explicit name if set, otherwise PoloDB's default naming scheme (`<key>_<direction>`,
+/// with `.` replaced by `_`), extended to every key so compound & descending indexes get distinct names (ie `a_1_b_-1`).
+pub fn index_name(index: &IndexModel) -> String {
+    match index.options.as_ref().and_then(|o| o.name.clone()) {
+        Some(name) => name,
+        None => index
+            .keys
+            .iter()
+            .map(|(key, direction)| {
+                let direction = match direction {
+                    Bson::Int32(value) => value.to_string(),
+                    Bson::Int64(value) => value.to_string(),
+                    Bson::Double(value) => (*value as i64).to_string(),
+                    Bson::String(value) => value.clone(),
+                    other => other.to_string(),
+                };
+                format!("{}_{direction}", key.replace('.', "_"))
+            })
+            .collect::<Vec<String>>()
+            .join("_"),
+    }
+}
+
+/// A storage engine that databases can be opened with.
+pub trait StorageBackend: Send + Sync {
+    /// Opens the store at `path`, creating it if it doesn't exist yet.
+    fn open(&self, path: &Path) -> crate::Result<Box<dyn DocumentStore>>;
+}
+
+/// An open database, holding any number of named document collections.
+pub trait DocumentStore: Send + Sync {
+    fn collection_names(&self) -> crate::Result<Vec<String>>;
+
+    /// Returns a handle to the named collection. Collections that don't exist yet are created on first write.
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection>;
+
+    fn start_transaction(&self) -> crate::Result<Box<dyn StoreTransaction>>;
+
+    /// Writes a consistent copy of the store to `destination`, which doesn't exist yet. Callers ensure no writes happen concurrently.
+    fn backup(&self, destination: &Path) -> crate::Result<()>;
+
+    /// Raw SQL access, for backends that support it
+    fn sql(&self) -> Option<&dyn SqlStore> {
+        None
+    }
+}
+
+/// Raw SQL access to a store. Parameters are bound positionally (`?1`, `?2`, ...).
+pub trait SqlStore: Send + Sync {
+    /// Executes one statement, returning the number of rows changed.
+    fn execute(&self, sql: &str, params: Vec<SqlValue>) -> crate::Result<usize>;
+
+    /// Runs a query, returning every row it produces.
+    fn query(&self, sql: &str, params: Vec<SqlValue>) -> crate::Result<SqlRows>;
+}
+
+/// An open transaction. Changes made through its collections are only visible to others once committed.
+pub trait StoreTransaction: Send + Sync {
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection>;
+
+    fn commit(&self) -> crate::Result<()>;
+
+    fn rollback(&self) -> crate::Result<()>;
+}
+
+/// CRUD & index operations on a single collection, with MongoDB-style queries & updates.
+pub trait DocumentCollection: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn count_documents(&self) -> crate::Result<u64>;
+
+    fn update_one(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult>;
+
+    fn update_many(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult>;
+
+    fn delete_one(&self, query: Document) -> crate::Result<DeleteResult>;
+
+    fn delete_many(&self, query: Document) -> crate::Result<DeleteResult>;
+
+    fn create_index(&self, index: IndexModel) -> crate::Result<()>;
+
+    fn drop_index(&self, name: &str) -> crate::Result<()>;
+
+    fn drop(&self) -> crate::Result<()>;
+
+    fn insert_one(&self, document: Document) -> crate::Result<InsertOneResult>;
+
+    fn insert_many(&self, documents: Vec<Document>) -> crate::Result<InsertManyResult>;
+
+    fn find(&self, filter: Document, options: FindOptions) -> crate::Result<Vec<Document>>;
+
+    fn find_one(&self, filter: Document) -> crate::Result<Option<Document>>;
+}
+
+impl StorageBackendKind {
+    /// Returns the implementation of this backend, if it was compiled in.
+    pub fn backend(&self) -> crate::Result<&'static dyn StorageBackend> {
+        match self {
+            Self::Polodb => Ok(&PoloBackend),
+            Self::Memory => Ok(&super::memory::MemoryBackend),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => Ok(&super::sqlite::SqliteBackend),
+            #[cfg(not(feature = "sqlite"))]
+            Self::Sqlite => Err(crate::Error::database_code(crate::DatabaseErrorCode::Unsupported, "The SQLite backend requires the `sqlite` feature.")),
+            Self::Encrypted => Err(crate::Error::encryption("Encrypted databases need a key from the keyring.")),
+        }
+    }
+
+    /// Whether stores of this kind keep rewriting their files while open, like RocksDB's background flushes & compactions.
+    /// Their files are only consistent once the store is closed, so they're closed before being copied.
+    pub(crate) fn rewrites_while_open(&self) -> bool {
+        matches!(self, Self::Polodb)
+    }
+
+    /// Opens a store at `path`. `key` is required by [StorageBackendKind::Encrypted] & ignored by every other backend.
+    pub fn open(&self, path: &Path, key: Option<EncryptionKey>) -> crate::Result<Box<dyn DocumentStore>> {
+        match (self, key) {
+            (Self::Encrypted, Some(key)) => EncryptedBackend { key }.open(path),
+            _ => self.backend()?.open(path),
+        }
+    }
+}
+
+/// [StorageBackend] implementation for PoloDB
+pub struct PoloBackend;
+
+impl StorageBackend for PoloBackend {
+    fn open(&self, path: &Path) -> crate::Result<Box<dyn DocumentStore>> {
+        Ok(Box::new(PoloStore { database: polodb_core::Database::open_path(path)?, path: path.to_path_buf() }))
+    }
+}
+
+struct PoloStore {
+    database: polodb_core::Database,
+    path: PathBuf,
+}
+
+impl DocumentStore for PoloStore {
+    fn collection_names(&self) -> crate::Result<Vec<String>> {
+        Ok(self.database.list_collection_names()?)
+    }
+
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(PoloCollection(self.database.collection::<Document>(name)))
+    }
+
+    fn start_transaction(&self) -> crate::Result<Box<dyn StoreTransaction>> {
+        Ok(Box::new(PoloTransaction(self.database.start_transaction()?)))
+    }
+
+    /// RocksDB flushes & compacts in the background while the database is open, so a live copy can be torn. Callers close the
+    /// store & copy its files with [copy_files] instead, see [StorageBackendKind::rewrites_while_open].
+    fn backup(&self, _destination: &Path) -> crate::Result<()> {
+        Err(crate::Error::database_code(crate::DatabaseErrorCode::Unsupported, format!("PoloDB database at {} must be closed to be copied.", self.path.display())))
+    }
+}
+
+pub(crate) fn copy_files(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if !source.is_dir() {
+        return std::fs::copy(source, destination).and(Ok(()));
+    }
+
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() || file_type.is_file() {
+            copy_files(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
+
+struct PoloTransaction(polodb_core::Transaction);
+
+impl StoreTransaction for PoloTransaction {
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(PoloCollection(self.0.collection::<Document>(name)))
+    }
+
+    fn commit(&self) -> crate::Result<()> {
+        Ok(self.0.commit()?)
+    }
+
+    fn rollback(&self) -> crate::Result<()> {
+        Ok(self.0.rollback()?)
+    }
+}
+
+/// Wraps either a standalone or a transactional PoloDB collection
+struct PoloCollection<C: CollectionT<Document>>(C);
+
+impl<C: CollectionT<Document> + Send + Sync> DocumentCollection for PoloCollection<C> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn count_documents(&self) -> crate::Result<u64> {
+        Ok(self.0.count_documents()?)
+    }
+
+    fn update_one(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        Ok(match options {
+            Some(options) => self.0.update_one_with_options(query, update, options),
+            None => self.0.update_one(query, update),
+        }?)
+    }
+
+    fn update_many(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        Ok(match options {
+            Some(options) => self.0.update_many_with_options(query, update, options),
+            None => self.0.update_many(query, update),
+        }?)
+    }
+
+    fn delete_one(&self, query: Document) -> crate::Result<DeleteResult> {
+        Ok(self.0.delete_one(query)?)
+    }
+
+    fn delete_many(&self, query: Document) -> crate::Result<DeleteResult> {
+        Ok(self.0.delete_many(query)?)
+    }
+
+    fn create_index(&self, index: IndexModel) -> crate::Result<()> {
+        Ok(self.0.create_index(index)?)
+    }
+
+    fn drop_index(&self, name: &str) -> crate::Result<()> {
+        Ok(self.0.drop_index(name)?)
+    }
+
+    fn drop(&self) -> crate::Result<()> {
+        Ok(self.0.drop()?)
+    }
+
+    fn insert_one(&self, document: Document) -> crate::Result<InsertOneResult> {
+        Ok(self.0.insert_one(document)?)
+    }
+
+    fn insert_many(&self, documents: Vec<Document>) -> crate::Result<InsertManyResult> {
+        Ok(self.0.insert_many(documents)?)
+    }
+
+    fn find(&self, filter: Document, options: FindOptions) -> crate::Result<Vec<Document>> {
+        let mut find = self.0.find(filter);
+        if let Some(skip) = options.skip {
+            find = find.skip(skip);
+        }
+
+        if let Some(limit) = options.limit {
+            find = find.limit(limit);
+        }
+
+        if let Some(sort) = options.sort {
+            find = find.sort(sort);
+        }
+
+        let mut documents: Vec<Document> = Vec::new();
+        for document in find.run()? {
+            documents.push(document?);
+        }
+
+        Ok(documents)
+    }
+
+    fn find_one(&self, filter: Document) -> crate::Result<Option<Document>> {
+        Ok(self.0.find_one(filter)?)
+    }
+}
@@ -0,0 +1,156 @@
+//! Streaming compression for file handles. Data is pushed through in arbitrary pieces, & whatever is ready is handed back to be written or read.
+
+use std::io::Write;
+
+use flate2::write::{GzEncoder, MultiGzDecoder};
+
+use super::types::FileCompression;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+impl FileCompression {
+    /// Detects the format from the first bytes of a file. Needs at least 4 bytes to recognize zstd.
+    pub fn from_magic(data: &[u8]) -> Option<Self> {
+        if data.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+pub enum Compressor {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Compressor {
+    pub fn new(compression: FileCompression) -> crate::Result<Self> {
+        Ok(match compression {
+            FileCompression::Gzip => Self::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::default())),
+            FileCompression::Zstd => Self::Zstd(zstd::stream::write::Encoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)?),
+        })
+    }
+
+    /// Compresses `data`, returning any compressed output that's ready
+    pub fn push(&mut self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                std::mem::take(encoder.get_mut())
+            }
+            Self::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                std::mem::take(encoder.get_mut())
+            }
+        })
+    }
+
+    /// Flushes the encoder, returning everything needed to decompress the data pushed so far. The stream stays open.
+    pub fn flush(&mut self) -> crate::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Gzip(encoder) => {
+                encoder.flush()?;
+                std::mem::take(encoder.get_mut())
+            }
+            Self::Zstd(encoder) => {
+                encoder.flush()?;
+                std::mem::take(encoder.get_mut())
+            }
+        })
+    }
+
+    /// Ends the stream, returning the remaining compressed output
+    pub fn finish(self) -> crate::Result<Vec<u8>> {
+        Ok(match self {
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+enum DecoderState {
+    /// Waiting for enough data to detect the format
+    Detecting(Vec<u8>),
+    Plain,
+    Gzip(MultiGzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+/// Decompresses gzip or zstd data, detected by magic bytes. Anything else is passed through unchanged.
+/// Concatenated streams (e.g. from appending to a compressed file) are decompressed as one.
+pub struct Decompressor {
+    state: DecoderState,
+    output: Vec<u8>,
+    finished: bool,
+}
+
+impl Decompressor {
+    pub fn new() -> Self {
+        Self { state: DecoderState::Detecting(Vec::new()), output: Vec::new(), finished: false }
+    }
+
+    /// The detected format, once enough data has been pushed
+    pub fn compression(&self) -> Option<FileCompression> {
+        match self.state {
+            DecoderState::Gzip(_) => Some(FileCompression::Gzip),
+            DecoderState::Zstd(_) => Some(FileCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn available(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Decompresses `data`. `end` marks the end of the stream, flushing whatever is left.
+    pub fn push(&mut self, data: &[u8], end: bool) -> crate::Result<()> {
+        let mut data = data.to_vec();
+        if let DecoderState::Detecting(buffer) = &mut self.state {
+            buffer.extend(data);
+            if buffer.len() < ZSTD_MAGIC.len() && !end {
+                return Ok(());
+            }
+            data = std::mem::take(buffer);
+            self.state = match FileCompression::from_magic(&data) {
+                Some(FileCompression::Gzip) => DecoderState::Gzip(MultiGzDecoder::new(Vec::new())),
+                Some(FileCompression::Zstd) => DecoderState::Zstd(zstd::stream::write::Decoder::new(Vec::new())?),
+                None => DecoderState::Plain,
+            };
+        }
+
+        match &mut self.state {
+            DecoderState::Plain => self.output.extend(data),
+            DecoderState::Gzip(decoder) => {
+                decoder.write_all(&data)?;
+                if end {
+                    decoder.try_finish()?;
+                }
+                self.output.extend(std::mem::take(decoder.get_mut()));
+            }
+            DecoderState::Zstd(decoder) => {
+                decoder.write_all(&data)?;
+                if end {
+                    decoder.flush()?;
+                }
+                self.output.extend(std::mem::take(decoder.get_mut()));
+            }
+            DecoderState::Detecting(_) => (),
+        }
+        self.finished = end;
+        Ok(())
+    }
+
+    /// Takes up to `size` bytes of decompressed data, or all of it
+    pub fn take(&mut self, size: Option<usize>) -> Vec<u8> {
+        let size = size.unwrap_or(self.output.len()).min(self.output.len());
+        self.output.drain(..size).collect()
+    }
+}
@@ -1,17 +1,22 @@
-use std::{borrow::Borrow, collections::HashMap, marker::PhantomData, ops::Deref, path::PathBuf, str::FromStr, sync::Arc};
+use std::{borrow::{Borrow, Cow}, collections::{HashMap, HashSet}, marker::PhantomData, ops::Deref, path::{Path, PathBuf}, str::FromStr, sync::Arc, time::Duration};
 
 use bson::Document;
-use polodb_core::{options::UpdateOptions, results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult}, CollectionT, IndexModel};
-use serde::{de::DeserializeOwned, Serialize};
+use fs4::tokio::AsyncFileExt;
+use notify_debouncer_full::{new_debouncer, notify::{event::ModifyKind, EventKind, RecursiveMode}, DebounceEventResult};
+use polodb_core::{options::UpdateOptions, results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult}, IndexModel, IndexOptions};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Digest;
 use tauri::{AppHandle, Manager, Runtime};
-use tokio::{fs::{File, OpenOptions}, sync::Mutex};
+use tauri_specta::Event;
+use tokio::{fs::{File, OpenOptions}, io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt}, sync::{Mutex, OwnedMutexGuard}, time::MissedTickBehavior};
 
-use super::{state::{ContextDB, ContextFileHandle, ContextState, FileHandleMode, PluginState}, types::{PathInformation, PathMetadata}};
+use super::{archive, error::{DatabaseErrorCode, IoErrorKind}, compression::{Compressor, Decompressor}, backend::{copy_files, index_name, DocumentCollection, DocumentStore, FindOptions, StorageBackend}, crypto::{EncryptionKey, KeyRequest, StreamReader, StreamWriter, SEALED_CHUNK_SIZE}, kv::KvDatabase, memory::MemoryBackend, resolve::{canonicalize_nearest, relative_path, resolve_path}, state::{ContextDB, ContextFileHandle, ContextKv, ContextLock, FileCipher, FileCodec, KeyringState, ContextState, ContextWatcher, FileHandleMode, PluginState}, types::{ArchiveFormat, ContextLockOwner, DumpFormat, KvEncoding, KvEntry, StorageBackendKind, SnapshotInfo, SnapshotSchedule, SqlRows, SqlValue, ContextWatchEvent, FileHash, HashAlgorithm, PathFileType, PathResolution, WatchEventKind, DocumentFormat, FileHandleInfo, PathInformation, PathMetadata, SymlinkPolicy, WalkEntry, WalkOptions}};
 
 pub struct Context<R: Runtime> {
     handle: Arc<AppHandle<R>>,
     name: String,
     path: String,
+    resolution: PathResolution,
 }
 
 impl<R: Runtime> Clone for Context<R> {
@@ -20,16 +25,24 @@ impl<R: Runtime> Clone for Context<R> {
             handle: self.handle.clone(),
             name: self.name.clone(),
             path: self.path.clone(),
+            resolution: self.resolution,
         }
     }
 }
 
 impl<R: Runtime> Context<R> {
-    pub(crate) fn create(handle: AppHandle<R>, name: String, path: String) -> Self {
+    /// Reserved directory (relative to the context root) containing temporary files & directories
+    pub const TEMPORARY_DIRECTORY: &'static str = ".tmp";
+
+    /// Delay (in milliseconds) used to debounce filesystem events from watchers
+    pub const WATCH_DEBOUNCE_MS: u64 = 250;
+
+    pub(crate) fn create(handle: AppHandle<R>, name: String, path: String, resolution: PathResolution) -> Self {
         Self {
             handle: Arc::new(handle),
             name,
             path,
+            resolution,
         }
     }
 
@@ -41,106 +54,527 @@ impl<R: Runtime> Context<R> {
         self.path.clone()
     }
 
+    pub fn resolution(&self) -> PathResolution {
+        self.resolution
+    }
+
     pub(crate) fn handle(&self) -> AppHandle<R> {
         self.handle.clone().deref().clone()
     }
 
-    pub fn base_path(&self) -> PathBuf {
-        PathBuf::from_str(&self.path()).unwrap()
+    pub fn base_path(&self) -> crate::Result<PathBuf> {
+        PathBuf::from_str(&self.path()).or(Err(crate::Error::invalid_path(self.path())))
     }
 
     pub fn get_path(&self, path: impl AsRef<str>) -> crate::Result<PathBuf> {
-        let resolved = PathBuf::from_str(path.as_ref()).unwrap();
-        if resolved.is_absolute() {
-            return Err(crate::Error::no_absolute_path(path.as_ref()));
-        }
-        let joined = self
-            .base_path()
-            .canonicalize().or(Err(crate::Error::invalid_path(path.as_ref())))?
-            .join(&resolved);
-
-        if !joined.starts_with(self.base_path_canonicalized()?) {
-            return Err(crate::Error::path_escapes_context(path.as_ref()));
-        }
-
-        Ok(joined)
+        let requested = PathBuf::from_str(path.as_ref()).or(Err(crate::Error::invalid_path(path.as_ref())))?;
+        let base = self.base_path()?.canonicalize().or(Err(crate::Error::invalid_path(path.as_ref())))?;
+        resolve_path(&base, &requested, self.resolution).or_else(|e| Err(e.into_error(path.as_ref())))
     }
 
     pub fn base_path_canonicalized(&self) -> crate::Result<PathBuf> {
-        PathBuf::from_str(&self.path()).unwrap().canonicalize().or_else(|_| Err(crate::Error::invalid_path(self.path())))
+        self.base_path()?.canonicalize().or_else(|_| Err(crate::Error::invalid_path(self.path())))
     }
 
     pub async fn create_directory(&self, path: impl AsRef<str>, parents: bool) -> crate::Result<()> {
-        let resolved = self.get_path(path)?;
+        let resolved = self.get_path(&path)?;
         let create_result = if parents {tokio::fs::create_dir_all(&resolved).await} else {tokio::fs::create_dir(&resolved).await};
         if let Err(error) = create_result {
-            return Err(crate::Error::filesystem("CREATE_DIRECTORY", error.to_string()));
+            return Err(crate::Error::filesystem_io("CREATE_DIRECTORY", path.as_ref(), &error));
         }
 
         Ok(())
     }
 
     pub async fn remove_directory(&self, path: impl AsRef<str>) -> crate::Result<()> {
-        let resolved = self.get_path(path)?;
+        let resolved = self.get_path(&path)?;
         if !resolved.is_dir() {
-            return Err(crate::Error::filesystem("REMOVE_DIRECTORY", "Specified path is not a directory or does not exist."));
+            return Err(crate::Error::filesystem_at("REMOVE_DIRECTORY", path.as_ref(), IoErrorKind::NotFound, "Specified path is not a directory or does not exist."));
         }
-        tokio::fs::remove_dir_all(resolved).await.or_else(|error| Err(crate::Error::filesystem("REMOVE_DIRECTORY", error.to_string())))?;
+        tokio::fs::remove_dir_all(resolved).await.or_else(|error| Err(crate::Error::filesystem_io("REMOVE_DIRECTORY", path.as_ref(), &error)))?;
         Ok(())
     }
 
     pub async fn remove_file(&self, path: impl AsRef<str>) -> crate::Result<()> {
-        let resolved = self.get_path(path)?;
+        let resolved = self.get_path(&path)?;
         if !resolved.is_file() {
-            return Err(crate::Error::filesystem("REMOVE_FILE", "Specified path is not a file or does not exist."));
+            return Err(crate::Error::filesystem_at("REMOVE_FILE", path.as_ref(), IoErrorKind::NotFound, "Specified path is not a file or does not exist."));
         }
-        tokio::fs::remove_file(resolved).await.or_else(|error| Err(crate::Error::filesystem("REMOVE_FILE", error.to_string())))?;
+        tokio::fs::remove_file(resolved).await.or_else(|error| Err(crate::Error::filesystem_io("REMOVE_FILE", path.as_ref(), &error)))?;
         Ok(())
     }
 
+    /// Like [Context::get_path], but the final component is left unresolved, so a symlink there is returned as-is rather than followed.
+    fn get_link_path(&self, path: impl AsRef<str>) -> crate::Result<PathBuf> {
+        let requested = PathBuf::from_str(path.as_ref()).or(Err(crate::Error::invalid_path(path.as_ref())))?;
+        match (requested.parent(), requested.file_name()) {
+            (Some(parent), Some(name)) => Ok(self.get_path(parent.to_string_lossy())?.join(name)),
+            _ => self.get_path(path)
+        }
+    }
+
+    /// Returns metadata for a path without following it, so symlinks are reported as such (along with their target).
     pub async fn file_metadata(&self, path: impl AsRef<str>) -> crate::Result<PathMetadata> {
-        let resolved = self.get_path(path)?;
-        match tokio::fs::metadata(resolved).await {
-            Ok(meta) => Ok(PathMetadata::from(meta)),
-            Err(e) => Err(crate::Error::filesystem("FILE_METADATA", e.to_string()))
+        let resolved = self.get_link_path(&path)?;
+        match tokio::fs::symlink_metadata(&resolved).await {
+            Ok(meta) => Ok(PathMetadata::for_path(&resolved, meta)),
+            Err(e) => Err(crate::Error::filesystem_io("FILE_METADATA", path.as_ref(), &e))
+        }
+    }
+
+    /// Sets the permission bits of a path. Only the `0o777` bits are applied, so setuid, setgid & sticky bits can't be set.
+    /// On platforms without Unix permissions, only the write bits are honored (as the read-only flag).
+    pub async fn set_permissions(&self, path: impl AsRef<str>, mode: u32) -> crate::Result<()> {
+        let resolved = self.get_path(&path)?;
+        let mut permissions = tokio::fs::metadata(&resolved).await.or_else(|e| Err(crate::Error::filesystem_io("SET_PERMISSIONS", path.as_ref(), &e)))?.permissions();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(mode & 0o777);
+        }
+        #[cfg(not(unix))]
+        permissions.set_readonly(mode & 0o222 == 0);
+
+        tokio::fs::set_permissions(&resolved, permissions).await.or_else(|e| Err(crate::Error::filesystem_io("SET_PERMISSIONS", path.as_ref(), &e)))
+    }
+
+    pub async fn set_readonly(&self, path: impl AsRef<str>, readonly: bool) -> crate::Result<()> {
+        let resolved = self.get_path(&path)?;
+        let mut permissions = tokio::fs::metadata(&resolved).await.or_else(|e| Err(crate::Error::filesystem_io("SET_READONLY", path.as_ref(), &e)))?.permissions();
+        permissions.set_readonly(readonly);
+        tokio::fs::set_permissions(&resolved, permissions).await.or_else(|e| Err(crate::Error::filesystem_io("SET_READONLY", path.as_ref(), &e)))
+    }
+
+    pub async fn hash_file(&self, path: impl AsRef<str>, algorithm: HashAlgorithm) -> crate::Result<FileHash> {
+        self.hash_file_indexed(path, algorithm, None).await
+    }
+
+    /// Hashes a file like [Context::hash_file], reusing (and updating) digests stored in the hash index of `cache`.
+    /// Index entries are invalidated when the file's size or modification time changes.
+    pub async fn hash_file_cached(&self, path: impl AsRef<str>, algorithm: HashAlgorithm, cache: &Database<R>) -> crate::Result<FileHash> {
+        self.hash_file_indexed(path, algorithm, Some(cache)).await
+    }
+
+    pub async fn hash_directory(&self, path: impl AsRef<str>, algorithm: HashAlgorithm, options: WalkOptions, cache: Option<&Database<R>>) -> crate::Result<Vec<FileHash>> {
+        let mut files: Vec<String> = Vec::new();
+        self.walk_with(path, options, |entry| {
+            if matches!(entry.metadata.file_type, PathFileType::File) {
+                files.push(entry.info.relative_path);
+            }
+            Ok(())
+        }).await?;
+
+        let mut hashes: Vec<FileHash> = Vec::with_capacity(files.len());
+        for file in files {
+            hashes.push(self.hash_file_indexed(file, algorithm, cache).await?);
+        }
+
+        Ok(hashes)
+    }
+
+    /// Groups files under `path` with identical contents. Only groups with more than one file are returned.
+    pub async fn find_duplicates(&self, path: impl AsRef<str>, algorithm: HashAlgorithm, options: WalkOptions, cache: Option<&Database<R>>) -> crate::Result<Vec<Vec<FileHash>>> {
+        let mut groups: HashMap<String, Vec<FileHash>> = HashMap::new();
+        for hash in self.hash_directory(path, algorithm, options, cache).await? {
+            groups.entry(hash.digest.clone()).or_default().push(hash);
+        }
+
+        Ok(groups.into_values().filter(|group| group.len() > 1).collect())
+    }
+
+    async fn hash_file_indexed(&self, path: impl AsRef<str>, algorithm: HashAlgorithm, cache: Option<&Database<R>>) -> crate::Result<FileHash> {
+        let resolved = self.get_path(path.as_ref())?;
+        let metadata = tokio::fs::metadata(&resolved).await.or_else(|e| Err(crate::Error::filesystem_io("HASH", path.as_ref(), &e)))?;
+        if !metadata.is_file() {
+            return Err(crate::Error::filesystem_at("HASH", path.as_ref(), IoErrorKind::InvalidInput, "Specified path is not a file."));
+        }
+        let relative = relative_path(&resolved, &self.base_path_canonicalized()?).unwrap_or(path.as_ref().to_string());
+        let size = metadata.len();
+        let modified = metadata.modified().ok().map(chrono::DateTime::<chrono::Utc>::from);
+
+        let index = match cache {
+            Some(db) => Some(db.collection::<HashIndexEntry>(Database::<R>::HASH_INDEX_COLLECTION).await),
+            None => None
+        };
+        let query = bson::doc! {"path": &relative, "algorithm": algorithm.to_string()};
+        if let Some(index) = &index {
+            if let Some(entry) = index.find_one(query.clone()).await? {
+                if entry.size == size && entry.modified == modified {
+                    return Ok(FileHash {path: relative, algorithm, digest: entry.digest, size, cached: true});
+                }
+            }
+        }
+
+        let digest = hash_contents(&resolved, algorithm).await.or_else(|e| Err(crate::Error::filesystem_io("HASH", path.as_ref(), &e)))?;
+        if let Some(index) = &index {
+            index.delete_many(query).await?;
+            index.insert_one(HashIndexEntry {path: relative.clone(), algorithm: algorithm.to_string(), size, modified, digest: digest.clone()}).await?;
+        }
+
+        Ok(FileHash {path: relative, algorithm, digest, size, cached: false})
+    }
+
+    /// Packs the context directory into an archive at `destination` (an absolute path outside the context).
+    /// Open databases & key-value stores are locked for the duration of the export so their files aren't modified mid-copy.
+    /// PoloDB databases keep rewriting their files in the background while open, so they're closed while the archive is written
+    /// & reopened afterwards. Fails if any of them has open transactions.
+    pub async fn export(&self, destination: impl AsRef<Path>, format: ArchiveFormat) -> crate::Result<()> {
+        let base = self.base_path_canonicalized()?;
+        let destination = destination.as_ref().to_path_buf();
+        let parent = match destination.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize().or_else(|e| Err(crate::Error::archive(e.to_string())))?,
+            _ => return Err(crate::Error::archive("Export destination must be an absolute path."))
+        };
+        if !destination.is_absolute() || parent.starts_with(&base) {
+            return Err(crate::Error::archive("Export destination must be an absolute path outside of the context."));
+        }
+
+        for info in self.file_handles().await? {
+            if let Ok(handle) = self.file_handle(info.id).await {
+                let _ = handle.flush().await;
+            }
+        }
+
+        let databases = self.databases().await?.lock().await.values().cloned().collect::<Vec<ContextDB>>();
+        let mut guards = Vec::with_capacity(databases.len());
+        for database in &databases {
+            let guard = database.database.clone().lock_owned().await;
+            if database.backend.rewrites_while_open() && !database.transactions.lock().await.is_empty() {
+                return Err(crate::Error::path_in_use(&database.path, format!("Database {} has open transactions.", database.name)));
+            }
+            guards.push(guard);
+        }
+        for (database, guard) in databases.iter().zip(guards.iter_mut()) {
+            if database.backend.rewrites_while_open() {
+                drop(guard.take());
+            }
+        }
+        let kv_stores = self.kv_stores().await?.lock().await.values().map(|kv| kv.transactions.clone()).collect::<Vec<_>>();
+        let mut kv_guards = Vec::with_capacity(kv_stores.len());
+        for transactions in kv_stores {
+            kv_guards.push(transactions.lock_owned().await);
+        }
+
+        let temporary = Self::TEMPORARY_DIRECTORY.to_string();
+        let result = tokio::task::spawn_blocking(move || {
+            archive::pack(&base, &destination, format, |name| {
+                name != ContextLock::FILE_NAME && name != temporary && !name.starts_with(&format!("{temporary}/"))
+            })
+        }).await.or_else(|e| Err(crate::Error::archive(e.to_string())));
+
+        let mut reopened = Ok(());
+        for (database, guard) in databases.iter().zip(guards.iter_mut()) {
+            if guard.is_none() {
+                match self.get_path(&database.path).and_then(|resolved| self.open_backend(database.backend, &database.path, &resolved)) {
+                    Ok(store) => **guard = Some(store),
+                    Err(e) => reopened = reopened.and(Err(e))
+                }
+            }
+        }
+
+        drop(kv_guards);
+        drop(guards);
+        result.and_then(|packed| packed).and(reopened)
+    }
+
+    pub(crate) async fn import_archive(&self, archive: impl AsRef<Path>) -> crate::Result<()> {
+        let archive = archive.as_ref().to_path_buf();
+        let context = self.clone();
+        tokio::task::spawn_blocking(move || archive::unpack(&archive, |name| context.get_path(name)))
+            .await
+            .or_else(|e| Err(crate::Error::archive(e.to_string())))?
+    }
+
+    pub async fn exists(&self, path: impl AsRef<str>) -> crate::Result<bool> {
+        let resolved = self.get_path(&path)?;
+        tokio::fs::try_exists(resolved).await.or_else(|e| Err(crate::Error::filesystem_io("EXISTS", path.as_ref(), &e)))
+    }
+
+    pub async fn is_file(&self, path: impl AsRef<str>) -> crate::Result<bool> {
+        Ok(self.stat(path).await?.is_some_and(|(_, meta)| meta.is_file()))
+    }
+
+    pub async fn is_dir(&self, path: impl AsRef<str>) -> crate::Result<bool> {
+        Ok(self.stat(path).await?.is_some_and(|(_, meta)| meta.is_dir()))
+    }
+
+    /// Returns metadata for each path (without following symlinks, like [Context::file_metadata]), or `None` for paths that don't exist.
+    /// Invalid or escaping paths still fail the whole batch.
+    pub async fn stat_many(&self, paths: Vec<String>) -> crate::Result<Vec<Option<PathMetadata>>> {
+        let mut results: Vec<Option<PathMetadata>> = Vec::with_capacity(paths.len());
+        for path in paths {
+            let resolved = self.get_link_path(&path)?;
+            match tokio::fs::symlink_metadata(&resolved).await {
+                Ok(meta) => results.push(Some(PathMetadata::for_path(resolved, meta))),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => results.push(None),
+                Err(e) => return Err(crate::Error::filesystem_io("STAT", path, &e))
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn stat(&self, path: impl AsRef<str>) -> crate::Result<Option<(PathBuf, std::fs::Metadata)>> {
+        let resolved = self.get_path(&path)?;
+        match tokio::fs::metadata(&resolved).await {
+            Ok(meta) => Ok(Some((resolved, meta))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(crate::Error::filesystem_io("STAT", path.as_ref(), &e))
         }
     }
 
     pub async fn list_directory(&self, path: impl AsRef<str>) -> crate::Result<Vec<PathInformation>> {
-        let resolved = self.get_path(path)?;
+        let resolved = self.get_path(&path)?;
         if !resolved.is_dir() {
-            return Err(crate::Error::filesystem("LIST_DIRECTORY", "Specified path is not a directory or does not exist."));
+            return Err(crate::Error::filesystem_at("LIST_DIRECTORY", path.as_ref(), IoErrorKind::NotFound, "Specified path is not a directory or does not exist."));
         }
+        let base = self.base_path_canonicalized()?;
 
         match tokio::fs::read_dir(resolved).await {
             Ok(mut results) => {
                 let mut infos: Vec<PathInformation> = Vec::new();
-                while let Ok(Some(info)) = results.next_entry().await {
-                    infos.push(PathInformation::from(info));
+                while let Some(info) = results.next_entry().await.or_else(|e| Err(crate::Error::filesystem_io("LIST_DIRECTORY", path.as_ref(), &e)))? {
+                    infos.push(PathInformation::from_entry(&info, &base));
                 }
 
                 Ok(infos)
             },
-            Err(e) => Err(crate::Error::filesystem("LIST_DIRECTORY", e.to_string()))
+            Err(e) => Err(crate::Error::filesystem_io("LIST_DIRECTORY", path.as_ref(), &e))
+        }
+    }
+
+    pub async fn walk(&self, path: impl AsRef<str>, options: WalkOptions) -> crate::Result<Vec<WalkEntry>> {
+        let mut entries: Vec<WalkEntry> = Vec::new();
+        self.walk_with(path, options, |entry| {
+            entries.push(entry);
+            Ok(())
+        }).await?;
+        Ok(entries)
+    }
+
+    pub async fn walk_with(&self, path: impl AsRef<str>, options: WalkOptions, mut callback: impl FnMut(WalkEntry) -> crate::Result<()>) -> crate::Result<()> {
+        let resolved = self.get_path(&path)?;
+        if !resolved.is_dir() {
+            return Err(crate::Error::filesystem_at("WALK", path.as_ref(), IoErrorKind::NotFound, "Specified path is not a directory or does not exist."));
+        }
+        let base = self.base_path_canonicalized()?;
+        let include = compile_patterns(&options.include)?;
+        let exclude = compile_patterns(&options.exclude)?;
+
+        let mut visited: HashSet<PathBuf> = HashSet::new();
+        if let Ok(canonical) = resolved.canonicalize() {
+            visited.insert(canonical);
+        }
+
+        let mut pending: Vec<(PathBuf, u32)> = vec![(resolved.clone(), 1)];
+        while let Some((directory, depth)) = pending.pop() {
+            let mut results = tokio::fs::read_dir(&directory).await.or_else(|e| Err(crate::Error::filesystem_io("WALK", path.as_ref(), &e)))?;
+            while let Some(entry) = results.next_entry().await.or_else(|e| Err(crate::Error::filesystem_io("WALK", path.as_ref(), &e)))? {
+                let entry_path = entry.path();
+                let relative = relative_path(&entry_path, &resolved).unwrap_or(entry_path.to_string_lossy().to_string());
+                if exclude.iter().any(|pattern| pattern.matches(&relative)) {
+                    continue;
+                }
+
+                let file_type = entry.file_type().await.or_else(|e| Err(crate::Error::filesystem_io("WALK", path.as_ref(), &e)))?;
+                let metadata = if file_type.is_symlink() {
+                    match options.symlinks {
+                        SymlinkPolicy::Skip => continue,
+                        SymlinkPolicy::Include => entry.metadata().await,
+                        SymlinkPolicy::Follow => match entry_path.canonicalize() {
+                            Ok(target) if target.starts_with(&base) => tokio::fs::metadata(&target).await,
+                            _ => continue
+                        }
+                    }
+                } else {
+                    entry.metadata().await
+                }.or_else(|e| Err(crate::Error::filesystem_io("WALK", path.as_ref(), &e)))?;
+
+                let within_depth = match options.max_depth {
+                    Some(max) => depth < max,
+                    None => true
+                };
+                if metadata.is_dir() && within_depth {
+                    // Guards against symlink cycles when following links
+                    let canonical = entry_path.canonicalize().unwrap_or(entry_path.clone());
+                    if visited.insert(canonical) {
+                        pending.push((entry_path.clone(), depth + 1));
+                    }
+                }
+
+                if include.is_empty() || include.iter().any(|pattern| pattern.matches(&relative)) {
+                    callback(WalkEntry {
+                        path: relative,
+                        depth,
+                        info: PathInformation::from_entry(&entry, &base),
+                        metadata: PathMetadata::for_path(&entry_path, metadata)
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn copy(&self, from: impl AsRef<str>, to: impl AsRef<str>, recursive: bool, overwrite: bool) -> crate::Result<()> {
+        let source = self.get_path(from.as_ref())?;
+        let destination = self.get_path(to.as_ref())?;
+        if !source.exists() {
+            return Err(crate::Error::filesystem_at("COPY", from.as_ref(), IoErrorKind::NotFound, "Source path does not exist."));
+        }
+        if destination.exists() && !overwrite {
+            return Err(crate::Error::filesystem_at("COPY", to.as_ref(), IoErrorKind::AlreadyExists, "Destination path already exists."));
+        }
+
+        // Compared canonically, since copying a file onto itself (through `..` or a symlink) would truncate it
+        let canonical_source = canonicalize_nearest(&source).or_else(|e| Err(crate::Error::filesystem_io("COPY", from.as_ref(), &e)))?;
+        let canonical_destination = canonicalize_nearest(&destination).or_else(|e| Err(crate::Error::filesystem_io("COPY", to.as_ref(), &e)))?;
+        if canonical_source == canonical_destination {
+            return Err(crate::Error::filesystem_at("COPY", to.as_ref(), IoErrorKind::InvalidInput, "Source and destination are the same path."));
+        }
+        self.ensure_no_open_databases(to.as_ref(), &destination).await?;
+
+        if source.is_dir() {
+            if !recursive {
+                return Err(crate::Error::filesystem_at("COPY", from.as_ref(), IoErrorKind::InvalidInput, "Source path is a directory, but recursive copying was not requested."));
+            }
+            if canonical_destination.starts_with(&canonical_source) {
+                return Err(crate::Error::filesystem_at("COPY", to.as_ref(), IoErrorKind::InvalidInput, "Cannot copy a directory into itself."));
+            }
+            copy_directory(source, destination, overwrite).await.or_else(|e| Err(crate::Error::filesystem_io("COPY", from.as_ref(), &e)))
+        } else {
+            if destination.is_dir() {
+                return Err(crate::Error::filesystem_at("COPY", to.as_ref(), IoErrorKind::IsADirectory, "Cannot overwrite a directory with a file."));
+            }
+            if let Some(parent) = destination.parent() {
+                tokio::fs::create_dir_all(parent).await.or_else(|e| Err(crate::Error::filesystem_io("COPY", to.as_ref(), &e)))?;
+            }
+            tokio::fs::copy(source, destination).await.and(Ok(())).or_else(|e| Err(crate::Error::filesystem_io("COPY", to.as_ref(), &e)))
+        }
+    }
+
+    pub async fn rename(&self, from: impl AsRef<str>, to: impl AsRef<str>) -> crate::Result<()> {
+        let source = self.get_path(from.as_ref())?;
+        let destination = self.get_path(to.as_ref())?;
+        if !source.exists() {
+            return Err(crate::Error::filesystem_at("RENAME", from.as_ref(), IoErrorKind::NotFound, "Source path does not exist."));
+        }
+        if destination.exists() {
+            return Err(crate::Error::filesystem_at("RENAME", to.as_ref(), IoErrorKind::AlreadyExists, "Destination path already exists."));
+        }
+        self.ensure_no_open_databases(from.as_ref(), &source).await?;
+        self.ensure_no_open_databases(to.as_ref(), &destination).await?;
+
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await.or_else(|e| Err(crate::Error::filesystem_io("RENAME", to.as_ref(), &e)))?;
+        }
+        tokio::fs::rename(&source, &destination).await.or_else(|e| Err(crate::Error::filesystem_io("RENAME", from.as_ref(), &e)))?;
+
+        // Open file handles keep working after a rename, so their tracked paths are moved along with them.
+        let files = self.files().await?;
+        for handle in files.lock().await.values_mut() {
+            if let Ok(Ok(remainder)) = self.get_path(&handle.path).and_then(|p| Ok(p.strip_prefix(&source).and_then(|r| Ok(r.to_path_buf())))) {
+                handle.path = if remainder.as_os_str().is_empty() {
+                    to.as_ref().to_string()
+                } else {
+                    PathBuf::from(to.as_ref()).join(remainder).to_string_lossy().to_string()
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn ensure_no_open_databases(&self, path: impl AsRef<str>, resolved: &Path) -> crate::Result<()> {
+        for (name, db) in self.databases().await?.lock().await.iter().filter(|(_, db)| db.backend != StorageBackendKind::Memory) {
+            if let Ok(db_path) = self.get_path(&db.path) {
+                if db_path.starts_with(resolved) || resolved.starts_with(&db_path) {
+                    return Err(crate::Error::path_in_use(path.as_ref(), format!("Database {name} is open at {}.", db.path)));
+                }
+            }
+        }
+        for (name, kv) in self.kv_stores().await?.lock().await.iter() {
+            if let Ok(kv_path) = self.get_path(&kv.path) {
+                if kv_path.starts_with(resolved) || resolved.starts_with(&kv_path) {
+                    return Err(crate::Error::path_in_use(path.as_ref(), format!("Key-value store {name} is open at {}.", kv.path)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn read_document<T: DeserializeOwned>(&self, path: impl AsRef<str>, format: Option<DocumentFormat>) -> crate::Result<T> {
+        let resolved = self.get_path(path.as_ref())?;
+        let format = match format {
+            Some(f) => f,
+            None => DocumentFormat::from_path(&resolved).ok_or(crate::Error::unknown_document_format(path.as_ref()))?
+        };
+        let data = tokio::fs::read(&resolved).await.or_else(|e| Err(crate::Error::filesystem_io("READ_DOCUMENT", path.as_ref(), &e)))?;
+        format.parse::<T>(&data, path.as_ref())
+    }
+
+    pub async fn write_document<T: Serialize>(&self, path: impl AsRef<str>, document: &T, format: Option<DocumentFormat>) -> crate::Result<()> {
+        let resolved = self.get_path(path.as_ref())?;
+        let format = match format {
+            Some(f) => f,
+            None => DocumentFormat::from_path(&resolved).ok_or(crate::Error::unknown_document_format(path.as_ref()))?
+        };
+        let data = format.serialize(document)?;
+        self.write_atomic(&resolved, &data).await.or_else(|e| Err(crate::Error::filesystem_io("WRITE_DOCUMENT", path.as_ref(), &e)))
+    }
+
+    pub(crate) async fn write_atomic(&self, resolved: &Path, data: &[u8]) -> std::io::Result<()> {
+        let parent = resolved.parent().ok_or(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Path has no parent directory."))?;
+        tokio::fs::create_dir_all(parent).await?;
+        let file_name = resolved.file_name().and_then(|n| Some(n.to_string_lossy().to_string())).unwrap_or_default();
+        let temporary = parent.join(format!(".{file_name}.{}.tmp", bson::Uuid::new()));
+
+        let mut file = File::create(&temporary).await?;
+        let written = match file.write_all(data).await {
+            Ok(_) => file.sync_all().await,
+            Err(e) => Err(e)
+        };
+        drop(file);
+
+        let renamed = match written {
+            Ok(_) => tokio::fs::rename(&temporary, resolved).await,
+            Err(e) => Err(e)
+        };
+        if let Err(e) = renamed {
+            let _ = tokio::fs::remove_file(&temporary).await;
+            return Err(e);
         }
+
+        Ok(())
     }
 
-    pub(crate) async fn state(&self) -> ContextState {
+    /// Fails with [crate::Error::UnknownContext] once the context has been closed
+    pub(crate) async fn state(&self) -> crate::Result<ContextState> {
         self.handle()
             .state::<PluginState>()
             .lock()
             .await
             .get(&self.name())
-            .expect("Context not initialized.")
-            .clone()
+            .cloned()
+            .ok_or(crate::Error::unknown_context(self.name()))
+    }
+
+    pub async fn lock_owner(&self) -> crate::Result<Option<ContextLockOwner>> {
+        Ok(self.state().await?.lock.and_then(|lock| lock.owner()))
+    }
+
+    pub(crate) async fn databases(&self) -> crate::Result<Arc<Mutex<HashMap<String, ContextDB>>>> {
+        Ok(self.state().await?.databases.clone())
     }
 
-    pub(crate) async fn databases(&self) -> Arc<Mutex<HashMap<String, ContextDB>>> {
-        self.state().await.databases.clone()
+    pub(crate) async fn kv_stores(&self) -> crate::Result<Arc<Mutex<HashMap<String, ContextKv>>>> {
+        Ok(self.state().await?.kv_stores.clone())
     }
 
-    pub(crate) async fn files(&self) -> Arc<Mutex<HashMap<bson::Uuid, ContextFileHandle>>> {
-        self.state().await.files.clone()
+    pub(crate) async fn files(&self) -> crate::Result<Arc<Mutex<HashMap<bson::Uuid, ContextFileHandle>>>> {
+        Ok(self.state().await?.files.clone())
     }
 
     pub async fn open_database(
@@ -148,11 +582,36 @@ impl<R: Runtime> Context<R> {
         name: impl AsRef<str>,
         path: impl AsRef<str>,
     ) -> crate::Result<Database<R>> {
-        let _dbs = self.databases().await;
+        self.open_database_with_backend(name, path, StorageBackendKind::default()).await
+    }
+
+    pub async fn open_database_with_backend(
+        &self,
+        name: impl AsRef<str>,
+        path: impl AsRef<str>,
+        backend: StorageBackendKind,
+    ) -> crate::Result<Database<R>> {
+        if backend == StorageBackendKind::Memory {
+            return Err(crate::Error::open_database(
+                name.as_ref(),
+                self.name(),
+                path.as_ref(),
+                "In-memory databases are opened with open_memory_database.",
+            ));
+        }
+
+        let _dbs = self.databases().await?;
         let mut dbs = _dbs.lock().await;
         let resolved_path = self.get_path(path.as_ref())?;
         if let Some(db) = dbs.get(&name.as_ref().to_string()) {
-            if db.path == path.as_ref().to_string() {
+            if db.backend != backend {
+                Err(crate::Error::open_database(
+                    name.as_ref(),
+                    self.name(),
+                    path.as_ref(),
+                    "Database is already open with another backend.",
+                ))
+            } else if db.path == path.as_ref() {
                 Ok(Database::<R>::create(
                     self.clone(),
                     name.as_ref().to_string(),
@@ -169,22 +628,24 @@ impl<R: Runtime> Context<R> {
         } else {
             if resolved_path.exists() {
                 if resolved_path.is_file() {
-                    let database =
-                        polodb_core::Database::open_path(resolved_path).or_else(|e| {
-                            Err(crate::Error::open_database(
-                                name.as_ref(),
-                                self.name(),
-                                path.as_ref(),
-                                e.to_string(),
-                            ))
-                        })?;
+                    let database = self.open_backend(backend, path.as_ref(), &resolved_path).or_else(|e| match e {
+                        crate::Error::WrongKey { .. } => Err(e),
+                        e => Err(crate::Error::open_database(
+                            name.as_ref(),
+                            self.name(),
+                            path.as_ref(),
+                            e.to_string(),
+                        )),
+                    })?;
                     let _ = dbs.insert(
                         name.as_ref().to_string(),
                         ContextDB {
                             name: name.as_ref().to_string(),
                             path: path.as_ref().to_string(),
-                            database: Arc::new(Mutex::new(database)),
+                            backend,
+                            database: Arc::new(Mutex::new(Some(database))),
                             transactions: Arc::new(Mutex::new(HashMap::new())),
+                            snapshots: Arc::new(Mutex::new(None)),
                         },
                     );
                     Ok(Database::<R>::create(
@@ -201,21 +662,24 @@ impl<R: Runtime> Context<R> {
                     ))
                 }
             } else {
-                let database = polodb_core::Database::open_path(resolved_path).or_else(|e| {
-                    Err(crate::Error::open_database(
+                let database = self.open_backend(backend, path.as_ref(), &resolved_path).or_else(|e| match e {
+                    crate::Error::WrongKey { .. } => Err(e),
+                    e => Err(crate::Error::open_database(
                         name.as_ref(),
                         self.name(),
                         path.as_ref(),
                         e.to_string(),
-                    ))
+                    )),
                 })?;
                 let _ = dbs.insert(
                     name.as_ref().to_string(),
                     ContextDB {
                         name: name.as_ref().to_string(),
                         path: path.as_ref().to_string(),
-                        database: Arc::new(Mutex::new(database)),
+                        backend,
+                        database: Arc::new(Mutex::new(Some(database))),
                         transactions: Arc::new(Mutex::new(HashMap::new())),
+                        snapshots: Arc::new(Mutex::new(None)),
                     },
                 );
                 Ok(Database::<R>::create(
@@ -227,10 +691,60 @@ impl<R: Runtime> Context<R> {
         }
     }
 
+    /// Requests the key for `path` from the keyring registered on [crate::PluginBuilder]
+    pub(crate) fn encryption_key(&self, path: impl AsRef<str>) -> crate::Result<EncryptionKey> {
+        let keyring = self.handle().try_state::<KeyringState>().and_then(|state| state.0.clone());
+        let Some(keyring) = keyring else {
+            return Err(crate::Error::encryption("No keyring was registered with the plugin."));
+        };
+        keyring(&KeyRequest { context: self.name(), path: path.as_ref().to_string() })
+            .ok_or(crate::Error::encryption(format!("The keyring has no key for {}.", path.as_ref())))
+    }
+
+    /// Fetches the key for a database at `path`, if `backend` needs one
+    fn database_key(&self, backend: StorageBackendKind, path: impl AsRef<str>) -> crate::Result<Option<EncryptionKey>> {
+        match backend {
+            StorageBackendKind::Encrypted => Ok(Some(self.encryption_key(path)?)),
+            _ => Ok(None)
+        }
+    }
+
+    fn open_backend(&self, backend: StorageBackendKind, path: impl AsRef<str>, resolved: &Path) -> crate::Result<Box<dyn DocumentStore>> {
+        backend.open(resolved, self.database_key(backend, path)?)
+    }
+
+    /// Opens a new database that's kept in memory & discarded when closed, unless it's saved with [Database::persist] first.
+    /// If `name` is already open in memory, a handle to that database is returned.
+    pub async fn open_memory_database(&self, name: impl AsRef<str>) -> crate::Result<Database<R>> {
+        let _dbs = self.databases().await?;
+        let mut dbs = _dbs.lock().await;
+        if let Some(db) = dbs.get(name.as_ref()) {
+            return if db.backend == StorageBackendKind::Memory {
+                Ok(Database::<R>::create(self.clone(), name.as_ref().to_string(), db.path.clone()))
+            } else {
+                Err(crate::Error::open_database(name.as_ref(), self.name(), &db.path, "Database is already open with another backend."))
+            };
+        }
+
+        let database = MemoryBackend.open(Path::new(""))?;
+        let _ = dbs.insert(
+            name.as_ref().to_string(),
+            ContextDB {
+                name: name.as_ref().to_string(),
+                path: String::new(),
+                backend: StorageBackendKind::Memory,
+                database: Arc::new(Mutex::new(Some(database))),
+                transactions: Arc::new(Mutex::new(HashMap::new())),
+                snapshots: Arc::new(Mutex::new(None)),
+            },
+        );
+        Ok(Database::<R>::create(self.clone(), name.as_ref().to_string(), String::new()))
+    }
+
     pub async fn database(&self, name: impl AsRef<str>) -> crate::Result<Database<R>> {
         if let Some(db) = self
             .databases()
-            .await
+            .await?
             .lock()
             .await
             .get(&name.as_ref().to_string())
@@ -246,25 +760,114 @@ impl<R: Runtime> Context<R> {
     }
 
     pub(crate) async fn close_database(&self, name: impl AsRef<str>) -> crate::Result<()> {
-        if let Some(_) = self
+        if let Some(db) = self
             .databases()
-            .await
+            .await?
             .lock()
             .await
             .remove(&name.as_ref().to_string())
         {
+            if let Some(schedule) = db.snapshots.lock().await.take() {
+                schedule.abort();
+            }
             Ok(())
         } else {
             Err(crate::Error::unknown_database(name.as_ref()))
         }
     }
 
+    pub async fn open_kv_store(&self, name: impl AsRef<str>, path: impl AsRef<str>) -> crate::Result<KvStore<R>> {
+        self.open_kv_store_with_encoding(name, path, KvEncoding::default()).await
+    }
+
+    /// Opens (or creates) a key-value store at `path`. Stores keep the encoding they were created with, so reopening one with another encoding fails.
+    pub async fn open_kv_store_with_encoding(&self, name: impl AsRef<str>, path: impl AsRef<str>, encoding: KvEncoding) -> crate::Result<KvStore<R>> {
+        let _stores = self.kv_stores().await?;
+        let mut stores = _stores.lock().await;
+        let resolved_path = self.get_path(path.as_ref())?;
+        if let Some(kv) = stores.get(name.as_ref()) {
+            return if kv.path != path.as_ref() {
+                Err(crate::Error::open_kv_store(name.as_ref(), self.name(), path.as_ref(), "Key-value store is already open at another path."))
+            } else if kv.store.encoding != encoding {
+                Err(crate::Error::open_kv_store(name.as_ref(), self.name(), path.as_ref(), "Key-value store is already open with another encoding."))
+            } else {
+                Ok(KvStore::<R>::create(self.clone(), name.as_ref().to_string(), path.as_ref().to_string()))
+            };
+        }
+
+        if resolved_path.is_dir() {
+            return Err(crate::Error::open_kv_store(name.as_ref(), self.name(), path.as_ref(), "Specified path is not a file."));
+        }
+        if let Some(parent) = resolved_path.parent() {
+            tokio::fs::create_dir_all(parent).await.or_else(|e| Err(crate::Error::open_kv_store(name.as_ref(), self.name(), path.as_ref(), e.to_string())))?;
+        }
+
+        let store = KvDatabase::open(&resolved_path, encoding).or_else(|e| Err(crate::Error::open_kv_store(name.as_ref(), self.name(), path.as_ref(), e.to_string())))?;
+        let _ = stores.insert(
+            name.as_ref().to_string(),
+            ContextKv {
+                name: name.as_ref().to_string(),
+                path: path.as_ref().to_string(),
+                store: Arc::new(store),
+                transactions: Arc::new(Mutex::new(HashMap::new())),
+            },
+        );
+        Ok(KvStore::<R>::create(self.clone(), name.as_ref().to_string(), path.as_ref().to_string()))
+    }
+
+    pub async fn kv_store(&self, name: impl AsRef<str>) -> crate::Result<KvStore<R>> {
+        if let Some(kv) = self.kv_stores().await?.lock().await.get(name.as_ref()) {
+            Ok(KvStore::<R>::create(self.clone(), name.as_ref().to_string(), kv.path.clone()))
+        } else {
+            Err(crate::Error::unknown_kv_store(name.as_ref()))
+        }
+    }
+
+    /// Closes a key-value store, rolling back any of its open transactions
+    pub(crate) async fn close_kv_store(&self, name: impl AsRef<str>) -> crate::Result<()> {
+        if let Some(kv) = self.kv_stores().await?.lock().await.remove(name.as_ref()) {
+            for (_, transaction) in kv.transactions.lock().await.drain() {
+                let _ = KvDatabase::rollback(transaction);
+            }
+            Ok(())
+        } else {
+            Err(crate::Error::unknown_kv_store(name.as_ref()))
+        }
+    }
+
     pub async fn open_file_handle(
         &self,
         path: impl AsRef<str>,
         mode: FileHandleMode,
+    ) -> crate::Result<FileHandle<R>> {
+        self.open_tracked_file_handle(path, mode, false).await
+    }
+
+    async fn open_tracked_file_handle(
+        &self,
+        path: impl AsRef<str>,
+        mode: FileHandleMode,
+        temporary: bool,
     ) -> crate::Result<FileHandle<R>> {
         let resolved = self.get_path(path.as_ref())?;
+        let cipher = if mode.is_encrypted() {
+            if mode.writeable() && !mode.overwrites() {
+                return Err(crate::Error::open_file_handle(path.as_ref(), self.name(), "Encrypted files can't be appended to."));
+            }
+            let key = self.encryption_key(path.as_ref())?;
+            Some(Arc::new(Mutex::new(if mode.writeable() {
+                FileCipher::Writer(StreamWriter::new(&key)?)
+            } else {
+                FileCipher::Reader(StreamReader::new(&key, path.as_ref()))
+            })))
+        } else {
+            None
+        };
+        let codec = match mode.compression() {
+            Some(compression) => Some(Arc::new(Mutex::new(FileCodec::Compressor(Some(Compressor::new(compression)?))))),
+            None if mode.decompresses() => Some(Arc::new(Mutex::new(FileCodec::Decompressor(Decompressor::new())))),
+            None => None
+        };
         if mode.create() && !resolved.exists() && resolved.clone().parent().is_some() {
             tokio::fs::create_dir_all(resolved.clone().parent().unwrap())
                 .await
@@ -290,24 +893,83 @@ impl<R: Runtime> Context<R> {
             path: path.as_ref().to_string(),
             handle: async_dup::Arc::new(async_dup::Mutex::new(file)),
             mode: mode.clone(),
+            opened: chrono::Utc::now(),
+            temporary,
+            cipher,
+            codec,
         };
-        let id = handle.id.clone();
+        let id = handle.id;
 
-        let _files = self.files().await;
+        let _files = self.files().await?;
         let mut files = _files.lock().await;
-        let _ = files.insert(id.clone(), handle);
+        let _ = files.insert(id, handle);
         Ok(FileHandle::<R>::create(
             self.clone(),
-            id.clone(),
+            id,
             path.as_ref().to_string(),
         ))
     }
 
+    pub async fn temp_file(&self) -> crate::Result<FileHandle<R>> {
+        let path = format!("{}/{}", Self::TEMPORARY_DIRECTORY, bson::Uuid::new());
+        self.open_tracked_file_handle(path, FileHandleMode::create_new(true), true).await
+    }
+
+    pub async fn temp_dir(&self) -> crate::Result<String> {
+        let path = format!("{}/{}", Self::TEMPORARY_DIRECTORY, bson::Uuid::new());
+        self.create_directory(&path, true).await?;
+        self.state().await?.temporary_directories.lock().await.push(path.clone());
+        Ok(path)
+    }
+
+    pub(crate) async fn remove_temporary_directory(&self) -> crate::Result<()> {
+        let resolved = self.get_path(Self::TEMPORARY_DIRECTORY)?;
+        match tokio::fs::remove_dir_all(resolved).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(crate::Error::filesystem_io("REMOVE_TEMPORARY", Self::TEMPORARY_DIRECTORY, &e))
+        }
+    }
+
+    pub async fn watch(&self, path: impl AsRef<str>, recursive: bool) -> crate::Result<bson::Uuid> {
+        let resolved = self.get_path(path.as_ref())?;
+        if !resolved.exists() {
+            return Err(crate::Error::filesystem_at("WATCH", path.as_ref(), IoErrorKind::NotFound, "Specified path does not exist."));
+        }
+
+        let id = bson::Uuid::new();
+        let base = self.base_path_canonicalized()?;
+        let context = self.name();
+        let handle = self.handle();
+        let mut debouncer = new_debouncer(Duration::from_millis(Self::WATCH_DEBOUNCE_MS), None, move |result: DebounceEventResult| {
+            if let Ok(events) = result {
+                for event in events {
+                    if let Some(payload) = watch_event::<R>(&context, id, &base, &event.event) {
+                        let _ = payload.emit(&handle);
+                    }
+                }
+            }
+        }).or_else(|e| Err(crate::Error::filesystem_at("WATCH", path.as_ref(), IoErrorKind::from(&e), e.to_string())))?;
+        debouncer
+            .watch(&resolved, if recursive {RecursiveMode::Recursive} else {RecursiveMode::NonRecursive})
+            .or_else(|e| Err(crate::Error::filesystem_at("WATCH", path.as_ref(), IoErrorKind::from(&e), e.to_string())))?;
+
+        self.state().await?.watchers.lock().await.insert(id, ContextWatcher {path: path.as_ref().to_string(), recursive, debouncer});
+        Ok(id)
+    }
+
+    pub async fn unwatch(&self, id: bson::Uuid) -> crate::Result<()> {
+        match self.state().await?.watchers.lock().await.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(crate::Error::unknown_watcher(id.to_string()))
+        }
+    }
+
     pub async fn file_handle(&self, id: bson::Uuid) -> crate::Result<FileHandle<R>> {
-        if let Some(handle) = self.files().await.lock().await.get(&id) {
+        if let Some(handle) = self.files().await?.lock().await.get(&id) {
             Ok(FileHandle::<R>::create(
                 self.clone(),
-                id.clone(),
+                id,
                 handle.path.clone(),
             ))
         } else {
@@ -315,56 +977,874 @@ impl<R: Runtime> Context<R> {
         }
     }
 
-    pub(crate) async fn close_file_handle(&self, id: bson::Uuid) -> crate::Result<()> {
-        if let Some(_) = self.files().await.lock().await.remove(&id) {
-            Ok(())
-        } else {
-            Err(crate::Error::unknown_file_handle(id.to_string()))
+    pub(crate) async fn close_file_handle(&self, id: bson::Uuid) -> crate::Result<()> {
+        let removed = self.files().await?.lock().await.remove(&id);
+        if let Some(handle) = removed {
+            let mut file = handle.handle.lock();
+            let finished = match finish_streams(&handle).await {
+                Ok(remaining) => file.write_all(&remaining).await.or_else(|e| Err(crate::Error::from(e))),
+                Err(e) => Err(e)
+            };
+            let flushed = finished.and(file.flush().await.or_else(|e| Err(crate::Error::from(e))));
+            if handle.temporary {
+                let _ = tokio::fs::remove_file(self.get_path(&handle.path)?).await;
+            }
+
+            flushed
+        } else {
+            Err(crate::Error::unknown_file_handle(id.to_string()))
+        }
+    }
+
+    pub async fn file_handles(&self) -> crate::Result<Vec<FileHandleInfo>> {
+        let mut infos: Vec<FileHandleInfo> = Vec::new();
+        for id in self.file_ids().await? {
+            if let Ok(handle) = self.file_handle(id).await {
+                if let Ok(info) = handle.info().await {
+                    infos.push(info);
+                }
+            }
+        }
+
+        Ok(infos)
+    }
+
+    pub async fn close_all_file_handles(&self) -> crate::Result<()> {
+        let mut result = Ok(());
+        for handle_id in self.file_ids().await? {
+            if let Err(e) = self.close_file_handle(handle_id).await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    pub(crate) async fn file_ids(&self) -> crate::Result<Vec<bson::Uuid>> {
+        let files = self.files().await?;
+        let handles = files.lock().await;
+        let mut result: Vec<bson::Uuid> = Vec::new();
+        for id in handles.keys() {
+            result.push(*id);
+        }
+
+        Ok(result)
+    }
+
+    pub(crate) async fn db_ids(&self) -> crate::Result<Vec<String>> {
+        let dbs = self.databases().await?;
+        let bases = dbs.lock().await;
+        let mut result: Vec<String> = Vec::new();
+        for id in bases.keys() {
+            result.push(id.clone());
+        }
+
+        Ok(result)
+    }
+    
+    pub async fn close(self) -> crate::Result<()> {
+        self.state().await?.watchers.lock().await.clear();
+        let result = self.close_all_file_handles().await;
+
+        for db_id in self.db_ids().await? {
+            self.close_database(db_id).await?;
+        }
+
+        let kv_ids = self.kv_stores().await?.lock().await.keys().cloned().collect::<Vec<String>>();
+        for kv_id in kv_ids {
+            self.close_kv_store(kv_id).await?;
+        }
+
+        let directories = self.state().await?.temporary_directories.lock().await.drain(..).collect::<Vec<String>>();
+        for directory in directories {
+            let _ = self.remove_directory(directory).await;
+        }
+        let _ = tokio::fs::remove_dir(self.get_path(Self::TEMPORARY_DIRECTORY)?).await;
+
+        result
+    }
+}
+
+fn watch_event<R: Runtime>(context: &str, watcher: bson::Uuid, base: &Path, event: &notify_debouncer_full::notify::Event) -> Option<ContextWatchEvent> {
+    let kind = match event.kind {
+        EventKind::Create(_) => WatchEventKind::Create,
+        EventKind::Modify(ModifyKind::Name(_)) => WatchEventKind::Rename,
+        EventKind::Modify(ModifyKind::Metadata(_)) => WatchEventKind::Other,
+        EventKind::Modify(_) => WatchEventKind::Modify,
+        EventKind::Remove(_) => WatchEventKind::Remove,
+        EventKind::Access(_) => return None,
+        _ => WatchEventKind::Other
+    };
+
+    // Internal bookkeeping files (locks & temporary files) are not reported
+    let paths = event.paths
+        .iter()
+        .filter_map(|path| relative_path(path, base))
+        .filter(|relative| {
+            let first = relative.split('/').next();
+            first != Some(ContextLock::FILE_NAME) && first != Some(Context::<R>::TEMPORARY_DIRECTORY)
+        })
+        .collect::<Vec<String>>();
+
+    if paths.is_empty() {
+        None
+    } else {
+        Some(ContextWatchEvent {context: context.to_string(), watcher, kind, paths})
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct HashIndexEntry {
+    path: String,
+    algorithm: String,
+    size: u64,
+    modified: Option<chrono::DateTime<chrono::Utc>>,
+    digest: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct IndexEntry {
+    collection: String,
+    name: String,
+    keys: Document,
+    unique: Option<bool>
+}
+
+impl IndexEntry {
+    /// Names entries the way backends name their indexes, so they can be matched up with [Collection::drop_index]
+    fn new(collection: impl AsRef<str>, index: &IndexModel) -> Self {
+        Self {
+            collection: collection.as_ref().to_string(),
+            name: index_name(index),
+            keys: index.keys.clone(),
+            unique: index.options.as_ref().and_then(|o| o.unique)
+        }
+    }
+
+    fn model(&self) -> IndexModel {
+        IndexModel { keys: self.keys.clone(), options: Some(IndexOptions { name: Some(self.name.clone()), unique: self.unique }) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct DumpManifest {
+    format: DumpFormat,
+    collections: Vec<String>,
+    indexes: Vec<IndexEntry>
+}
+
+enum ContentHasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>)
+}
+
+impl ContentHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new()))
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => sha2::Digest::update(hasher, data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Sha256(hasher) => hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect(),
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string()
+        }
+    }
+}
+
+/// Hashes a file in fixed-size chunks, so large files are never fully loaded into memory
+async fn hash_contents(path: &Path, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = ContentHasher::new(algorithm);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+fn compile_patterns(patterns: &[String]) -> crate::Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| glob::Pattern::new(pattern).or_else(|e| Err(crate::Error::filesystem_at("WALK", pattern, IoErrorKind::InvalidInput, format!("Invalid glob pattern {pattern}: {e}")))))
+        .collect()
+}
+
+async fn copy_directory(source: PathBuf, destination: PathBuf, overwrite: bool) -> std::io::Result<()> {
+    let mut pending = vec![(source, destination)];
+    while let Some((from, to)) = pending.pop() {
+        tokio::fs::create_dir_all(&to).await?;
+        let mut entries = tokio::fs::read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push((entry.path(), target));
+            } else if file_type.is_file() {
+                if target.exists() && !overwrite {
+                    return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("{} already exists.", target.to_string_lossy())));
+                }
+                tokio::fs::copy(entry.path(), target).await?;
+            }
+            // Symlinks are skipped, since following them could copy data from outside the context.
+        }
+    }
+
+    Ok(())
+}
+
+/// Ends the compressed & encrypted streams of a writeable handle, returning the data still to be written.
+/// Both formats are only complete once their end has been written.
+async fn finish_streams(handle: &ContextFileHandle) -> crate::Result<Vec<u8>> {
+    let mut remaining = Vec::new();
+    if let Some(codec) = &handle.codec {
+        if let FileCodec::Compressor(compressor) = &mut *codec.lock().await {
+            if let Some(compressor) = compressor.take() {
+                remaining = compressor.finish()?;
+            }
+        }
+    }
+    if let Some(cipher) = &handle.cipher {
+        if let FileCipher::Writer(writer) = &mut *cipher.lock().await {
+            remaining = writer.push(&remaining)?;
+            remaining.extend(writer.finish()?);
+        }
+    }
+
+    Ok(remaining)
+}
+
+fn open_store<'a>(store: &'a Option<Box<dyn DocumentStore>>, name: &str) -> crate::Result<&'a dyn DocumentStore> {
+    store.as_deref().ok_or(crate::Error::database_code(DatabaseErrorCode::Closed, format!("Database {name} is closed.")))
+}
+
+/// The file a collection is dumped to. Anything outside `[A-Za-z0-9_.-]` is percent-encoded, so names containing separators
+/// always stay a single file inside the dump directory (`..` just becomes `...ndjson`).
+fn dump_file_name(name: &str, format: DumpFormat) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'.' | b'-' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}"))
+        }
+    }
+    format!("{encoded}.{}", format.extension())
+}
+
+fn parse_dump(data: &[u8], format: DumpFormat, path: impl AsRef<str>) -> crate::Result<Vec<Document>> {
+    let mut documents: Vec<Document> = Vec::new();
+    match format {
+        DumpFormat::Json => {
+            let text = std::str::from_utf8(data).or(Err(crate::Error::string_encoding(data.len())))?;
+            for (line, content) in text.lines().enumerate().filter(|(_, content)| !content.trim().is_empty()) {
+                let value: serde_json::Value = serde_json::from_str(content).or_else(|e| Err(crate::Error::parse(path.as_ref(), DocumentFormat::Json, Some(line + 1), Some(e.column()), e.to_string())))?;
+                match bson::Bson::try_from(value) {
+                    Ok(bson::Bson::Document(document)) => documents.push(document),
+                    Ok(_) => return Err(crate::Error::parse(path.as_ref(), DocumentFormat::Json, Some(line + 1), None, "Expected a document.")),
+                    Err(e) => return Err(crate::Error::parse(path.as_ref(), DocumentFormat::Json, Some(line + 1), None, e.to_string()))
+                }
+            }
+        }
+        DumpFormat::Bson => {
+            let mut reader = std::io::Cursor::new(data);
+            while (reader.position() as usize) < data.len() {
+                documents.push(Document::from_reader(&mut reader).or_else(|e| Err(crate::Error::parse(path.as_ref(), DocumentFormat::Bson, None, None, e.to_string())))?);
+            }
+        }
+    }
+
+    Ok(documents)
+}
+
+fn snapshot_path(directory: &str, name: String) -> String {
+    match directory.trim_end_matches('/') {
+        "" => name,
+        directory => format!("{directory}/{name}")
+    }
+}
+
+async fn remove_database_files(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        tokio::fs::remove_dir_all(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+/// Copies a database (a single file, or a directory for PoloDB's RocksDB backend) to `destination`.
+/// The copy is staged next to the destination, so an existing destination is only replaced once the copy is complete.
+async fn replace_database_files(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let staging = staging_path(destination).await?;
+
+    let copied = if source.is_dir() {
+        copy_directory(source.to_path_buf(), staging.clone(), false).await
+    } else {
+        tokio::fs::copy(source, &staging).await.and(Ok(()))
+    };
+    if let Err(e) = copied {
+        let _ = remove_database_files(&staging).await;
+        return Err(e);
+    }
+
+    replace_with_staged(&staging, destination).await
+}
+
+/// Returns a hidden path next to `destination` to stage a copy at, creating the parent directory if needed.
+async fn staging_path(destination: &Path) -> std::io::Result<PathBuf> {
+    let name = destination.file_name().and_then(|n| Some(n.to_string_lossy().to_string())).unwrap_or_default();
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    Ok(destination.with_file_name(format!(".{name}.{}.partial", bson::Uuid::new())))
+}
+
+async fn replace_with_staged(staging: &Path, destination: &Path) -> std::io::Result<()> {
+    if destination.exists() {
+        remove_database_files(destination).await?;
+    }
+    tokio::fs::rename(staging, destination).await
+}
+
+pub struct Database<R: Runtime> {
+    context: Context<R>,
+    name: String,
+    path: String,
+}
+
+impl<R: Runtime> Clone for Database<R> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            name: self.name.clone(),
+            path: self.path.clone()
+        }
+    }
+}
+
+impl<R: Runtime> Database<R> {
+    /// Reserved collection storing the content hash index used by [Context::hash_file_cached]
+    pub const HASH_INDEX_COLLECTION: &'static str = "_persistence_hash_index";
+
+    /// Timestamp format used in snapshot names
+    pub const SNAPSHOT_TIMESTAMP: &'static str = "%Y%m%dT%H%M%S%.3fZ";
+
+    /// Reserved collection recording index definitions, since PoloDB can't list them
+    pub const INDEX_COLLECTION: &'static str = "_persistence_indexes";
+
+    /// Name of the manifest file written by [Database::dump]
+    pub const DUMP_MANIFEST: &'static str = "manifest.json";
+
+    /// Whether `collection` is reserved for the plugin's own bookkeeping
+    pub fn is_reserved(collection: impl AsRef<str>) -> bool {
+        collection.as_ref().starts_with("_persistence_")
+    }
+
+    pub(crate) fn create(context: Context<R>, name: String, path: String) -> Self {
+        Self {
+            context,
+            name,
+            path,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn absolute_path(&self) -> crate::Result<PathBuf> {
+        self.context.get_path(self.path())
+    }
+
+    pub(crate) async fn db_context(&self) -> crate::Result<ContextDB> {
+        if let Some(db) = self.context.databases().await?.lock().await.get(&self.name) {
+            Ok(db.clone())
+        } else {
+            Err(crate::Error::unknown_database(self.name()))
+        }
+    }
+
+    pub(crate) async fn db(&self) -> crate::Result<Arc<Mutex<Option<Box<dyn DocumentStore>>>>> {
+        Ok(self.db_context().await?.database.clone())
+    }
+
+    pub async fn backend(&self) -> crate::Result<StorageBackendKind> {
+        Ok(self.db_context().await?.backend)
+    }
+
+    pub async fn close(self) -> crate::Result<()> {
+        self.context.close_database(self.name()).await
+    }
+
+    pub async fn collections(&self) -> crate::Result<Vec<String>> {
+        let db = self.db().await?;
+        let database = db.lock().await;
+        open_store(&database, &self.name)?.collection_names()
+    }
+
+    pub async fn collection<T: Serialize + DeserializeOwned + Send + Sync>(&self, name: impl AsRef<str>) -> Collection<T, R> {
+        Collection::<T, R>::create(self.clone(), name.as_ref().to_string(), None)
+    }
+
+    pub async fn start_transaction(&self) -> crate::Result<Transaction<R>> {
+        let context = self.db_context().await?;
+        let db = context.database.lock().await;
+        let mut transactions = context.transactions.lock().await;
+        let new_id = bson::Uuid::new();
+        transactions.insert(new_id, Arc::new(Mutex::new(open_store(&db, &self.name)?.start_transaction()?)));
+        Ok(Transaction::<R>::create(self.clone(), new_id))
+    }
+
+    pub async fn get_transaction(&self, id: bson::Uuid) -> crate::Result<Transaction<R>> {
+        let context = self.db_context().await?;
+        let transactions = context.transactions.lock().await;
+        if let Some(_) = transactions.get(&id) {
+            Ok(Transaction::<R>::create(self.clone(), id))
+        } else {
+            Err(crate::Error::unknown_transaction(id.to_string()))
+        }
+    }
+
+    pub async fn commit_transaction(&self, id: bson::Uuid) -> crate::Result<()> {
+        let context = self.db_context().await?;
+        let _database = context.database.lock().await;
+        let removed = context.transactions.lock().await.remove(&id);
+        if let Some(mutex) = removed {
+            let transaction = mutex.lock().await;
+            transaction.commit()
+        } else {
+            Err(crate::Error::unknown_transaction(id.to_string()))
+        }
+    }
+
+    pub async fn rollback_transaction(&self, id: bson::Uuid) -> crate::Result<()> {
+        let context = self.db_context().await?;
+        let _database = context.database.lock().await;
+        let removed = context.transactions.lock().await.remove(&id);
+        if let Some(mutex) = removed {
+            let transaction = mutex.lock().await;
+            transaction.rollback()
+        } else {
+            Err(crate::Error::unknown_transaction(id.to_string()))
+        }
+    }
+
+    /// Copies the database to `path` (relative to the context root). The database is locked for the duration of the copy,
+    /// so the backup reflects every operation that completed before it started & none that started after.
+    /// PoloDB databases are closed (flushing them) while their files are copied & reopened afterwards, so they can't have open transactions.
+    pub async fn backup_to(&self, path: impl AsRef<str>, overwrite: bool) -> crate::Result<()> {
+        let destination = self.context.get_path(path.as_ref())?;
+        if destination.exists() && !overwrite {
+            return Err(crate::Error::filesystem_at("BACKUP", path.as_ref(), IoErrorKind::AlreadyExists, "Destination path already exists."));
+        }
+        self.context.ensure_no_open_databases(path.as_ref(), &destination).await?;
+
+        let context = self.db_context().await?;
+        let mut database = context.database.clone().lock_owned().await;
+        let transactions = context.transactions.lock().await;
+        if context.backend.rewrites_while_open() && !transactions.is_empty() {
+            return Err(crate::Error::path_in_use(self.path(), format!("Database {} has open transactions.", self.name)));
+        }
+        let staging = staging_path(&destination).await.or_else(|e| Err(crate::Error::filesystem_io("BACKUP", path.as_ref(), &e)))?;
+        let (name, target) = (self.name(), staging.clone());
+        let written = if context.backend.rewrites_while_open() {
+            let source = self.absolute_path()?;
+            drop(database.take());
+            let copied = tokio::task::spawn_blocking({
+                let source = source.clone();
+                move || copy_files(&source, &target).or_else(|e| Err(crate::Error::filesystem_io("BACKUP", target.to_string_lossy(), &e)))
+            })
+            .await
+            .or_else(|e| Err(crate::Error::filesystem("BACKUP", e.to_string())))
+            .and_then(|copied| copied);
+
+            // Reopened however the copy went, so the alias is never left without a store
+            match self.context.open_backend(context.backend, self.path(), &source) {
+                Ok(reopened) => {
+                    *database = Some(reopened);
+                    copied
+                }
+                Err(e) => Err(e)
+            }
+        } else {
+            tokio::task::spawn_blocking(move || open_store(&database, &name)?.backup(&target))
+                .await
+                .or_else(|e| Err(crate::Error::filesystem("BACKUP", e.to_string())))?
+        };
+        drop(transactions);
+        if let Err(e) = written {
+            let _ = remove_database_files(&staging).await;
+            return Err(e);
+        }
+
+        replace_with_staged(&staging, &destination).await.or_else(|e| Err(crate::Error::filesystem_io("BACKUP", path.as_ref(), &e)))
+    }
+
+    /// Replaces the contents of the database with the backup at `path`, then reopens it under the same alias.
+    /// Existing [Database] & [Collection] instances remain valid. Fails if the database has open transactions.
+    pub async fn restore_from(&self, path: impl AsRef<str>) -> crate::Result<()> {
+        if self.backend().await? == StorageBackendKind::Memory {
+            return Err(crate::Error::database_code(DatabaseErrorCode::Unsupported, "In-memory databases can't be restored in place. Open the backup as a new database instead."));
+        }
+        let source = self.context.get_path(path.as_ref())?;
+        let target = self.absolute_path()?;
+        if !source.exists() {
+            return Err(crate::Error::filesystem_at("RESTORE", path.as_ref(), IoErrorKind::NotFound, "Backup path does not exist."));
+        }
+        self.context.ensure_no_open_databases(path.as_ref(), &source).await?;
+
+        let context = self.db_context().await?;
+        let mut database = context.database.lock().await;
+        let transactions = context.transactions.lock().await;
+        if !transactions.is_empty() {
+            return Err(crate::Error::path_in_use(self.path(), format!("Database {} has open transactions.", self.name)));
+        }
+
+        let scratch = self.context.temp_dir().await?;
+        let scratch_path = self.context.get_path(&scratch)?;
+        let staged = scratch_path.join("restored");
+        let previous = scratch_path.join("previous");
+        let result = match replace_database_files(&source, &staged).await {
+            Ok(_) => {
+                // Dropping the store releases its files, so they can be swapped out
+                drop(database.take());
+                let swapped = match tokio::fs::rename(&target, &previous).await {
+                    Ok(_) => tokio::fs::rename(&staged, &target).await,
+                    Err(e) => Err(e)
+                };
+                let reopened = swapped
+                    .or_else(|e| Err(crate::Error::filesystem_io("RESTORE", path.as_ref(), &e)))
+                    .and_then(|_| self.context.open_backend(context.backend, self.path(), &target));
+
+                match reopened {
+                    Ok(restored) => {
+                        *database = Some(restored);
+                        Ok(())
+                    }
+                    Err(e) => {
+                        // Put the original database back before reporting the failure
+                        if previous.exists() {
+                            let _ = remove_database_files(&target).await;
+                            let _ = tokio::fs::rename(&previous, &target).await;
+                        }
+                        *database = self.context.open_backend(context.backend, self.path(), &target).ok();
+                        Err(e)
+                    }
+                }
+            }
+            Err(e) => Err(crate::Error::filesystem_io("RESTORE", path.as_ref(), &e))
+        };
+
+        drop(transactions);
+        drop(database);
+        let _ = self.context.remove_directory(&scratch).await;
+        result
+    }
+
+    /// Saves an in-memory database to `path` (relative to the context root) using `backend`, then keeps it open from there under
+    /// the same alias. Contents & indexes are copied. Fails if the database has open transactions or `path` already exists.
+    /// Returns a handle with the new path. Existing handles stay valid but still report the old path.
+    pub async fn persist(&self, path: impl AsRef<str>, backend: StorageBackendKind) -> crate::Result<Database<R>> {
+        if self.backend().await? != StorageBackendKind::Memory {
+            return Err(crate::Error::database_code(DatabaseErrorCode::Unsupported, "Only in-memory databases can be persisted."));
+        }
+        if backend == StorageBackendKind::Memory {
+            return Err(crate::Error::database_code(DatabaseErrorCode::Unsupported, "In-memory databases must be persisted to a file-backed backend."));
+        }
+        let destination = self.context.get_path(path.as_ref())?;
+        if destination.exists() {
+            return Err(crate::Error::filesystem_at("PERSIST", path.as_ref(), IoErrorKind::AlreadyExists, "Destination path already exists."));
+        }
+        self.context.ensure_no_open_databases(path.as_ref(), &destination).await?;
+
+        let context = self.db_context().await?;
+        let database = context.database.clone().lock_owned().await;
+        let transactions = context.transactions.lock().await;
+        if !transactions.is_empty() {
+            return Err(crate::Error::path_in_use(path.as_ref(), format!("Database {} has open transactions.", self.name)));
+        }
+
+        let key = self.context.database_key(backend, path.as_ref())?;
+        let staging = staging_path(&destination).await.or_else(|e| Err(crate::Error::filesystem_io("PERSIST", path.as_ref(), &e)))?;
+        let (name, target) = (self.name(), staging.clone());
+        let (mut database, copied) = tokio::task::spawn_blocking(move || {
+            let copied = backend.open(&target, key).and_then(|target| Self::copy_store(open_store(&database, &name)?, target.as_ref()));
+            (database, copied)
+        })
+        .await
+        .or_else(|e| Err(crate::Error::filesystem("PERSIST", e.to_string())))?;
+        if let Err(e) = copied {
+            let _ = remove_database_files(&staging).await;
+            return Err(e);
+        }
+
+        replace_with_staged(&staging, &destination).await.or_else(|e| Err(crate::Error::filesystem_io("PERSIST", path.as_ref(), &e)))?;
+        *database = Some(backend.open(&destination, key)?);
+        if let Some(db) = self.context.databases().await?.lock().await.get_mut(&self.name) {
+            db.path = path.as_ref().to_string();
+            db.backend = backend;
+        }
+
+        drop(transactions);
+        Ok(Database::<R>::create(self.context.clone(), self.name(), path.as_ref().to_string()))
+    }
+
+    /// Copies every collection from `source` to `target`, then recreates the indexes recorded in [Database::INDEX_COLLECTION]
+    fn copy_store(source: &dyn DocumentStore, target: &dyn DocumentStore) -> crate::Result<()> {
+        for name in source.collection_names()? {
+            let documents = source.collection(&name).find(Document::new(), FindOptions::default())?;
+            if !documents.is_empty() {
+                target.collection(&name).insert_many(documents)?;
+            }
+        }
+        for document in source.collection(Self::INDEX_COLLECTION).find(Document::new(), FindOptions::default())? {
+            let entry: IndexEntry = bson::from_document(document)?;
+            target.collection(&entry.collection).create_index(entry.model())?;
+        }
+
+        Ok(())
+    }
+
+    /// Backs the database up into `directory` under a timestamped name. If `keep` is set, the oldest snapshots beyond that count are removed afterwards.
+    pub async fn snapshot(&self, directory: impl AsRef<str>, keep: Option<u32>) -> crate::Result<SnapshotInfo> {
+        let created = chrono::Utc::now();
+        let path = snapshot_path(directory.as_ref(), format!("{}-{}", self.name, created.format(Self::SNAPSHOT_TIMESTAMP)));
+        self.backup_to(&path, false).await?;
+
+        if let Some(keep) = keep {
+            let snapshots = self.snapshots(directory.as_ref()).await?;
+            let excess = snapshots.len().saturating_sub(keep.max(1) as usize);
+            for snapshot in snapshots.into_iter().take(excess) {
+                remove_database_files(&self.context.get_path(&snapshot.path)?).await.or_else(|e| Err(crate::Error::filesystem_io("SNAPSHOT", &snapshot.path, &e)))?;
+            }
+        }
+
+        Ok(SnapshotInfo { path, created })
+    }
+
+    /// Lists snapshots of this database in `directory`, oldest first.
+    pub async fn snapshots(&self, directory: impl AsRef<str>) -> crate::Result<Vec<SnapshotInfo>> {
+        let resolved = self.context.get_path(directory.as_ref())?;
+        let prefix = format!("{}-", self.name);
+        let mut snapshots: Vec<SnapshotInfo> = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&resolved).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(snapshots),
+            Err(e) => return Err(crate::Error::filesystem_io("LIST_SNAPSHOTS", directory.as_ref(), &e))
+        };
+
+        while let Some(entry) = entries.next_entry().await.or_else(|e| Err(crate::Error::filesystem_io("LIST_SNAPSHOTS", directory.as_ref(), &e)))? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(timestamp) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+            if let Ok(created) = chrono::NaiveDateTime::parse_from_str(timestamp, Self::SNAPSHOT_TIMESTAMP) {
+                snapshots.push(SnapshotInfo { path: snapshot_path(directory.as_ref(), name.clone()), created: created.and_utc() });
+            }
+        }
+
+        snapshots.sort_by_key(|snapshot| snapshot.created);
+        Ok(snapshots)
+    }
+
+    /// Takes a snapshot every `schedule.interval` seconds, replacing any existing schedule. Passing `None` stops scheduled snapshots.
+    /// Schedules end when the database is closed.
+    pub async fn schedule_snapshots(&self, schedule: Option<SnapshotSchedule>) -> crate::Result<()> {
+        let context = self.db_context().await?;
+        let mut current = context.snapshots.lock().await;
+        if let Some(task) = current.take() {
+            task.abort();
         }
-    }
 
-    pub(crate) async fn file_ids(&self) -> Vec<bson::Uuid> {
-        let files = self.files().await;
-        let handles = files.lock().await;
-        let mut result: Vec<bson::Uuid> = Vec::new();
-        for id in handles.keys() {
-            result.push(id.clone());
+        if let Some(schedule) = schedule {
+            if schedule.interval == 0 || schedule.keep == 0 {
+                return Err(crate::Error::filesystem_at("SCHEDULE_SNAPSHOTS", &schedule.directory, IoErrorKind::InvalidInput, "Snapshot interval & keep count must be greater than zero."));
+            }
+            self.context.get_path(&schedule.directory)?;
+
+            let database = self.clone();
+            *current = Some(tauri::async_runtime::spawn(async move {
+                let mut ticker = tokio::time::interval(Duration::from_secs(schedule.interval));
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    if let Err(crate::Error::UnknownDatabase { .. }) = database.snapshot(&schedule.directory, Some(schedule.keep)).await {
+                        break;
+                    }
+                }
+            }));
         }
 
+        Ok(())
+    }
+
+    /// Writes a logical dump of the database to `directory` (relative to the context root): one file per collection (named after it,
+    /// percent-encoded), plus a manifest listing the dumped collections & their index definitions. Reserved collections are skipped.
+    /// Documents are read inside a transaction, so the dump is consistent across collections.
+    pub async fn dump(&self, directory: impl AsRef<str>, format: DumpFormat) -> crate::Result<()> {
+        let resolved = self.context.get_path(directory.as_ref())?;
+        tokio::fs::create_dir_all(&resolved).await.or_else(|e| Err(crate::Error::filesystem_io("DUMP", directory.as_ref(), &e)))?;
+
+        let collections = self.collections().await?.into_iter().filter(|name| !Self::is_reserved(name)).collect::<Vec<String>>();
+        let transaction = self.start_transaction().await?;
+        let result = async {
+            for name in &collections {
+                let mut data: Vec<u8> = Vec::new();
+                for document in transaction.collection::<Document>(name).find(bson::doc! {}, None, None, None).await? {
+                    match format {
+                        DumpFormat::Json => {
+                            serde_json::to_writer(&mut data, &bson::Bson::Document(document).into_relaxed_extjson()).or_else(|e| Err(crate::Error::serialization(e.to_string())))?;
+                            data.push(b'\n');
+                        }
+                        DumpFormat::Bson => document.to_writer(&mut data)?
+                    }
+                }
+                tokio::fs::write(resolved.join(dump_file_name(name, format)), data).await.or_else(|e| Err(crate::Error::filesystem_io("DUMP", directory.as_ref(), &e)))?;
+            }
+
+            let indexes = transaction.collection::<IndexEntry>(Self::INDEX_COLLECTION).find(bson::doc! {}, None, None, None).await?;
+            let manifest = DumpManifest {
+                format,
+                indexes: indexes.into_iter().filter(|index| collections.contains(&index.collection)).collect(),
+                collections: collections.clone()
+            };
+            let manifest = serde_json::to_vec_pretty(&manifest).or_else(|e| Err(crate::Error::serialization(e.to_string())))?;
+            tokio::fs::write(resolved.join(Self::DUMP_MANIFEST), manifest).await.or_else(|e| Err(crate::Error::filesystem_io("DUMP", directory.as_ref(), &e)))
+        }.await;
+
+        transaction.rollback().await?;
         result
     }
 
-    pub(crate) async fn db_ids(&self) -> Vec<String> {
-        let dbs = self.databases().await;
-        let bases = dbs.lock().await;
-        let mut result: Vec<String> = Vec::new();
-        for id in bases.keys() {
-            result.push(id.clone());
+    /// Loads a dump written by [Database::dump] from `directory`. Every collection in the dump is dropped & recreated (along with its indexes)
+    /// inside a single transaction, so a failed restore leaves the database unchanged. Collections not in the dump are left alone.
+    /// Not to be confused with [Database::restore_from], which replaces the database files with a physical backup.
+    pub async fn restore(&self, directory: impl AsRef<str>) -> crate::Result<()> {
+        let resolved = self.context.get_path(directory.as_ref())?;
+        let manifest_path = resolved.join(Self::DUMP_MANIFEST);
+        let manifest = tokio::fs::read(&manifest_path).await.or_else(|e| Err(crate::Error::filesystem_io("RESTORE_DUMP", directory.as_ref(), &e)))?;
+        let manifest: DumpManifest = DocumentFormat::Json.parse(&manifest, manifest_path.to_string_lossy())?;
+
+        let transaction = self.start_transaction().await?;
+        let result = async {
+            for name in &manifest.collections {
+                if Self::is_reserved(name) {
+                    continue;
+                }
+
+                let path = resolved.join(dump_file_name(name, manifest.format));
+                let data = tokio::fs::read(&path).await.or_else(|e| Err(crate::Error::filesystem_io("RESTORE_DUMP", directory.as_ref(), &e)))?;
+                let documents = parse_dump(&data, manifest.format, path.to_string_lossy())?;
+
+                let collection = transaction.collection::<Document>(name);
+                collection.drop().await?;
+                if !documents.is_empty() {
+                    collection.insert_many(documents).await?;
+                }
+                for index in manifest.indexes.iter().filter(|index| &index.collection == name) {
+                    collection.create_index(index.model()).await?;
+                }
+            }
+
+            Ok(())
+        }.await;
+
+        match result {
+            Ok(_) => transaction.commit().await,
+            Err(e) => {
+                let _ = transaction.rollback().await;
+                Err(e)
+            }
         }
+    }
 
-        result
+    /// Executes a single SQL statement with positional parameters, returning the number of rows changed.
+    /// Only available on backends with raw SQL access (currently [StorageBackendKind::Sqlite]).
+    pub async fn execute_sql(&self, sql: impl AsRef<str>, params: Vec<SqlValue>) -> crate::Result<usize> {
+        let db = self.db().await?;
+        let database = db.lock().await;
+        open_store(&database, &self.name)?.sql().ok_or_else(|| self.sql_unsupported())?.execute(sql.as_ref(), params)
     }
-    
-    pub async fn close(self) -> crate::Result<()> {
-        for handle_id in self.file_ids().await {
-            self.close_file_handle(handle_id).await?;
+
+    /// Runs a SQL query with positional parameters, returning its columns & rows.
+    pub async fn query_sql(&self, sql: impl AsRef<str>, params: Vec<SqlValue>) -> crate::Result<SqlRows> {
+        let db = self.db().await?;
+        let database = db.lock().await;
+        open_store(&database, &self.name)?.sql().ok_or_else(|| self.sql_unsupported())?.query(sql.as_ref(), params)
+    }
+
+    fn sql_unsupported(&self) -> crate::Error {
+        crate::Error::database_code(DatabaseErrorCode::Unsupported, format!("Database {} does not support SQL access.", self.name))
+    }
+}
+
+pub struct Transaction<R: Runtime> {
+    database: Database<R>,
+    id: bson::Uuid
+}
+
+impl<R: Runtime> Clone for Transaction<R> {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+            id: self.id
         }
+    }
+}
 
-        for db_id in self.db_ids().await {
-            self.close_database(db_id).await?;
+impl<R: Runtime> Transaction<R> {
+    pub(crate) fn create(database: Database<R>, id: bson::Uuid) -> Self {
+        Self {
+            database, id
         }
+    }
 
-        Ok(())
+    pub fn id(&self) -> bson::Uuid {
+        self.id
+    }
+
+    pub fn collection<T: Serialize + DeserializeOwned + Send + Sync>(&self, name: impl AsRef<str>) -> Collection<T, R> {
+        Collection::create(self.database.clone(), name.as_ref().to_string(), Some(self.id))
+    }
+
+    pub async fn commit(self) -> crate::Result<()> {
+        self.database.commit_transaction(self.id()).await
+    }
+
+    pub async fn rollback(self) -> crate::Result<()> {
+        self.database.rollback_transaction(self.id()).await
     }
 }
 
-pub struct Database<R: Runtime> {
+pub struct KvStore<R: Runtime> {
     context: Context<R>,
     name: String,
     path: String,
 }
 
-impl<R: Runtime> Clone for Database<R> {
+impl<R: Runtime> Clone for KvStore<R> {
     fn clone(&self) -> Self {
         Self {
             context: self.context.clone(),
@@ -374,13 +1854,9 @@ impl<R: Runtime> Clone for Database<R> {
     }
 }
 
-impl<R: Runtime> Database<R> {
+impl<R: Runtime> KvStore<R> {
     pub(crate) fn create(context: Context<R>, name: String, path: String) -> Self {
-        Self {
-            context,
-            name,
-            path,
-        }
+        Self { context, name, path }
     }
 
     pub fn name(&self) -> String {
@@ -395,105 +1871,178 @@ impl<R: Runtime> Database<R> {
         self.context.get_path(self.path())
     }
 
-    pub(crate) async fn db_context(&self) -> crate::Result<ContextDB> {
-        if let Some(db) = self.context.databases().await.lock().await.get(&self.name) {
-            Ok(db.clone())
+    pub(crate) async fn kv_context(&self) -> crate::Result<ContextKv> {
+        if let Some(kv) = self.context.kv_stores().await?.lock().await.get(&self.name) {
+            Ok(kv.clone())
         } else {
-            Err(crate::Error::unknown_database(self.name()))
+            Err(crate::Error::unknown_kv_store(self.name()))
         }
     }
 
-    pub(crate) async fn db(&self) -> crate::Result<Arc<Mutex<polodb_core::Database>>> {
-        Ok(self.db_context().await?.database.clone())
+    pub async fn encoding(&self) -> crate::Result<KvEncoding> {
+        Ok(self.kv_context().await?.store.encoding)
     }
 
     pub async fn close(self) -> crate::Result<()> {
-        self.context.close_database(self.name()).await
+        self.context.close_kv_store(self.name()).await
+    }
+
+    /// Runs `operation` against the store, inside `transaction` if specified. redb allows a single writer at a time,
+    /// so writes outside a transaction fail while one is open instead of blocking until it finishes.
+    async fn run<T>(&self, transaction: Option<bson::Uuid>, write: bool, operation: impl FnOnce(&KvDatabase, Option<&redb::WriteTransaction>) -> crate::Result<T>) -> crate::Result<T> {
+        let kv = self.kv_context().await?;
+        let transactions = kv.transactions.lock().await;
+        match transaction {
+            Some(id) => operation(&kv.store, Some(transactions.get(&id).ok_or_else(|| crate::Error::unknown_transaction(id.to_string()))?)),
+            None if write && !transactions.is_empty() => Err(crate::Error::database_code(DatabaseErrorCode::Busy, format!("Key-value store {} has an open transaction.", self.name))),
+            None => operation(&kv.store, None),
+        }
     }
 
-    pub async fn collections(&self) -> crate::Result<Vec<String>> {
-        let db = self.db().await?;
-        let database = db.lock().await;
-        Ok(database.list_collection_names().or_else(|e| Err(crate::Error::from(e)))?)
+    pub(crate) async fn get_in<T: DeserializeOwned>(&self, transaction: Option<bson::Uuid>, key: impl AsRef<str>) -> crate::Result<Option<T>> {
+        self.run(transaction, false, |store, transaction| match store.get(transaction, key.as_ref())? {
+            Some(data) => Ok(Some(store.encoding.decode(&data)?)),
+            None => Ok(None),
+        }).await
     }
 
-    pub async fn collection<T: Serialize + DeserializeOwned + Send + Sync>(&self, name: impl AsRef<str>) -> Collection<T, R> {
-        Collection::<T, R>::create(self.clone(), name.as_ref().to_string(), None)
+    pub(crate) async fn set_in<T: Serialize>(&self, transaction: Option<bson::Uuid>, key: impl AsRef<str>, value: &T) -> crate::Result<()> {
+        self.run(transaction, true, |store, transaction| store.set(transaction, key.as_ref(), &store.encoding.encode(value)?)).await
     }
 
-    pub async fn start_transaction(&self) -> crate::Result<Transaction<R>> {
-        let context = self.db_context().await?;
-        let db = context.database.lock().await;
-        let mut transactions = context.transactions.lock().await;
-        let new_id = bson::Uuid::new();
-        transactions.insert(new_id.clone(), Arc::new(Mutex::new(db.start_transaction().or_else(|e| Err(crate::Error::from(e)))?)));
-        Ok(Transaction::<R>::create(self.clone(), new_id))
+    pub(crate) async fn delete_in(&self, transaction: Option<bson::Uuid>, key: impl AsRef<str>) -> crate::Result<bool> {
+        self.run(transaction, true, |store, transaction| store.delete(transaction, key.as_ref())).await
     }
 
-    pub async fn get_transaction(&self, id: bson::Uuid) -> crate::Result<Transaction<R>> {
-        let context = self.db_context().await?;
-        let transactions = context.transactions.lock().await;
-        if let Some(_) = transactions.get(&id) {
-            Ok(Transaction::<R>::create(self.clone(), id.clone()))
+    pub(crate) async fn list_in<T: DeserializeOwned>(&self, transaction: Option<bson::Uuid>, prefix: impl AsRef<str>) -> crate::Result<Vec<KvEntry<T>>> {
+        self.run(transaction, false, |store, transaction| {
+            store
+                .list(transaction, prefix.as_ref())?
+                .into_iter()
+                .map(|(key, data)| Ok(KvEntry { key, value: store.encoding.decode(&data)? }))
+                .collect()
+        }).await
+    }
+
+    pub(crate) async fn compare_and_swap_in<T: Serialize>(&self, transaction: Option<bson::Uuid>, key: impl AsRef<str>, expected: Option<&T>, value: Option<&T>) -> crate::Result<bool> {
+        self.run(transaction, true, |store, transaction| {
+            let expected = expected.map(|expected| store.encoding.encode(expected)).transpose()?;
+            let value = value.map(|value| store.encoding.encode(value)).transpose()?;
+            store.compare_and_swap(transaction, key.as_ref(), expected.as_deref(), value.as_deref())
+        }).await
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: impl AsRef<str>) -> crate::Result<Option<T>> {
+        self.get_in(None, key).await
+    }
+
+    pub async fn set<T: Serialize>(&self, key: impl AsRef<str>, value: &T) -> crate::Result<()> {
+        self.set_in(None, key, value).await
+    }
+
+    /// Removes `key`, returning whether it existed
+    pub async fn delete(&self, key: impl AsRef<str>) -> crate::Result<bool> {
+        self.delete_in(None, key).await
+    }
+
+    /// Lists every entry whose key starts with `prefix`, in key order
+    pub async fn list<T: DeserializeOwned>(&self, prefix: impl AsRef<str>) -> crate::Result<Vec<KvEntry<T>>> {
+        self.list_in(None, prefix).await
+    }
+
+    /// Atomically replaces the value of `key` with `value` if it currently equals `expected`. `None` stands for a missing key on either side,
+    /// so this can also insert-if-absent or delete-if-unchanged. Returns whether the swap happened.
+    pub async fn compare_and_swap<T: Serialize>(&self, key: impl AsRef<str>, expected: Option<&T>, value: Option<&T>) -> crate::Result<bool> {
+        self.compare_and_swap_in(None, key, expected, value).await
+    }
+
+    /// Starts a write transaction. Only one transaction can be open per store.
+    pub async fn start_transaction(&self) -> crate::Result<KvTransaction<R>> {
+        let kv = self.kv_context().await?;
+        let mut transactions = kv.transactions.lock().await;
+        if !transactions.is_empty() {
+            return Err(crate::Error::database_code(DatabaseErrorCode::Busy, format!("Key-value store {} already has an open transaction.", self.name)));
+        }
+
+        let id = bson::Uuid::new();
+        transactions.insert(id, kv.store.begin()?);
+        Ok(KvTransaction::<R>::create(self.clone(), id))
+    }
+
+    pub async fn get_transaction(&self, id: bson::Uuid) -> crate::Result<KvTransaction<R>> {
+        if self.kv_context().await?.transactions.lock().await.contains_key(&id) {
+            Ok(KvTransaction::<R>::create(self.clone(), id))
         } else {
             Err(crate::Error::unknown_transaction(id.to_string()))
         }
     }
 
     pub async fn commit_transaction(&self, id: bson::Uuid) -> crate::Result<()> {
-        if let Some(mutex) = self.db_context().await?.transactions.lock().await.remove(&id) {
-            let transaction = mutex.lock().await;
-            transaction.commit().or_else(|e| Err(crate::Error::from(e)))
-        } else {
-            Err(crate::Error::unknown_transaction(id.to_string()))
+        let removed = self.kv_context().await?.transactions.lock().await.remove(&id);
+        match removed {
+            Some(transaction) => KvDatabase::commit(transaction),
+            None => Err(crate::Error::unknown_transaction(id.to_string())),
         }
     }
 
     pub async fn rollback_transaction(&self, id: bson::Uuid) -> crate::Result<()> {
-        if let Some(mutex) = self.db_context().await?.transactions.lock().await.remove(&id) {
-            let transaction = mutex.lock().await;
-            transaction.rollback().or_else(|e| Err(crate::Error::from(e)))
-        } else {
-            Err(crate::Error::unknown_transaction(id.to_string()))
+        let removed = self.kv_context().await?.transactions.lock().await.remove(&id);
+        match removed {
+            Some(transaction) => KvDatabase::rollback(transaction),
+            None => Err(crate::Error::unknown_transaction(id.to_string())),
         }
     }
 }
 
-pub struct Transaction<R: Runtime> {
-    database: Database<R>,
+pub struct KvTransaction<R: Runtime> {
+    store: KvStore<R>,
     id: bson::Uuid
 }
 
-impl<R: Runtime> Clone for Transaction<R> {
+impl<R: Runtime> Clone for KvTransaction<R> {
     fn clone(&self) -> Self {
         Self {
-            database: self.database.clone(),
-            id: self.id.clone()
+            store: self.store.clone(),
+            id: self.id
         }
     }
 }
 
-impl<R: Runtime> Transaction<R> {
-    pub(crate) fn create(database: Database<R>, id: bson::Uuid) -> Self {
-        Self {
-            database, id
-        }
+impl<R: Runtime> KvTransaction<R> {
+    pub(crate) fn create(store: KvStore<R>, id: bson::Uuid) -> Self {
+        Self { store, id }
     }
 
     pub fn id(&self) -> bson::Uuid {
-        self.id.clone()
+        self.id
     }
 
-    pub fn collection<T: Serialize + DeserializeOwned + Send + Sync>(&self, name: impl AsRef<str>) -> Collection<T, R> {
-        Collection::create(self.database.clone(), name.as_ref().to_string(), Some(self.id.clone()))
+    pub async fn get<T: DeserializeOwned>(&self, key: impl AsRef<str>) -> crate::Result<Option<T>> {
+        self.store.get_in(Some(self.id), key).await
+    }
+
+    pub async fn set<T: Serialize>(&self, key: impl AsRef<str>, value: &T) -> crate::Result<()> {
+        self.store.set_in(Some(self.id), key, value).await
+    }
+
+    pub async fn delete(&self, key: impl AsRef<str>) -> crate::Result<bool> {
+        self.store.delete_in(Some(self.id), key).await
+    }
+
+    pub async fn list<T: DeserializeOwned>(&self, prefix: impl AsRef<str>) -> crate::Result<Vec<KvEntry<T>>> {
+        self.store.list_in(Some(self.id), prefix).await
+    }
+
+    pub async fn compare_and_swap<T: Serialize>(&self, key: impl AsRef<str>, expected: Option<&T>, value: Option<&T>) -> crate::Result<bool> {
+        self.store.compare_and_swap_in(Some(self.id), key, expected, value).await
     }
 
     pub async fn commit(self) -> crate::Result<()> {
-        self.database.commit_transaction(self.id()).await
+        self.store.commit_transaction(self.id()).await
     }
 
     pub async fn rollback(self) -> crate::Result<()> {
-        self.database.rollback_transaction(self.id()).await
+        self.store.rollback_transaction(self.id()).await
     }
 }
 
@@ -507,7 +2056,7 @@ impl<R: Runtime> Clone for FileHandle<R> {
     fn clone(&self) -> Self {
         Self {
             context: self.context.clone(),
-            id: self.id.clone(),
+            id: self.id,
             path: self.path.clone()
         }
     }
@@ -519,7 +2068,7 @@ impl<R: Runtime> FileHandle<R> {
     }
 
     pub fn id(&self) -> bson::Uuid {
-        self.id.clone()
+        self.id
     }
 
     pub fn path(&self) -> String {
@@ -534,139 +2083,217 @@ impl<R: Runtime> FileHandle<R> {
         self.context.close_file_handle(self.id()).await
     }
 
-    async fn metadata(&self) -> ContextFileHandle {
-        self.context.files().await.lock().await.get(&self.id()).expect("File handle has been closed.").clone()
+    /// Fails with [crate::Error::UnknownFileHandle] once the handle has been closed
+    async fn metadata(&self) -> crate::Result<ContextFileHandle> {
+        self.context.files().await?.lock().await.get(&self.id()).cloned().ok_or(crate::Error::unknown_file_handle(self.id().to_string()))
     }
 
-    pub async fn mode(&self) -> FileHandleMode {
-        self.metadata().await.mode
+    pub async fn mode(&self) -> crate::Result<FileHandleMode> {
+        Ok(self.metadata().await?.mode)
     }
 
-    pub async fn handle(&self) -> async_dup::Arc<async_dup::Mutex<File>> {
-        self.metadata().await.handle.clone()
+    pub async fn handle(&self) -> crate::Result<async_dup::Arc<async_dup::Mutex<File>>> {
+        Ok(self.metadata().await?.handle.clone())
     }
-}
-
-pub(crate) enum CollectionType {
-    Standalone(polodb_core::Collection<Document>),
-    Transaction(polodb_core::TransactionalCollection<Document>)
-}
 
-impl CollectionT<Document> for CollectionType {
-    fn name(&self) -> &str {
-        match self {
-            Self::Standalone(c) => c.name(),
-            Self::Transaction(c) => c.name()
+    pub async fn info(&self) -> crate::Result<FileHandleInfo> {
+        let metadata = self.metadata().await?;
+        let mut handle = metadata.handle.lock();
+        let position = handle.stream_position().await.ok();
+        let size = handle.metadata().await.and_then(|m| Ok(m.len())).ok();
+        let compression = match &metadata.codec {
+            Some(codec) => match &*codec.lock().await {
+                FileCodec::Decompressor(decompressor) => decompressor.compression(),
+                FileCodec::Compressor(_) => metadata.mode.compression()
+            },
+            None => None
+        };
+        Ok(FileHandleInfo {
+            id: metadata.id,
+            path: metadata.path.clone(),
+            mode: metadata.mode.clone(),
+            position,
+            size,
+            opened: metadata.opened,
+            temporary: metadata.temporary,
+            compression
+        })
+    }
+
+    /// Writes `data` to the file, compressing & then encrypting it if the mode asks for it. Both work on streams, so some data is
+    /// only written once there's enough of it, & the end of the stream is only written when the handle is closed.
+    pub async fn write(&self, data: &[u8]) -> crate::Result<()> {
+        let metadata = self.metadata().await?;
+        let sync = metadata.mode.sync_on_write();
+        let mut handle = metadata.handle.lock();
+        let data: Cow<[u8]> = match &metadata.codec {
+            Some(codec) => match &mut *codec.lock().await {
+                FileCodec::Compressor(Some(compressor)) => Cow::Owned(compressor.push(data)?),
+                _ => return Err(crate::Error::filesystem_at("WRITE", &metadata.path, IoErrorKind::PermissionDenied, "Handle is not writeable."))
+            },
+            None => Cow::Borrowed(data)
+        };
+        let data: Cow<[u8]> = match &metadata.cipher {
+            Some(cipher) => match &mut *cipher.lock().await {
+                FileCipher::Writer(writer) => Cow::Owned(writer.push(&data)?),
+                FileCipher::Reader(_) => return Err(crate::Error::encryption("Encrypted handle is not writeable."))
+            },
+            None => data
+        };
+        handle.write_all(&data).await.or_else(|e| Err(crate::Error::from(e)))?;
+        if sync {
+            handle.sync_all().await.or_else(|e| Err(crate::Error::from(e)))?;
         }
-    }
 
-    fn count_documents(&self) -> polodb_core::Result<u64> {
-        match self {
-            Self::Standalone(c) => c.count_documents(),
-            Self::Transaction(c) => c.count_documents()
-        }
+        Ok(())
     }
 
-    fn update_one(&self, query: Document, update: Document) -> polodb_core::Result<polodb_core::results::UpdateResult> {
-        match self {
-            Self::Standalone(c) => c.update_one(query, update),
-            Self::Transaction(c) => c.update_one(query, update)
+    /// Reads up to `size` bytes, or everything that's left. Encrypted handles decrypt & then decompress what they read, so `size`
+    /// counts bytes after decompression.
+    pub async fn read(&self, size: Option<usize>) -> crate::Result<Vec<u8>> {
+        let metadata = self.metadata().await?;
+        if metadata.cipher.is_some() || metadata.codec.is_some() {
+            return self.read_stream(&metadata, size).await;
         }
-    }
 
-    fn update_one_with_options(&self, query: Document, update: Document, options: polodb_core::options::UpdateOptions) -> polodb_core::Result<polodb_core::results::UpdateResult> {
-        match self {
-            Self::Standalone(c) => c.update_one_with_options(query, update, options),
-            Self::Transaction(c) => c.update_one_with_options(query, update, options)
+        let mut handle = metadata.handle.lock();
+        if let Some(sz) = size {
+            let mut buffer: Vec<u8> = vec![0; sz];
+            let read = handle.read(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
+            buffer.truncate(read);
+            Ok(buffer)
+        } else {
+            let mut buffer: Vec<u8> = Vec::new();
+            handle.read_to_end(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
+            Ok(buffer)
         }
     }
 
-    fn update_many(&self, query: Document, update: Document) -> polodb_core::Result<polodb_core::results::UpdateResult> {
-        match self {
-            Self::Standalone(c) => c.update_many(query, update),
-            Self::Transaction(c) => c.update_many(query, update)
-        }
-    }
+    async fn read_stream(&self, metadata: &ContextFileHandle, size: Option<usize>) -> crate::Result<Vec<u8>> {
+        let mut cipher = match &metadata.cipher {
+            Some(cipher) => Some(cipher.lock().await),
+            None => None
+        };
+        let mut codec = match &metadata.codec {
+            Some(codec) => Some(codec.lock().await),
+            None => None
+        };
+        let mut reader = match cipher.as_deref_mut() {
+            Some(FileCipher::Reader(reader)) => Some(reader),
+            Some(FileCipher::Writer(_)) => return Err(crate::Error::encryption("Encrypted handle is not readable.")),
+            None => None
+        };
+        let mut decompressor = match codec.as_deref_mut() {
+            Some(FileCodec::Decompressor(decompressor)) => Some(decompressor),
+            Some(FileCodec::Compressor(_)) => return Err(crate::Error::filesystem_at("READ", &metadata.path, IoErrorKind::PermissionDenied, "Handle is not readable.")),
+            None => None
+        };
 
-    fn update_many_with_options(&self, query: Document, update: Document, options: polodb_core::options::UpdateOptions) -> polodb_core::Result<polodb_core::results::UpdateResult> {
-        match self {
-            Self::Standalone(c) => c.update_many_with_options(query, update, options),
-            Self::Transaction(c) => c.update_many_with_options(query, update, options)
-        }
-    }
+        let mut handle = metadata.handle.lock();
+        let mut buffer = vec![0; SEALED_CHUNK_SIZE];
+        loop {
+            let (finished, available) = match (&decompressor, &reader) {
+                (Some(decompressor), _) => (decompressor.finished(), decompressor.available()),
+                (None, Some(reader)) => (reader.finished(), reader.available()),
+                (None, None) => (true, 0)
+            };
+            if finished || size.is_some_and(|size| available >= size) {
+                break;
+            }
 
-    fn delete_one(&self, query: Document) -> polodb_core::Result<polodb_core::results::DeleteResult> {
-        match self {
-            Self::Standalone(c) => c.delete_one(query),
-            Self::Transaction(c) => c.delete_one(query)
+            let read = handle.read(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
+            let data = match reader.as_deref_mut() {
+                Some(reader) => {
+                    reader.feed(&buffer[..read], read == 0)?;
+                    reader.take(None)
+                }
+                None => buffer[..read].to_vec()
+            };
+            if let Some(decompressor) = decompressor.as_deref_mut() {
+                decompressor.push(&data, read == 0)?;
+            }
         }
-    }
 
-    fn delete_many(&self, query: Document) -> polodb_core::Result<polodb_core::results::DeleteResult> {
-        match self {
-            Self::Standalone(c) => c.delete_many(query),
-            Self::Transaction(c) => c.delete_many(query)
-        }
+        Ok(match (decompressor, reader) {
+            (Some(decompressor), _) => decompressor.take(size),
+            (None, Some(reader)) => reader.take(size),
+            (None, None) => Vec::new()
+        })
+    }
+
+    /// Flushes buffered writes to the file. Compressed handles flush their encoder as well, so everything written so far can be
+    /// decompressed. Encrypted handles can only write whole chunks, so up to the last 64KiB written are only persisted on close.
+    pub async fn flush(&self) -> crate::Result<()> {
+        let metadata = self.metadata().await?;
+        let mut handle = metadata.handle.lock();
+        let pending = match &metadata.codec {
+            Some(codec) => match &mut *codec.lock().await {
+                FileCodec::Compressor(Some(compressor)) => compressor.flush()?,
+                _ => Vec::new()
+            },
+            None => Vec::new()
+        };
+        let pending = match &metadata.cipher {
+            Some(cipher) => match &mut *cipher.lock().await {
+                FileCipher::Writer(writer) => writer.push(&pending)?,
+                FileCipher::Reader(_) => pending
+            },
+            None => pending
+        };
+        handle.write_all(&pending).await.or_else(|e| Err(crate::Error::from(e)))?;
+        handle.flush().await.or_else(|e| Err(crate::Error::from(e)))
     }
 
-    fn create_index(&self, index: polodb_core::IndexModel) -> polodb_core::Result<()> {
-        match self {
-            Self::Standalone(c) => c.create_index(index),
-            Self::Transaction(c) => c.create_index(index)
-        }
+    /// Like [FileHandle::flush], additionally syncing the file to disk
+    pub async fn sync(&self) -> crate::Result<()> {
+        self.flush().await?;
+        let mutex_handle = self.handle().await?;
+        let handle = mutex_handle.lock();
+        handle.sync_all().await.or_else(|e| Err(crate::Error::from(e)))
     }
 
-    fn drop_index(&self, name: impl AsRef<str>) -> polodb_core::Result<()> {
-        match self {
-            Self::Standalone(c) => c.drop_index(name),
-            Self::Transaction(c) => c.drop_index(name)
-        }
-    }
+    async fn try_lock(&self, exclusive: bool) -> crate::Result<()> {
+        let mutex_handle = self.handle().await?;
+        let handle = mutex_handle.lock();
+        let acquired = if exclusive {
+            AsyncFileExt::try_lock_exclusive(&*handle)
+        } else {
+            AsyncFileExt::try_lock_shared(&*handle)
+        }.or_else(|e| Err(crate::Error::from(e)))?;
 
-    fn drop(&self) -> polodb_core::Result<()> {
-        match self {
-            Self::Standalone(c) => c.drop(),
-            Self::Transaction(c) => c.drop()
+        if acquired {
+            Ok(())
+        } else {
+            Err(crate::Error::file_locked(self.path()))
         }
     }
 
-    fn insert_one(&self, doc: impl std::borrow::Borrow<Document>) -> polodb_core::Result<polodb_core::results::InsertOneResult>
-    where Document: Serialize {
-        match self {
-            Self::Standalone(c) => c.insert_one(doc),
-            Self::Transaction(c) => c.insert_one(doc)
-        }
+    pub async fn lock_shared(&self) -> crate::Result<()> {
+        self.try_lock(false).await
     }
 
-    fn insert_many(&self, docs: impl IntoIterator<Item = impl std::borrow::Borrow<Document>>) -> polodb_core::Result<polodb_core::results::InsertManyResult>
-    where Document: Serialize {
-        match self {
-            Self::Standalone(c) => c.insert_many(docs),
-            Self::Transaction(c) => c.insert_many(docs)
-        }
+    pub async fn lock_exclusive(&self) -> crate::Result<()> {
+        self.try_lock(true).await
     }
 
-    fn find(&self, filter: Document) -> polodb_core::action::Find<'_, '_, Document>
-    where Document: DeserializeOwned + Send + Sync {
-        match self {
-            Self::Standalone(c) => c.find(filter),
-            Self::Transaction(c) => c.find(filter)
-        }
+    pub async fn unlock(&self) -> crate::Result<()> {
+        let mutex_handle = self.handle().await?;
+        let handle = mutex_handle.lock();
+        AsyncFileExt::unlock(&*handle).or_else(|e| Err(crate::Error::from(e)))
     }
+}
 
-    fn find_one(&self, filter: Document) -> polodb_core::Result<Option<Document>>
-    where Document: DeserializeOwned + Send + Sync {
-        match self {
-            Self::Standalone(c) => c.find_one(filter),
-            Self::Transaction(c) => c.find_one(filter)
-        }
-    }
+/// A collection that keeps its database locked for as long as it's in use, so backups & restores never observe a half-applied operation.
+pub(crate) struct LockedCollection {
+    collection: Box<dyn DocumentCollection>,
+    _guard: OwnedMutexGuard<Option<Box<dyn DocumentStore>>>,
+}
 
-    fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> polodb_core::action::Aggregate<'_, '_> {
-        match self {
-            Self::Standalone(c) => c.aggregate(pipeline),
-            Self::Transaction(c) => c.aggregate(pipeline)
-        }
+impl Deref for LockedCollection {
+    type Target = dyn DocumentCollection;
+
+    fn deref(&self) -> &Self::Target {
+        self.collection.as_ref()
     }
 }
 
@@ -682,7 +2309,7 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Clone for Collec
         Self {
             database: self.database.clone(),
             name: self.name.clone(),
-            transaction_id: self.transaction_id.clone(),
+            transaction_id: self.transaction_id,
             _doctype: PhantomData
         }
     }
@@ -702,27 +2329,33 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Collection<T, R>
         self.name.clone()
     }
 
-    pub(crate) async fn collection(&self) -> crate::Result<CollectionType> {
-        let db = self.database.db().await?;
-        if let Some(id) = self.transaction_id {
+    fn in_collection(&self, error: crate::Error) -> crate::Error {
+        error.in_collection(&self.name, self.transaction_id)
+    }
+
+    pub(crate) async fn collection(&self) -> crate::Result<LockedCollection> {
+        let guard = self.database.db().await?.lock_owned().await;
+        let collection = if let Some(id) = self.transaction_id {
             let dbcon = self.database.db_context().await?;
             let transactions = dbcon.transactions.lock().await;
             if let Some(transaction) = transactions.get(&id) {
-                Ok(CollectionType::Transaction(transaction.lock().await.collection::<Document>(&self.name())))
+                transaction.lock().await.collection(&self.name())
             } else {
-                Err(crate::Error::unknown_transaction(id.to_string()))
+                return Err(crate::Error::unknown_transaction(id.to_string()));
             }
         } else {
-            Ok(CollectionType::Standalone(db.lock().await.collection::<Document>(&self.name())))
-        }
+            open_store(&guard, &self.database.name)?.collection(&self.name())
+        };
+
+        Ok(LockedCollection { collection, _guard: guard })
     }
 
     pub async fn count_documents(&self) -> crate::Result<u64> {
-        self.collection().await?.count_documents().or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.count_documents().or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn update_one(&self, query: Document, update: Document) -> crate::Result<UpdateResult> {
-        self.collection().await?.update_one(query, update).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.update_one(query, update, None).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn update_one_with_options(
@@ -731,11 +2364,11 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Collection<T, R>
         update: Document,
         options: UpdateOptions,
     ) -> crate::Result<UpdateResult> {
-        self.collection().await?.update_one_with_options(query, update, options).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.update_one(query, update, Some(options)).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn update_many(&self, query: Document, update: Document) -> crate::Result<UpdateResult> {
-        self.collection().await?.update_many(query, update).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.update_many(query, update, None).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn update_many_with_options(
@@ -744,31 +2377,57 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Collection<T, R>
         update: Document,
         options: UpdateOptions,
     ) -> crate::Result<UpdateResult> {
-        self.collection().await?.update_many_with_options(query, update, options).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.update_many(query, update, Some(options)).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn delete_one(&self, query: Document) -> crate::Result<DeleteResult> {
-        self.collection().await?.delete_one(query).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.delete_one(query).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn delete_many(&self, query: Document) -> crate::Result<DeleteResult> {
-        self.collection().await?.delete_many(query).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.delete_many(query).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn create_index(&self, index: IndexModel) -> crate::Result<()> {
-        self.collection().await?.create_index(index).or_else(|e| Err(crate::Error::from(e)))
+        let entry = IndexEntry::new(&self.name, &index);
+        self.collection().await?.create_index(index).or_else(|e| Err(self.in_collection(e)))?;
+        if let Some(indexes) = self.index_registry() {
+            indexes.delete_many(bson::doc! {"collection": &entry.collection, "name": &entry.name}).await?;
+            indexes.insert_one(entry).await?;
+        }
+
+        Ok(())
     }
 
     pub async fn drop_index(&self, name: impl AsRef<str>) -> crate::Result<()> {
-        self.collection().await?.drop_index(name).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.drop_index(name.as_ref()).or_else(|e| Err(self.in_collection(e)))?;
+        if let Some(indexes) = self.index_registry() {
+            indexes.delete_many(bson::doc! {"collection": self.name(), "name": name.as_ref()}).await?;
+        }
+
+        Ok(())
     }
 
     pub async fn drop(&self) -> crate::Result<()> {
-        self.collection().await?.drop().or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.drop().or_else(|e| Err(self.in_collection(e)))?;
+        if let Some(indexes) = self.index_registry() {
+            indexes.delete_many(bson::doc! {"collection": self.name()}).await?;
+        }
+
+        Ok(())
+    }
+
+    /// PoloDB can't list a collection's indexes, so their definitions are recorded alongside (in the same transaction, if any) for [Database::dump].
+    fn index_registry(&self) -> Option<Collection<IndexEntry, R>> {
+        if Database::<R>::is_reserved(&self.name) {
+            None
+        } else {
+            Some(Collection::create(self.database.clone(), Database::<R>::INDEX_COLLECTION.to_string(), self.transaction_id))
+        }
     }
 
     pub async fn insert_one(&self, doc: impl Borrow<T>) -> crate::Result<InsertOneResult> {
-        self.collection().await?.insert_one(bson::to_document(doc.borrow()).or_else(|e| Err(crate::Error::from(e)))?).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.insert_one(bson::to_document(doc.borrow()).or_else(|e| Err(crate::Error::from(e)))?).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn insert_many(
@@ -780,38 +2439,21 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Collection<T, R>
             serialized.push(bson::to_document(doc.borrow()).or_else(|e| Err(crate::Error::from(e)))?);
         }
 
-        self.collection().await?.insert_many(serialized).or_else(|e| Err(crate::Error::from(e)))
+        self.collection().await?.insert_many(serialized).or_else(|e| Err(self.in_collection(e)))
     }
 
     pub async fn find(&self, filter: Document, skip: Option<u64>, limit: Option<u64>, sort: Option<Document>) -> crate::Result<Vec<T>> {
         let mut results: Vec<T> = Vec::new();
-        let collection = self.collection().await?;
-        let mut find = collection.find(filter);
-        if let Some(_skip) = skip {
-            find = find.skip(_skip);
-        }
-
-        if let Some(_limit) = limit {
-            find = find.limit(_limit);
-        }
-
-        if let Some(_sort) = sort {
-            find = find.sort(_sort);
-        }
-
-        let docs: Vec<Result<Document, polodb_core::Error>> = find.run().or_else(|e| Err(crate::Error::from(e)))?.collect();
-        for dresult in docs {
-            results.push(match dresult {
-                Ok(doc) => bson::from_document::<T>(doc).or_else(|e| Err(crate::Error::from(e))),
-                Err(e) => Err(crate::Error::from(e))
-            }?);
+        let docs = self.collection().await?.find(filter, FindOptions { skip, limit, sort }).or_else(|e| Err(self.in_collection(e)))?;
+        for doc in docs {
+            results.push(bson::from_document::<T>(doc).or_else(|e| Err(crate::Error::from(e)))?);
         }
 
         Ok(results)
     }
 
     pub async fn find_one(&self, filter: Document) -> crate::Result<Option<T>> {
-        let raw = self.collection().await?.find_one(filter).or_else(|e| Err(crate::Error::from(e)))?;
+        let raw = self.collection().await?.find_one(filter).or_else(|e| Err(self.in_collection(e)))?;
         if let Some(doc) = raw {
             Ok(Some(bson::from_document::<T>(doc).or_else(|e| Err(crate::Error::from(e)))?))
         } else {
@@ -819,3 +2461,128 @@ impl<T: Serialize + DeserializeOwned + Send + Sync, R: Runtime> Collection<T, R>
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tauri::test::{mock_app, MockRuntime};
+
+    use super::*;
+    use crate::{types::ContextSpecifier, Persistence};
+
+    struct Sandbox {
+        app: tauri::App<MockRuntime>,
+        root: PathBuf,
+    }
+
+    impl Sandbox {
+        fn new() -> Self {
+            let app = mock_app();
+            app.manage(Persistence::new(app.handle().clone()));
+            crate::manage_state(app.handle(), crate::PluginBuilder::new());
+            let root = std::env::temp_dir().join(format!("persistence-stale-{}", bson::Uuid::new()));
+            Self { app, root }
+        }
+
+        fn persistence(&self) -> &Persistence<MockRuntime> {
+            self.app.state::<Persistence<MockRuntime>>().inner()
+        }
+
+        async fn context(&self) -> Context<MockRuntime> {
+            self.persistence().open_context("stale", self.root.to_string_lossy()).await.unwrap()
+        }
+
+        async fn close(&self) {
+            self.persistence().close_context(ContextSpecifier::Aliased { alias: "stale".to_string() }).await.unwrap();
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn assert_unknown_context<T: std::fmt::Debug>(result: crate::Result<T>) {
+        assert!(matches!(result, Err(crate::Error::UnknownContext { .. })), "expected UnknownContext, got {result:?}");
+    }
+
+    fn assert_unknown_file_handle<T: std::fmt::Debug>(result: crate::Result<T>) {
+        assert!(matches!(result, Err(crate::Error::UnknownFileHandle { .. })), "expected UnknownFileHandle, got {result:?}");
+    }
+
+    #[tokio::test]
+    async fn closed_context_accessors() {
+        let sandbox = Sandbox::new();
+        let context = sandbox.context().await;
+        sandbox.close().await;
+
+        assert_unknown_context(context.lock_owner().await);
+        assert_unknown_context(context.file_handles().await);
+        assert_unknown_context(context.close_all_file_handles().await);
+        assert_unknown_context(context.file_handle(bson::Uuid::new()).await.map(|h| h.id()));
+        assert_unknown_context(context.database("db").await.map(|db| db.name()));
+        assert_unknown_context(context.kv_store("kv").await.map(|kv| kv.name()));
+        assert_unknown_context(context.temp_dir().await);
+        assert_unknown_context(context.watch("", false).await);
+        assert_unknown_context(context.unwatch(bson::Uuid::new()).await);
+        assert_unknown_context(context.clone().close().await);
+    }
+
+    #[tokio::test]
+    async fn closed_context_open() {
+        let sandbox = Sandbox::new();
+        let context = sandbox.context().await;
+        sandbox.close().await;
+
+        assert_unknown_context(context.open_memory_database("db").await.map(|db| db.name()));
+        assert_unknown_context(context.open_kv_store("kv", "settings.redb").await.map(|kv| kv.name()));
+        assert_unknown_context(context.open_file_handle("file.txt", FileHandleMode::create_or_open(true)).await.map(|h| h.id()));
+        assert_unknown_context(context.temp_file().await.map(|h| h.id()));
+    }
+
+    #[tokio::test]
+    async fn closed_context_children() {
+        let sandbox = Sandbox::new();
+        let context = sandbox.context().await;
+        let database = context.open_memory_database("db").await.unwrap();
+        let collection = database.collection::<Document>("items").await;
+        let kv = context.open_kv_store("kv", "settings.redb").await.unwrap();
+        let handle = context.open_file_handle("file.txt", FileHandleMode::create_or_open(true)).await.unwrap();
+        sandbox.close().await;
+
+        assert_unknown_context(database.backend().await);
+        assert_unknown_context(database.collections().await);
+        assert_unknown_context(database.start_transaction().await.map(|t| t.id()));
+        assert_unknown_context(collection.count_documents().await);
+        assert_unknown_context(collection.insert_one(bson::doc! {"a": 1}).await.map(|_| ()));
+        assert_unknown_context(kv.encoding().await);
+        assert_unknown_context(kv.get::<String>("key").await);
+        assert_unknown_context(handle.info().await);
+        assert_unknown_context(handle.write(b"data").await);
+        assert_unknown_context(handle.close().await);
+    }
+
+    #[tokio::test]
+    async fn closed_file_handle() {
+        let sandbox = Sandbox::new();
+        let context = sandbox.context().await;
+        let handle = context.open_file_handle("file.txt", FileHandleMode::create_or_open(true)).await.unwrap();
+        let id = handle.id();
+        let stale = context.file_handle(id).await.unwrap();
+        handle.close().await.unwrap();
+
+        assert_unknown_file_handle(stale.mode().await);
+        assert_unknown_file_handle(stale.handle().await.map(|_| ()));
+        assert_unknown_file_handle(stale.info().await);
+        assert_unknown_file_handle(stale.write(b"data").await);
+        assert_unknown_file_handle(stale.read(None).await);
+        assert_unknown_file_handle(stale.flush().await);
+        assert_unknown_file_handle(stale.sync().await);
+        assert_unknown_file_handle(stale.lock_exclusive().await);
+        assert_unknown_file_handle(stale.unlock().await);
+        assert_unknown_file_handle(stale.close().await);
+        assert_unknown_file_handle(context.file_handle(id).await.map(|h| h.id()));
+        assert_unknown_file_handle(context.close_file_handle(id).await);
+        sandbox.close().await;
+    }
+}
@@ -0,0 +1,236 @@
+//! Encryption at rest with ChaCha20-Poly1305. Keys come from the keyring registered on [crate::PluginBuilder], never from JS.
+//!
+//! Encrypted data starts with a header holding a magic value, the stream nonce prefix & a key check tag, so a wrong key can be
+//! told apart from corrupted data. The rest is split into chunks sealed with the STREAM construction, which also detects
+//! reordered, dropped or truncated chunks.
+
+use std::{io::Write, path::{Path, PathBuf}, sync::Arc};
+
+use bson::Document;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, stream::{DecryptorBE32, EncryptorBE32}, Aead, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+
+use super::backend::{DocumentStore, StorageBackend};
+
+/// A 256-bit key
+pub type EncryptionKey = [u8; 32];
+
+/// Describes what a key is requested for
+#[derive(Clone, Debug)]
+pub struct KeyRequest {
+    /// Name of the context the data belongs to
+    pub context: String,
+
+    /// Path of the database or file, relative to the context root
+    pub path: String,
+}
+
+/// Supplies keys for encrypted databases & file handles. Returning `None` denies access.
+pub type Keyring = Arc<dyn Fn(&KeyRequest) -> Option<EncryptionKey> + Send + Sync>;
+
+const MAGIC: &[u8; 8] = b"TPPENC01";
+const NONCE_PREFIX_SIZE: usize = 7;
+const TAG_SIZE: usize = 16;
+const HEADER_SIZE: usize = MAGIC.len() + NONCE_PREFIX_SIZE + TAG_SIZE;
+
+/// Size of the plaintext in each sealed chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of a full sealed chunk
+pub const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_SIZE;
+
+/// Nonce for the key check tag. STREAM nonces end in a 0 or 1 flag byte, so this one never collides with a chunk's.
+fn check_nonce(prefix: &[u8]) -> Nonce {
+    let mut nonce = [0xFF; 12];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[11] = 0x02;
+    Nonce::from(nonce)
+}
+
+/// Encrypts a stream of data written in arbitrary pieces
+pub struct StreamWriter {
+    encryptor: Option<EncryptorBE32<ChaCha20Poly1305>>,
+    header: Option<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl StreamWriter {
+    pub fn new(key: &EncryptionKey) -> crate::Result<Self> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+        let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut prefix);
+        let check = cipher
+            .encrypt(&check_nonce(&prefix), Payload { msg: &[], aad: MAGIC })
+            .or_else(|e| Err(crate::Error::encryption(e.to_string())))?;
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&prefix);
+        header.extend_from_slice(&check);
+        Ok(Self { encryptor: Some(EncryptorBE32::from_aead(cipher, (&prefix).into())), header: Some(header), buffer: Vec::new() })
+    }
+
+    /// Buffers `data`, returning the header & any chunks that are complete. The last chunk is only sealed by [StreamWriter::finish].
+    pub fn push(&mut self, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let Some(encryptor) = self.encryptor.as_mut() else {
+            return Err(crate::Error::encryption("Encrypted stream has already been finished."));
+        };
+        self.buffer.extend_from_slice(data);
+        let mut output = self.header.take().unwrap_or_default();
+        while self.buffer.len() > CHUNK_SIZE {
+            let chunk: Vec<u8> = self.buffer.drain(..CHUNK_SIZE).collect();
+            output.extend(encryptor.encrypt_next(chunk.as_slice()).or_else(|e| Err(crate::Error::encryption(e.to_string())))?);
+        }
+
+        Ok(output)
+    }
+
+    /// Seals the remaining data as the last chunk, returning everything not yet returned by [StreamWriter::push]
+    pub fn finish(&mut self) -> crate::Result<Vec<u8>> {
+        let Some(encryptor) = self.encryptor.take() else {
+            return Ok(Vec::new());
+        };
+        let mut output = self.header.take().unwrap_or_default();
+        output.extend(encryptor.encrypt_last(self.buffer.as_slice()).or_else(|e| Err(crate::Error::encryption(e.to_string())))?);
+        self.buffer.clear();
+        Ok(output)
+    }
+}
+
+/// Decrypts a stream of data read in arbitrary pieces
+pub struct StreamReader {
+    key: EncryptionKey,
+    path: String,
+    decryptor: Option<DecryptorBE32<ChaCha20Poly1305>>,
+    sealed: Vec<u8>,
+    plain: Vec<u8>,
+    finished: bool,
+}
+
+impl StreamReader {
+    /// `path` is only used in errors
+    pub fn new(key: &EncryptionKey, path: impl AsRef<str>) -> Self {
+        Self { key: *key, path: path.as_ref().to_string(), decryptor: None, sealed: Vec::new(), plain: Vec::new(), finished: false }
+    }
+
+    fn corrupted(&self) -> crate::Error {
+        crate::Error::encryption(format!("Encrypted data in {} is corrupted or truncated.", self.path))
+    }
+
+    /// Whether the last chunk has been decrypted
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Decrypted data that hasn't been taken yet
+    pub fn available(&self) -> usize {
+        self.plain.len()
+    }
+
+    /// Decrypts as much of `data` as possible. `end` marks the end of the stream, so the remainder is decrypted as the last chunk.
+    pub fn feed(&mut self, data: &[u8], end: bool) -> crate::Result<()> {
+        if self.finished {
+            return if data.is_empty() { Ok(()) } else { Err(self.corrupted()) };
+        }
+        self.sealed.extend_from_slice(data);
+
+        if self.decryptor.is_none() {
+            if self.sealed.len() < HEADER_SIZE {
+                return if end { Err(self.corrupted()) } else { Ok(()) };
+            }
+            let header: Vec<u8> = self.sealed.drain(..HEADER_SIZE).collect();
+            if &header[..MAGIC.len()] != MAGIC {
+                return Err(crate::Error::encryption(format!("{} is not encrypted.", self.path)));
+            }
+            let prefix = &header[MAGIC.len()..MAGIC.len() + NONCE_PREFIX_SIZE];
+            let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+            cipher
+                .decrypt(&check_nonce(prefix), Payload { msg: &header[MAGIC.len() + NONCE_PREFIX_SIZE..], aad: MAGIC })
+                .or(Err(crate::Error::wrong_key(&self.path)))?;
+            self.decryptor = Some(DecryptorBE32::from_aead(cipher, prefix.into()));
+        }
+
+        // A full chunk is only known not to be the last one once more data follows it
+        while self.sealed.len() > SEALED_CHUNK_SIZE {
+            let chunk: Vec<u8> = self.sealed.drain(..SEALED_CHUNK_SIZE).collect();
+            let decrypted = self.decryptor.as_mut().and_then(|d| d.decrypt_next(chunk.as_slice()).ok()).ok_or(self.corrupted())?;
+            self.plain.extend(decrypted);
+        }
+        if end {
+            let chunk = std::mem::take(&mut self.sealed);
+            let decrypted = self.decryptor.take().and_then(|d| d.decrypt_last(chunk.as_slice()).ok()).ok_or(self.corrupted())?;
+            self.plain.extend(decrypted);
+            self.finished = true;
+        }
+
+        Ok(())
+    }
+
+    /// Takes up to `size` bytes of decrypted data, or all of it
+    pub fn take(&mut self, size: Option<usize>) -> Vec<u8> {
+        let size = size.unwrap_or(self.plain.len()).min(self.plain.len());
+        self.plain.drain(..size).collect()
+    }
+}
+
+pub fn encrypt(key: &EncryptionKey, data: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut writer = StreamWriter::new(key)?;
+    let mut output = writer.push(data)?;
+    output.extend(writer.finish()?);
+    Ok(output)
+}
+
+/// `path` is only used in errors
+pub fn decrypt(key: &EncryptionKey, data: &[u8], path: impl AsRef<str>) -> crate::Result<Vec<u8>> {
+    let mut reader = StreamReader::new(key, path);
+    reader.feed(data, true)?;
+    Ok(reader.take(None))
+}
+
+/// A BSON document stored as a single encrypted file, replaced atomically on every save
+pub(crate) struct EncryptedFile {
+    pub path: PathBuf,
+    pub key: EncryptionKey,
+}
+
+impl EncryptedFile {
+    pub fn load(&self) -> crate::Result<Option<Document>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = std::fs::read(&self.path)?;
+        let decrypted = decrypt(&self.key, &data, self.path.to_string_lossy())?;
+        Ok(Some(Document::from_reader(decrypted.as_slice()).or_else(|e| Err(crate::Error::DeserializationError { reason: e.to_string() }))?))
+    }
+
+    /// Writes `contents` to `destination` (usually [EncryptedFile::path]) with this file's key
+    pub fn save(&self, destination: &Path, contents: &Document) -> crate::Result<()> {
+        let data = encrypt(&self.key, &bson::to_vec(contents)?)?;
+        let name = destination.file_name().and_then(|n| Some(n.to_string_lossy().to_string())).unwrap_or_default();
+        let temporary = destination.with_file_name(format!(".{name}.{}.tmp", bson::Uuid::new()));
+        let written = std::fs::File::create(&temporary).and_then(|mut file| {
+            file.write_all(&data)?;
+            file.sync_all()
+        });
+        if let Err(e) = written.and_then(|_| std::fs::rename(&temporary, destination)) {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(crate::Error::from(e));
+        }
+
+        Ok(())
+    }
+}
+
+/// [StorageBackend] keeping the database in memory & writing it to a single encrypted file after every change.
+/// Every write re-encrypts the whole database, so it's best suited to small databases.
+pub struct EncryptedBackend {
+    pub key: EncryptionKey,
+}
+
+impl StorageBackend for EncryptedBackend {
+    fn open(&self, path: &Path) -> crate::Result<Box<dyn DocumentStore>> {
+        super::memory::open_encrypted(EncryptedFile { path: path.to_path_buf(), key: self.key })
+    }
+}
@@ -1,6 +1,158 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+/// Stable code for the kind of an IO failure, mirroring [std::io::ErrorKind]
+pub enum IoErrorKind {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    ReadOnlyFilesystem,
+    StorageFull,
+    ResourceBusy,
+    CrossesDevices,
+    InvalidInput,
+    InvalidData,
+    UnexpectedEof,
+    TimedOut,
+    Interrupted,
+    WouldBlock,
+    Unsupported,
+    OutOfMemory,
+    #[default]
+    Other
+}
+
+impl From<std::io::ErrorKind> for IoErrorKind {
+    fn from(value: std::io::ErrorKind) -> Self {
+        use std::io::ErrorKind;
+        match value {
+            ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::PermissionDenied => Self::PermissionDenied,
+            ErrorKind::AlreadyExists => Self::AlreadyExists,
+            ErrorKind::InvalidInput => Self::InvalidInput,
+            ErrorKind::InvalidData => Self::InvalidData,
+            ErrorKind::UnexpectedEof => Self::UnexpectedEof,
+            ErrorKind::TimedOut => Self::TimedOut,
+            ErrorKind::Interrupted => Self::Interrupted,
+            ErrorKind::WouldBlock => Self::WouldBlock,
+            ErrorKind::Unsupported => Self::Unsupported,
+            ErrorKind::OutOfMemory => Self::OutOfMemory,
+            _ => Self::Other
+        }
+    }
+}
+
+/// Most filesystem-specific [std::io::ErrorKind]s were only stabilized after the crate's MSRV, so those are mapped from the OS error code instead.
+impl From<&std::io::Error> for IoErrorKind {
+    fn from(value: &std::io::Error) -> Self {
+        value.raw_os_error().and_then(Self::from_os_error).unwrap_or_else(|| value.kind().into())
+    }
+}
+
+impl IoErrorKind {
+    #[cfg(unix)]
+    fn from_os_error(code: i32) -> Option<Self> {
+        match code {
+            libc::ENOTDIR => Some(Self::NotADirectory),
+            libc::EISDIR => Some(Self::IsADirectory),
+            libc::ENOTEMPTY => Some(Self::DirectoryNotEmpty),
+            libc::EROFS => Some(Self::ReadOnlyFilesystem),
+            libc::ENOSPC | libc::EDQUOT => Some(Self::StorageFull),
+            libc::EBUSY | libc::ETXTBSY => Some(Self::ResourceBusy),
+            libc::EXDEV => Some(Self::CrossesDevices),
+            libc::ENAMETOOLONG => Some(Self::InvalidInput),
+            _ => None
+        }
+    }
+
+    /// Win32 error codes, as in `winerror.h`
+    #[cfg(windows)]
+    fn from_os_error(code: i32) -> Option<Self> {
+        match code {
+            267 => Some(Self::NotADirectory), // ERROR_DIRECTORY
+            145 => Some(Self::DirectoryNotEmpty), // ERROR_DIR_NOT_EMPTY
+            19 => Some(Self::ReadOnlyFilesystem), // ERROR_WRITE_PROTECT
+            39 | 112 | 1295 => Some(Self::StorageFull), // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL, ERROR_DISK_QUOTA_EXCEEDED
+            170 => Some(Self::ResourceBusy), // ERROR_BUSY
+            17 => Some(Self::CrossesDevices), // ERROR_NOT_SAME_DEVICE
+            123 | 206 => Some(Self::InvalidInput), // ERROR_INVALID_NAME, ERROR_FILENAME_EXCED_RANGE
+            _ => None
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn from_os_error(_: i32) -> Option<Self> {
+        None
+    }
+}
+
+impl From<&notify_debouncer_full::notify::Error> for IoErrorKind {
+    fn from(value: &notify_debouncer_full::notify::Error) -> Self {
+        use notify_debouncer_full::notify::ErrorKind;
+        match &value.kind {
+            ErrorKind::Io(e) => e.into(),
+            ErrorKind::PathNotFound | ErrorKind::WatchNotFound => Self::NotFound,
+            ErrorKind::MaxFilesWatch => Self::StorageFull,
+            _ => Self::Other
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+/// Stable code for the cause of a database failure, the same across storage backends
+pub enum DatabaseErrorCode {
+    /// A unique index or `_id` already has the key
+    DuplicateKey,
+    /// A field or operand has the wrong type for the operation
+    TypeMismatch,
+    /// The filter, update or index definition is malformed or unsupported
+    InvalidQuery,
+    /// The collection or index doesn't exist
+    NotFound,
+    /// A transaction conflicts with changes committed after it started
+    TransactionConflict,
+    /// The database or store is in use by another connection or transaction
+    Busy,
+    Closed,
+    Corrupted,
+    /// The backend doesn't support the operation
+    Unsupported,
+    Io,
+    #[default]
+    Other
+}
+
+impl From<&polodb_core::Error> for DatabaseErrorCode {
+    fn from(value: &polodb_core::Error) -> Self {
+        use polodb_core::Error as E;
+        match value {
+            E::DuplicateKey(_) | E::DataExist(_) | E::IndexAlreadyExists(_) | E::CollectionAlreadyExits(_) => Self::DuplicateKey,
+            E::UnexpectedIdType(..) | E::NotAValidKeyType(_) | E::FieldTypeUnexpected(_) | E::UnexpectedTypeForOp(_) | E::CannotApplyOperation(_)
+                | E::IncrementNullField | E::SetIsNotADocument => Self::TypeMismatch,
+            E::InvalidField(_) | E::ValidationError(_) | E::InvalidOrderOfIndex(_) | E::ParseError(_) | E::IllegalCollectionName(_) | E::IllegalIndexName(_)
+                | E::UnknownUpdateOperation(_) | E::UnableToUpdatePrimaryKey | E::RegexError(_) | E::UnknownAggregationOperation(_)
+                | E::InvalidAggregationStage(_) | E::UpsertError(_) | E::OnlySupportSingleFieldIndexes(_) | E::OnlySupportsAscendingOrder(_)
+                | E::DataHasNoPrimaryKey => Self::InvalidQuery,
+            E::CollectionNotFound(_) => Self::NotFound,
+            E::Busy | E::DatabaseOccupied => Self::Busy,
+            E::DbIsClosed | E::DbNotReady => Self::Closed,
+            E::ChecksumMismatch | E::NotAValidDatabase | E::PageMagicMismatch(_) | E::SaltMismatch | E::JournalPageSizeMismatch(..)
+                | E::VersionMismatch(_) => Self::Corrupted,
+            E::IOErr(_) => Self::Io,
+            // RocksDB reports optimistic transaction conflicts as busy or try-again statuses
+            E::RocksDbErr(message) if message.contains("Resource busy") || message.contains("Try again") => Self::TransactionConflict,
+            E::Multiple(errors) => errors.first().map(Self::from).unwrap_or_default(),
+            _ => Self::Other
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, thiserror::Error, Type)]
 #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum Error {
@@ -22,6 +174,14 @@ pub enum Error {
         reason: String
     },
 
+    #[error("Failed to open key-value store {name} in context {context} at {path}: {reason}")]
+    OpenKvStore {
+        name: String,
+        context: String,
+        path: String,
+        reason: String
+    },
+
     #[error("Failed to open {path} in {context}: {reason}")]
     OpenFileHandle {
         path: String,
@@ -35,12 +195,18 @@ pub enum Error {
     #[error("The requested database ({reason}) has not been opened.")]
     UnknownDatabase{ reason: String },
 
+    #[error("The requested key-value store ({reason}) has not been opened.")]
+    UnknownKvStore{ reason: String },
+
     #[error("The file handle with ID {reason} does not exist.")]
     UnknownFileHandle{ reason: String },
 
     #[error("Unknown transaction ID {reason} in current database.")]
     UnknownTransaction{ reason: String },
 
+    #[error("The watcher with ID {reason} does not exist.")]
+    UnknownWatcher{ reason: String },
+
     #[error("Invalid path: {reason}")]
     InvalidPath{ reason: String },
 
@@ -50,8 +216,21 @@ pub enum Error {
     #[error("Specified relative path escapes root path of this context: {reason}")]
     PathEscapesContext{ reason: String },
 
+    #[error("Specified path traverses a symlink, which this context does not allow: {reason}")]
+    SymlinkDenied{ reason: String },
+
     #[error("Encountered a database error: {reason}")]
-    DatabaseError{ reason: String },
+    DatabaseError {
+        reason: String,
+        #[serde(default)]
+        code: DatabaseErrorCode,
+        /// Collection the failing operation ran on, if known
+        #[serde(default)]
+        collection: Option<String>,
+        /// Transaction the failing operation ran in, if any
+        #[serde(default)]
+        transaction: Option<bson::Uuid>
+    },
 
     #[error("Serialization error: {reason}")]
     SerializationError{ reason: String },
@@ -60,13 +239,68 @@ pub enum Error {
     DeserializationError{ reason: String },
 
     #[error("Encountered an IO error: {reason}")]
-    IOError{ reason: String },
+    IOError {
+        reason: String,
+        #[serde(default)]
+        io_kind: IoErrorKind
+    },
 
     #[error("Failed to encode {reason} bytes as UTF-8 string.")]
     StringEncodingError{ reason: String },
 
     #[error("Filesystem operation failed ({operation}): {reason}")]
-    FilesystemError {operation: String, reason: String}
+    FilesystemError {
+        operation: String,
+        reason: String,
+        /// Path the operation failed on, relative to the context root
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        io_kind: Option<IoErrorKind>
+    },
+
+    #[error("Failed to parse {path} as {format} (line {line:?}, column {column:?}): {reason}")]
+    ParseError {
+        path: String,
+        format: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        reason: String
+    },
+
+    #[error("Unable to infer document format of {reason}")]
+    UnknownDocumentFormat{ reason: String },
+
+    #[error("Context at {path} is locked by process {pid:?} on host {hostname:?}")]
+    ContextLocked {
+        path: String,
+        pid: Option<u32>,
+        hostname: Option<String>
+    },
+
+    #[error("File {reason} is locked by another handle or process.")]
+    FileLocked{ reason: String },
+
+    #[error("Archive operation failed: {reason}")]
+    ArchiveError{ reason: String },
+
+    #[error("Path {path} is in use: {reason}")]
+    PathInUse {
+        path: String,
+        reason: String
+    },
+
+    #[error("Encryption error: {reason}")]
+    EncryptionError{ reason: String },
+
+    #[error("The encryption key for {reason} is incorrect.")]
+    WrongKey{ reason: String },
+
+    #[error("Context {alias} at {path} is outside the scope granted to this window.")]
+    ScopeDenied {
+        alias: String,
+        path: String
+    }
 }
 
 impl From<anyhow::Error> for Error {
@@ -75,9 +309,42 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+impl From<tauri::Error> for Error {
+    fn from(value: tauri::Error) -> Self {
+        Self::Unknown{reason: value.to_string()}
+    }
+}
+
 impl From<polodb_core::Error> for Error {
     fn from(value: polodb_core::Error) -> Self {
-        Self::DatabaseError{reason: value.to_string()}
+        let collection = match &value {
+            polodb_core::Error::DuplicateKey(duplicate) => Some(duplicate.ns.clone()),
+            polodb_core::Error::CollectionNotFound(name) => Some(name.clone()),
+            _ => None
+        };
+        Self::DatabaseError { reason: value.to_string(), code: DatabaseErrorCode::from(&value), collection, transaction: None }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(value: rusqlite::Error) -> Self {
+        use rusqlite::{ffi, ErrorCode};
+        let code = match &value {
+            rusqlite::Error::SqliteFailure(failure, _) => match failure.code {
+                ErrorCode::ConstraintViolation
+                    if failure.extended_code == ffi::SQLITE_CONSTRAINT_UNIQUE || failure.extended_code == ffi::SQLITE_CONSTRAINT_PRIMARYKEY => DatabaseErrorCode::DuplicateKey,
+                ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => DatabaseErrorCode::Busy,
+                ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase => DatabaseErrorCode::Corrupted,
+                ErrorCode::SystemIoFailure | ErrorCode::DiskFull | ErrorCode::CannotOpen => DatabaseErrorCode::Io,
+                ErrorCode::TypeMismatch => DatabaseErrorCode::TypeMismatch,
+                _ => DatabaseErrorCode::Other
+            },
+            rusqlite::Error::InvalidColumnType(..) | rusqlite::Error::FromSqlConversionFailure(..) | rusqlite::Error::ToSqlConversionFailure(_) => DatabaseErrorCode::TypeMismatch,
+            rusqlite::Error::SqlInputError { .. } | rusqlite::Error::InvalidParameterCount(..) | rusqlite::Error::InvalidColumnName(_) => DatabaseErrorCode::InvalidQuery,
+            _ => DatabaseErrorCode::Other
+        };
+        Self::database_code(code, value.to_string())
     }
 }
 
@@ -95,7 +362,7 @@ impl From<bson::de::Error> for Error {
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self::IOError {reason: value.to_string()}
+        Self::IOError {reason: value.to_string(), io_kind: (&value).into()}
     }
 }
 
@@ -120,6 +387,14 @@ impl Error {
         Self::UnknownDatabase{reason: name.as_ref().to_string()}
     }
 
+    pub fn open_kv_store(name: impl AsRef<str>, context: impl AsRef<str>, path: impl AsRef<str>, reason: impl AsRef<str>) -> Self {
+        Self::OpenKvStore { name: name.as_ref().to_string(), context: context.as_ref().to_string(), path: path.as_ref().to_string(), reason: reason.as_ref().to_string() }
+    }
+
+    pub fn unknown_kv_store(name: impl AsRef<str>) -> Self {
+        Self::UnknownKvStore{reason: name.as_ref().to_string()}
+    }
+
     pub fn no_absolute_path(path: impl AsRef<str>) -> Self {
         Self::NoAbsolutePaths{reason: path.as_ref().to_string()}
     }
@@ -128,6 +403,10 @@ impl Error {
         Self::PathEscapesContext{reason: path.as_ref().to_string()}
     }
 
+    pub fn symlink_denied(path: impl AsRef<str>) -> Self {
+        Self::SymlinkDenied{reason: path.as_ref().to_string()}
+    }
+
     pub fn open_file_handle(path: impl AsRef<str>, context: impl AsRef<str>, reason: impl AsRef<str>) -> Self {
         Self::OpenFileHandle { context: context.as_ref().to_string(), path: path.as_ref().to_string(), reason: reason.as_ref().to_string() }
     }
@@ -136,16 +415,89 @@ impl Error {
         Self::UnknownFileHandle{reason: id.as_ref().to_string()}
     }
 
+    pub fn unknown_watcher(id: impl AsRef<str>) -> Self {
+        Self::UnknownWatcher{reason: id.as_ref().to_string()}
+    }
+
     pub fn unknown_transaction(id: impl AsRef<str>) -> Self {
         Self::UnknownTransaction{reason: id.as_ref().to_string()}
     }
 
+    pub fn database(reason: impl AsRef<str>) -> Self {
+        Self::database_code(DatabaseErrorCode::Other, reason)
+    }
+
+    pub fn database_code(code: DatabaseErrorCode, reason: impl AsRef<str>) -> Self {
+        Self::DatabaseError { reason: reason.as_ref().to_string(), code, collection: None, transaction: None }
+    }
+
+    /// Records the collection & transaction a database error came from, unless the backend already reported them
+    pub fn in_collection(self, name: impl AsRef<str>, transaction: Option<bson::Uuid>) -> Self {
+        match self {
+            Self::DatabaseError { reason, code, collection, transaction: current } => Self::DatabaseError {
+                reason,
+                code,
+                collection: collection.or(Some(name.as_ref().to_string())),
+                transaction: current.or(transaction)
+            },
+            other => other
+        }
+    }
+
     pub fn string_encoding(size: usize) -> Self {
         Self::StringEncodingError{reason: size.to_string()}
     }
 
     pub fn filesystem(operation: impl AsRef<str>, reason: impl AsRef<str>) -> Self {
-        Self::FilesystemError { operation: operation.as_ref().to_string(), reason: reason.as_ref().to_string() }
+        Self::FilesystemError { operation: operation.as_ref().to_string(), reason: reason.as_ref().to_string(), path: None, io_kind: None }
+    }
+
+    pub fn filesystem_at(operation: impl AsRef<str>, path: impl AsRef<str>, kind: IoErrorKind, reason: impl AsRef<str>) -> Self {
+        Self::FilesystemError { operation: operation.as_ref().to_string(), reason: reason.as_ref().to_string(), path: Some(path.as_ref().to_string()), io_kind: Some(kind) }
+    }
+
+    pub fn filesystem_io(operation: impl AsRef<str>, path: impl AsRef<str>, error: &std::io::Error) -> Self {
+        Self::filesystem_at(operation, path, error.into(), error.to_string())
+    }
+
+    pub fn parse(path: impl AsRef<str>, format: impl ToString, line: Option<usize>, column: Option<usize>, reason: impl AsRef<str>) -> Self {
+        Self::ParseError { path: path.as_ref().to_string(), format: format.to_string(), line, column, reason: reason.as_ref().to_string() }
+    }
+
+    pub fn serialization(reason: impl AsRef<str>) -> Self {
+        Self::SerializationError{reason: reason.as_ref().to_string()}
+    }
+
+    pub fn unknown_document_format(path: impl AsRef<str>) -> Self {
+        Self::UnknownDocumentFormat{reason: path.as_ref().to_string()}
+    }
+
+    pub fn context_locked(path: impl AsRef<str>, owner: Option<crate::types::ContextLockOwner>) -> Self {
+        Self::ContextLocked { path: path.as_ref().to_string(), pid: owner.as_ref().and_then(|o| Some(o.pid)), hostname: owner.and_then(|o| Some(o.hostname)) }
+    }
+
+    pub fn file_locked(path: impl AsRef<str>) -> Self {
+        Self::FileLocked{reason: path.as_ref().to_string()}
+    }
+
+    pub fn archive(reason: impl AsRef<str>) -> Self {
+        Self::ArchiveError{reason: reason.as_ref().to_string()}
+    }
+
+    pub fn path_in_use(path: impl AsRef<str>, reason: impl AsRef<str>) -> Self {
+        Self::PathInUse { path: path.as_ref().to_string(), reason: reason.as_ref().to_string() }
+    }
+
+    pub fn encryption(reason: impl AsRef<str>) -> Self {
+        Self::EncryptionError{reason: reason.as_ref().to_string()}
+    }
+
+    pub fn wrong_key(path: impl AsRef<str>) -> Self {
+        Self::WrongKey{reason: path.as_ref().to_string()}
+    }
+
+    pub fn scope_denied(alias: impl AsRef<str>, path: impl AsRef<str>) -> Self {
+        Self::ScopeDenied { alias: alias.as_ref().to_string(), path: path.as_ref().to_string() }
     }
 }
 
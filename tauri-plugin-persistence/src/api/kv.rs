@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use redb::{ReadableTable, Table, TableDefinition, WriteTransaction};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{error::DatabaseErrorCode, types::KvEncoding};
+
+const ENTRIES: TableDefinition<&str, &[u8]> = TableDefinition::new("entries");
+const METADATA: TableDefinition<&str, &str> = TableDefinition::new("_persistence_metadata");
+
+/// Converts any redb error into a [crate::Error]
+trait RedbResult<T> {
+    fn or_database_error(self) -> crate::Result<T>;
+}
+
+impl<T, E: Into<redb::Error>> RedbResult<T> for Result<T, E> {
+    fn or_database_error(self) -> crate::Result<T> {
+        self.or_else(|e| {
+            let error: redb::Error = e.into();
+            let code = match &error {
+                redb::Error::DatabaseAlreadyOpen | redb::Error::TransactionInProgress => DatabaseErrorCode::Busy,
+                redb::Error::Corrupted(_) | redb::Error::UpgradeRequired(_) => DatabaseErrorCode::Corrupted,
+                redb::Error::TableTypeMismatch { .. } | redb::Error::TableIsMultimap(_) | redb::Error::TableIsNotMultimap(_)
+                    | redb::Error::TypeDefinitionChanged { .. } => DatabaseErrorCode::TypeMismatch,
+                redb::Error::TableDoesNotExist(_) => DatabaseErrorCode::NotFound,
+                redb::Error::Io(_) => DatabaseErrorCode::Io,
+                _ => DatabaseErrorCode::Other
+            };
+            Err(crate::Error::database_code(code, error.to_string()))
+        })
+    }
+}
+
+/// BSON can only encode documents at the top level, so BSON values are wrapped in one
+#[derive(Serialize, Deserialize)]
+struct BsonValue<T> {
+    value: T,
+}
+
+impl KvEncoding {
+    pub fn encode<T: Serialize>(&self, value: &T) -> crate::Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(value).or_else(|e| Err(crate::Error::serialization(e.to_string()))),
+            Self::Bson => Ok(bson::to_vec(&BsonValue { value })?),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> crate::Result<T> {
+        match self {
+            Self::Json => serde_json::from_slice(data).or_else(|e| Err(crate::Error::DeserializationError { reason: e.to_string() })),
+            Self::Bson => Ok(bson::from_slice::<BsonValue<T>>(data)?.value),
+        }
+    }
+
+    /// Compares two encoded values by content, so differences in key order or number formatting don't matter
+    fn same(&self, left: Option<&[u8]>, right: Option<&[u8]>) -> crate::Result<bool> {
+        match (left, right) {
+            (None, None) => Ok(true),
+            (Some(left), Some(right)) => match self {
+                Self::Json => Ok(self.decode::<serde_json::Value>(left)? == self.decode::<serde_json::Value>(right)?),
+                Self::Bson => Ok(self.decode::<bson::Bson>(left)? == self.decode::<bson::Bson>(right)?),
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+/// An open redb database holding a single table of encoded values.
+/// Reads outside a transaction see the last committed state, & redb allows only one write transaction at a time.
+pub struct KvDatabase {
+    database: redb::Database,
+    pub encoding: KvEncoding,
+}
+
+fn get(table: &impl ReadableTable<&'static str, &'static [u8]>, key: &str) -> crate::Result<Option<Vec<u8>>> {
+    Ok(table.get(key).or_database_error()?.map(|value| value.value().to_vec()))
+}
+
+fn list(table: &impl ReadableTable<&'static str, &'static [u8]>, prefix: &str) -> crate::Result<Vec<(String, Vec<u8>)>> {
+    let mut entries = Vec::new();
+    for entry in table.range(prefix..).or_database_error()? {
+        let (key, value) = entry.or_database_error()?;
+        if !key.value().starts_with(prefix) {
+            break;
+        }
+        entries.push((key.value().to_string(), value.value().to_vec()));
+    }
+
+    Ok(entries)
+}
+
+impl KvDatabase {
+    /// Opens or creates the store at `path`. Existing stores must have been created with the same `encoding`.
+    pub fn open(path: &Path, encoding: KvEncoding) -> crate::Result<Self> {
+        let database = redb::Database::create(path).or_database_error()?;
+        let transaction = database.begin_write().or_database_error()?;
+        {
+            transaction.open_table(ENTRIES).or_database_error()?;
+            let mut metadata = transaction.open_table(METADATA).or_database_error()?;
+            let stored = metadata.get("encoding").or_database_error()?.map(|value| value.value().to_string());
+            let expected = serde_json::to_string(&encoding).or_else(|e| Err(crate::Error::serialization(e.to_string())))?;
+            match stored {
+                Some(stored) if stored != expected => {
+                    return Err(crate::Error::database_code(DatabaseErrorCode::TypeMismatch, format!("Key-value store was created with encoding {stored}, not {expected}.")));
+                }
+                Some(_) => (),
+                None => {
+                    metadata.insert("encoding", expected.as_str()).or_database_error()?;
+                }
+            }
+        }
+        transaction.commit().or_database_error()?;
+
+        Ok(Self { database, encoding })
+    }
+
+    pub fn begin(&self) -> crate::Result<WriteTransaction> {
+        self.database.begin_write().or_database_error()
+    }
+
+    pub fn commit(transaction: WriteTransaction) -> crate::Result<()> {
+        transaction.commit().or_database_error()
+    }
+
+    pub fn rollback(transaction: WriteTransaction) -> crate::Result<()> {
+        transaction.abort().or_database_error()
+    }
+
+    pub fn get(&self, transaction: Option<&WriteTransaction>, key: &str) -> crate::Result<Option<Vec<u8>>> {
+        match transaction {
+            Some(transaction) => get(&transaction.open_table(ENTRIES).or_database_error()?, key),
+            None => get(&self.database.begin_read().or_database_error()?.open_table(ENTRIES).or_database_error()?, key),
+        }
+    }
+
+    pub fn list(&self, transaction: Option<&WriteTransaction>, prefix: &str) -> crate::Result<Vec<(String, Vec<u8>)>> {
+        match transaction {
+            Some(transaction) => list(&transaction.open_table(ENTRIES).or_database_error()?, prefix),
+            None => list(&self.database.begin_read().or_database_error()?.open_table(ENTRIES).or_database_error()?, prefix),
+        }
+    }
+
+    /// Runs `operation` in `transaction`, or in a new transaction that's committed if the operation succeeds
+    fn write<T>(&self, transaction: Option<&WriteTransaction>, operation: impl FnOnce(&mut Table<&'static str, &'static [u8]>) -> crate::Result<T>) -> crate::Result<T> {
+        if let Some(transaction) = transaction {
+            return operation(&mut transaction.open_table(ENTRIES).or_database_error()?);
+        }
+
+        let transaction = self.begin()?;
+        let result = operation(&mut transaction.open_table(ENTRIES).or_database_error()?)?;
+        Self::commit(transaction)?;
+        Ok(result)
+    }
+
+    pub fn set(&self, transaction: Option<&WriteTransaction>, key: &str, value: &[u8]) -> crate::Result<()> {
+        self.write(transaction, |table| table.insert(key, value).map(|_| ()).or_database_error())
+    }
+
+    /// Returns whether the key existed
+    pub fn delete(&self, transaction: Option<&WriteTransaction>, key: &str) -> crate::Result<bool> {
+        self.write(transaction, |table| Ok(table.remove(key).or_database_error()?.is_some()))
+    }
+
+    /// Sets `key` to `value` (or deletes it if `None`) only if its current value equals `expected` (`None` meaning absent).
+    /// Returns whether the swap happened.
+    pub fn compare_and_swap(&self, transaction: Option<&WriteTransaction>, key: &str, expected: Option<&[u8]>, value: Option<&[u8]>) -> crate::Result<bool> {
+        self.write(transaction, |table| {
+            let current = get(table, key)?;
+            if !self.encoding.same(current.as_deref(), expected)? {
+                return Ok(false);
+            }
+
+            match value {
+                Some(value) => table.insert(key, value).map(|_| ()).or_database_error()?,
+                None => table.remove(key).map(|_| ()).or_database_error()?,
+            }
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,411 @@
+//! In-memory storage backend, for tests & ephemeral state. Everything is discarded when the database is closed, unless the
+//! store is backed by an [EncryptedFile].
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use bson::{doc, oid::ObjectId, Bson, Document};
+use polodb_core::{
+    options::UpdateOptions,
+    results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult},
+    IndexModel, IndexOptions,
+};
+
+use super::{
+    crypto::EncryptedFile,
+    error::DatabaseErrorCode,
+    backend::{index_name, DocumentCollection, DocumentStore, FindOptions, PoloBackend, StorageBackend, StoreTransaction},
+    query,
+};
+
+#[derive(Clone)]
+struct MemoryIndex {
+    keys: Document,
+    unique: bool,
+}
+
+#[derive(Clone, Default)]
+struct MemoryCollectionData {
+    /// Documents keyed by their encoded `_id`
+    documents: BTreeMap<String, Document>,
+    indexes: BTreeMap<String, MemoryIndex>,
+}
+
+impl MemoryCollectionData {
+    /// Checks that no two documents share a key in a unique index. Documents missing every indexed field are skipped.
+    fn validate(&self) -> crate::Result<()> {
+        for (name, index) in self.indexes.iter().filter(|(_, index)| index.unique) {
+            let mut seen = HashSet::new();
+            for document in self.documents.values() {
+                let values: Vec<Bson> = index.keys.keys().map(|key| query::field(document, key).into_iter().next().unwrap_or(Bson::Null)).collect();
+                if values.iter().all(|value| value == &Bson::Null) {
+                    continue;
+                }
+                if !seen.insert(encode(&Bson::Array(values))) {
+                    return Err(crate::Error::database_code(DatabaseErrorCode::DuplicateKey, format!("Duplicate key for unique index {name}.")));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+struct MemoryState {
+    /// Incremented on every write, so transactions can detect changes committed since they started
+    version: u64,
+    collections: BTreeMap<String, MemoryCollectionData>,
+    /// Written after every change. Transactions work on a copy without one, so only commits are saved.
+    file: Option<Arc<EncryptedFile>>,
+}
+
+impl MemoryState {
+    fn contents(&self) -> Document {
+        let mut contents = Document::new();
+        for (name, data) in &self.collections {
+            let indexes: Vec<Document> = data
+                .indexes
+                .iter()
+                .map(|(name, index)| doc! { "name": name, "keys": index.keys.clone(), "unique": index.unique })
+                .collect();
+            contents.insert(name, doc! { "documents": data.documents.values().cloned().collect::<Vec<Document>>(), "indexes": indexes });
+        }
+
+        contents
+    }
+
+    fn load(contents: &Document) -> crate::Result<BTreeMap<String, MemoryCollectionData>> {
+        let invalid = |name: &str| crate::Error::DeserializationError { reason: format!("Invalid contents for collection {name}.") };
+        let mut collections = BTreeMap::new();
+        for (name, value) in contents {
+            let collection = value.as_document().ok_or(invalid(name))?;
+            let mut data = MemoryCollectionData::default();
+            for document in collection.get_array("documents").or(Err(invalid(name)))? {
+                let document = document.as_document().ok_or(invalid(name))?;
+                data.documents.insert(encode(document.get("_id").unwrap_or(&Bson::Null)), document.clone());
+            }
+            for index in collection.get_array("indexes").or(Err(invalid(name)))? {
+                let index = index.as_document().ok_or(invalid(name))?;
+                data.indexes.insert(
+                    index.get_str("name").or(Err(invalid(name)))?.to_string(),
+                    MemoryIndex { keys: index.get_document("keys").or(Err(invalid(name)))?.clone(), unique: index.get_bool("unique").unwrap_or(false) },
+                );
+            }
+            collections.insert(name.clone(), data);
+        }
+
+        Ok(collections)
+    }
+
+    /// Replaces the collections, keeping the previous ones if they can't be saved
+    fn replace(&mut self, collections: BTreeMap<String, MemoryCollectionData>) -> crate::Result<()> {
+        let previous = std::mem::replace(&mut self.collections, collections);
+        if let Some(file) = &self.file {
+            if let Err(e) = file.save(&file.path, &self.contents()) {
+                self.collections = previous;
+                return Err(e);
+            }
+        }
+
+        self.version += 1;
+        Ok(())
+    }
+}
+
+type SharedState = Arc<Mutex<MemoryState>>;
+
+fn lock(state: &SharedState) -> crate::Result<MutexGuard<'_, MemoryState>> {
+    state.lock().or_else(|_| Err(crate::Error::database("In-memory database was poisoned by a panic.")))
+}
+
+fn encode(value: &Bson) -> String {
+    value.clone().into_relaxed_extjson().to_string()
+}
+
+/// [StorageBackend] implementation keeping everything in memory. The path passed to [StorageBackend::open] is ignored.
+pub struct MemoryBackend;
+
+impl StorageBackend for MemoryBackend {
+    fn open(&self, _path: &Path) -> crate::Result<Box<dyn DocumentStore>> {
+        Ok(Box::new(MemoryStore(Arc::new(Mutex::new(MemoryState::default())))))
+    }
+}
+
+/// Opens a store holding the contents of `file`, which is rewritten after every change
+pub(crate) fn open_encrypted(file: EncryptedFile) -> crate::Result<Box<dyn DocumentStore>> {
+    let collections = match file.load()? {
+        Some(contents) => MemoryState::load(&contents)?,
+        None => {
+            if let Some(parent) = file.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            file.save(&file.path, &Document::new())?;
+            BTreeMap::new()
+        }
+    };
+
+    Ok(Box::new(MemoryStore(Arc::new(Mutex::new(MemoryState { version: 0, collections, file: Some(Arc::new(file)) })))))
+}
+
+struct MemoryStore(SharedState);
+
+impl DocumentStore for MemoryStore {
+    fn collection_names(&self) -> crate::Result<Vec<String>> {
+        Ok(lock(&self.0)?.collections.keys().cloned().collect())
+    }
+
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(MemoryCollection { name: name.to_string(), state: self.0.clone() })
+    }
+
+    /// Transactions work on a private copy of the database, which replaces it on commit. Committing fails if anything
+    /// else was written in the meantime, like PoloDB's own conflict detection.
+    fn start_transaction(&self) -> crate::Result<Box<dyn StoreTransaction>> {
+        let state = MemoryState { file: None, ..lock(&self.0)?.clone() };
+        Ok(Box::new(MemoryTransaction { shared: self.0.clone(), base: state.version, working: Arc::new(Mutex::new(state)) }))
+    }
+
+    /// Writes the contents to a new PoloDB database at `destination`, or to a new encrypted file with the same key if the
+    /// store is backed by one
+    fn backup(&self, destination: &Path) -> crate::Result<()> {
+        let state = lock(&self.0)?.clone();
+        if let Some(file) = &state.file {
+            return file.save(destination, &state.contents());
+        }
+        let target = PoloBackend.open(destination)?;
+        for (name, data) in state.collections {
+            let collection = target.collection(&name);
+            if !data.documents.is_empty() {
+                collection.insert_many(data.documents.into_values().collect())?;
+            }
+            for (index_name, index) in data.indexes {
+                collection.create_index(IndexModel {
+                    keys: index.keys,
+                    options: Some(IndexOptions { name: Some(index_name), unique: Some(index.unique) }),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct MemoryTransaction {
+    shared: SharedState,
+    base: u64,
+    working: SharedState,
+}
+
+impl StoreTransaction for MemoryTransaction {
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(MemoryCollection { name: name.to_string(), state: self.working.clone() })
+    }
+
+    fn commit(&self) -> crate::Result<()> {
+        let mut shared = lock(&self.shared)?;
+        if shared.version != self.base {
+            return Err(crate::Error::database_code(DatabaseErrorCode::TransactionConflict, "Transaction conflicts with changes committed after it started."));
+        }
+
+        let working = lock(&self.working)?;
+        shared.replace(working.collections.clone())
+    }
+
+    fn rollback(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+struct MemoryCollection {
+    name: String,
+    state: SharedState,
+}
+
+impl MemoryCollection {
+    fn read<T>(&self, operation: impl FnOnce(&MemoryCollectionData) -> crate::Result<T>) -> crate::Result<T> {
+        let state = lock(&self.state)?;
+        match state.collections.get(&self.name) {
+            Some(data) => operation(data),
+            None => operation(&MemoryCollectionData::default()),
+        }
+    }
+
+    /// Applies `operation` to a copy of the collection, which only replaces the original if the operation succeeds
+    /// & leaves every unique index intact. Each write is therefore atomic.
+    fn write<T>(&self, operation: impl FnOnce(&mut MemoryCollectionData) -> crate::Result<T>) -> crate::Result<T> {
+        let mut state = lock(&self.state)?;
+        let mut data = state.collections.get(&self.name).cloned().unwrap_or_default();
+        let result = operation(&mut data)?;
+        data.validate()?;
+        if state.file.is_some() {
+            let mut collections = state.collections.clone();
+            collections.insert(self.name.clone(), data);
+            state.replace(collections)?;
+        } else {
+            state.collections.insert(self.name.clone(), data);
+            state.version += 1;
+        }
+        Ok(result)
+    }
+
+    fn matching(data: &MemoryCollectionData, filter: &Document, limit: Option<usize>) -> crate::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for (key, document) in &data.documents {
+            if limit.is_some_and(|limit| keys.len() >= limit) {
+                break;
+            }
+            if query::matches(document, filter)? {
+                keys.push(key.clone());
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn insert(data: &mut MemoryCollectionData, mut document: Document) -> crate::Result<Bson> {
+        let id = match document.get("_id") {
+            Some(id) => id.clone(),
+            None => {
+                let id = Bson::ObjectId(ObjectId::new());
+                let mut with_id = doc! { "_id": id.clone() };
+                with_id.extend(document);
+                document = with_id;
+                id
+            }
+        };
+
+        let key = encode(&id);
+        if data.documents.contains_key(&key) {
+            return Err(crate::Error::database_code(DatabaseErrorCode::DuplicateKey, format!("Duplicate _id {id}.")));
+        }
+        data.documents.insert(key, document);
+        Ok(id)
+    }
+
+    fn update(&self, query: Document, update: Document, options: Option<UpdateOptions>, limit: Option<usize>) -> crate::Result<UpdateResult> {
+        self.write(|data| {
+            let keys = Self::matching(data, &query, limit)?;
+            let mut result = UpdateResult { matched_count: keys.len() as u64, modified_count: 0 };
+            for key in keys {
+                if let Some(document) = data.documents.get_mut(&key) {
+                    if query::apply_update(document, &update)? {
+                        result.modified_count += 1;
+                    }
+                }
+            }
+
+            if result.matched_count == 0 && options.and_then(|o| o.upsert).unwrap_or(false) {
+                Self::insert(data, query::upsert_document(&query, &update)?)?;
+            }
+            Ok(result)
+        })
+    }
+
+    fn delete(&self, query: Document, limit: Option<usize>) -> crate::Result<DeleteResult> {
+        self.write(|data| {
+            let keys = Self::matching(data, &query, limit)?;
+            for key in &keys {
+                data.documents.remove(key);
+            }
+            Ok(DeleteResult { deleted_count: keys.len() as u64 })
+        })
+    }
+}
+
+impl DocumentCollection for MemoryCollection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count_documents(&self) -> crate::Result<u64> {
+        self.read(|data| Ok(data.documents.len() as u64))
+    }
+
+    fn update_one(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        self.update(query, update, options, Some(1))
+    }
+
+    fn update_many(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        self.update(query, update, options, None)
+    }
+
+    fn delete_one(&self, query: Document) -> crate::Result<DeleteResult> {
+        self.delete(query, Some(1))
+    }
+
+    fn delete_many(&self, query: Document) -> crate::Result<DeleteResult> {
+        self.delete(query, None)
+    }
+
+    fn create_index(&self, index: IndexModel) -> crate::Result<()> {
+        let name = index_name(&index);
+        let unique = index.options.and_then(|o| o.unique).unwrap_or(false);
+        self.write(|data| {
+            data.indexes.insert(name, MemoryIndex { keys: index.keys, unique });
+            Ok(())
+        })
+    }
+
+    fn drop_index(&self, name: &str) -> crate::Result<()> {
+        self.write(|data| match data.indexes.remove(name) {
+            Some(_) => Ok(()),
+            None => Err(crate::Error::database_code(DatabaseErrorCode::NotFound, format!("Index {name} does not exist."))),
+        })
+    }
+
+    fn drop(&self) -> crate::Result<()> {
+        let mut state = lock(&self.state)?;
+        let mut collections = state.collections.clone();
+        collections.remove(&self.name);
+        state.replace(collections)
+    }
+
+    fn insert_one(&self, document: Document) -> crate::Result<InsertOneResult> {
+        self.write(|data| Ok(InsertOneResult { inserted_id: Self::insert(data, document)? }))
+    }
+
+    fn insert_many(&self, documents: Vec<Document>) -> crate::Result<InsertManyResult> {
+        self.write(|data| {
+            let mut inserted_ids = HashMap::new();
+            for (index, document) in documents.into_iter().enumerate() {
+                inserted_ids.insert(index, Self::insert(data, document)?);
+            }
+            Ok(InsertManyResult { inserted_ids })
+        })
+    }
+
+    fn find(&self, filter: Document, options: FindOptions) -> crate::Result<Vec<Document>> {
+        self.read(|data| {
+            let mut documents = Vec::new();
+            for document in data.documents.values() {
+                if query::matches(document, &filter)? {
+                    documents.push(document.clone());
+                }
+            }
+            if let Some(sort) = &options.sort {
+                documents = query::sort(documents, sort);
+            }
+
+            Ok(documents
+                .into_iter()
+                .skip(options.skip.unwrap_or(0) as usize)
+                .take(options.limit.map(|limit| limit as usize).unwrap_or(usize::MAX))
+                .collect())
+        })
+    }
+
+    fn find_one(&self, filter: Document) -> crate::Result<Option<Document>> {
+        self.read(|data| {
+            for document in data.documents.values() {
+                if query::matches(document, &filter)? {
+                    return Ok(Some(document.clone()));
+                }
+            }
+            Ok(None)
+        })
+    }
+}
@@ -1,10 +1,27 @@
 mod error;
 pub mod state;
+mod archive;
+mod backend;
+mod compression;
 mod context;
+mod crypto;
+mod kv;
+mod memory;
+mod query;
+mod resolve;
+mod scope;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 /// Exports a reference to various utility types.
 pub mod types;
 
-pub use state::{ContextDB, ContextFileHandle, ContextState, FileHandleMode};
-pub use error::{Error, Result};
-pub use context::{Context, FileHandle, Database, Collection, Transaction};
+pub use state::{ContextDB, ContextFileHandle, ContextKv, ContextState, ContextWatcher, FileHandleMode};
+pub use backend::{DocumentCollection, DocumentStore, FindOptions, PoloBackend, SqlStore, StorageBackend, StoreTransaction};
+pub use memory::MemoryBackend;
+pub use crypto::{EncryptedBackend, EncryptionKey, KeyRequest, Keyring};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteBackend;
+pub use scope::{ContextScope, ContextScopeEntry, ScopeTarget};
+pub use error::{DatabaseErrorCode, Error, IoErrorKind, Result};
+pub use context::{Context, FileHandle, Database, Collection, Transaction, KvStore, KvTransaction};
@@ -0,0 +1,493 @@
+//! MongoDB-style filters, sorting & update operators, evaluated in memory for backends without a native document query engine.
+
+use std::cmp::Ordering;
+
+use bson::{Bson, Document};
+
+use super::error::DatabaseErrorCode;
+
+fn unsupported(kind: &str, operator: &str) -> crate::Error {
+    crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("Unsupported {kind} operator: {operator}"))
+}
+
+/// Returns every value reachable at a dotted `path`, descending into arrays along the way.
+fn lookup(value: &Bson, path: &str) -> Vec<Bson> {
+    let (head, rest) = match path.split_once('.') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (path, None),
+    };
+    let child = match value {
+        Bson::Document(document) => return field(document, path),
+        Bson::Array(items) => match head.parse::<usize>() {
+            Ok(index) => items.get(index),
+            Err(_) => return items.iter().flat_map(|item| lookup(item, path)).collect(),
+        },
+        _ => None,
+    };
+
+    match (child, rest) {
+        (None, _) => vec![],
+        (Some(child), None) => vec![child.clone()],
+        (Some(child), Some(rest)) => lookup(child, rest),
+    }
+}
+
+/// Returns every value reachable at a dotted `path` in `document`
+pub fn field(document: &Document, path: &str) -> Vec<Bson> {
+    let (head, rest) = match path.split_once('.') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (path, None),
+    };
+
+    match (document.get(head), rest) {
+        (None, _) => vec![],
+        (Some(child), None) => vec![child.clone()],
+        (Some(child), Some(rest)) => lookup(child, rest),
+    }
+}
+
+fn type_rank(value: &Bson) -> u8 {
+    match value {
+        Bson::Null | Bson::Undefined | Bson::MinKey => 0,
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => 1,
+        Bson::String(_) | Bson::Symbol(_) => 2,
+        Bson::Document(_) => 3,
+        Bson::Array(_) => 4,
+        Bson::Binary(_) => 5,
+        Bson::ObjectId(_) => 6,
+        Bson::Boolean(_) => 7,
+        Bson::DateTime(_) => 8,
+        Bson::Timestamp(_) => 9,
+        Bson::RegularExpression(_) => 10,
+        _ => 11,
+    }
+}
+
+fn as_f64(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(v) => Some(*v as f64),
+        Bson::Int64(v) => Some(*v as f64),
+        Bson::Double(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(v) => Some(*v as i64),
+        Bson::Int64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// Total order over BSON values, ranking different types the way MongoDB does.
+pub fn compare(left: &Bson, right: &Bson) -> Ordering {
+    let (left_rank, right_rank) = (type_rank(left), type_rank(right));
+    if left_rank != right_rank {
+        return left_rank.cmp(&right_rank);
+    }
+
+    match (left, right) {
+        (Bson::String(a), Bson::String(b)) => a.cmp(b),
+        (Bson::Boolean(a), Bson::Boolean(b)) => a.cmp(b),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => a.bytes().cmp(&b.bytes()),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        (Bson::Timestamp(a), Bson::Timestamp(b)) => (a.time, a.increment).cmp(&(b.time, b.increment)),
+        (Bson::Binary(a), Bson::Binary(b)) => a.bytes.cmp(&b.bytes),
+        (Bson::Array(a), Bson::Array(b)) => compare_sequences(a.iter(), b.iter()),
+        (Bson::Document(a), Bson::Document(b)) => {
+            let left: Vec<Bson> = a.iter().flat_map(|(k, v)| [Bson::String(k.clone()), v.clone()]).collect();
+            let right: Vec<Bson> = b.iter().flat_map(|(k, v)| [Bson::String(k.clone()), v.clone()]).collect();
+            compare_sequences(left.iter(), right.iter())
+        }
+        _ => match (as_i64(left), as_i64(right)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => match (as_f64(left), as_f64(right)) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                _ => Ordering::Equal,
+            },
+        },
+    }
+}
+
+fn compare_sequences<'a>(mut left: impl Iterator<Item = &'a Bson>, mut right: impl Iterator<Item = &'a Bson>) -> Ordering {
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => match compare(a, b) {
+                Ordering::Equal => continue,
+                other => return other,
+            },
+        }
+    }
+}
+
+fn equals(left: &Bson, right: &Bson) -> bool {
+    match (as_f64(left), as_f64(right)) {
+        (Some(_), Some(_)) => compare(left, right) == Ordering::Equal,
+        _ => left == right,
+    }
+}
+
+/// Whether any of the values found at a path equal `operand`. Arrays match if they contain it, & `null` matches a missing field.
+fn matches_value(values: &[Bson], operand: &Bson) -> bool {
+    if values.is_empty() {
+        return matches!(operand, Bson::Null);
+    }
+
+    values.iter().any(|value| {
+        equals(value, operand)
+            || matches!(value, Bson::Array(items) if items.iter().any(|item| equals(item, operand)))
+    })
+}
+
+/// Values to compare against for range & pattern operators, with arrays expanded to their elements.
+fn candidates(values: &[Bson]) -> Vec<&Bson> {
+    values
+        .iter()
+        .flat_map(|value| match value {
+            Bson::Array(items) => items.iter().collect::<Vec<_>>(),
+            other => vec![other],
+        })
+        .collect()
+}
+
+fn is_operator_document(value: &Bson) -> bool {
+    matches!(value, Bson::Document(document) if !document.is_empty() && document.keys().all(|k| k.starts_with('$')))
+}
+
+fn compile_regex(pattern: &Bson, options: Option<&str>) -> crate::Result<regex::Regex> {
+    let (pattern, mut flags) = match pattern {
+        Bson::String(pattern) => (pattern.clone(), String::new()),
+        Bson::RegularExpression(regex) => (regex.pattern.clone(), regex.options.clone()),
+        other => return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("Invalid $regex pattern: {other}"))),
+    };
+    flags.push_str(options.unwrap_or_default());
+
+    regex::RegexBuilder::new(&pattern)
+        .case_insensitive(flags.contains('i'))
+        .multi_line(flags.contains('m'))
+        .dot_matches_new_line(flags.contains('s'))
+        .ignore_whitespace(flags.contains('x'))
+        .build()
+        .or_else(|e| Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("Invalid $regex pattern: {e}"))))
+}
+
+fn as_array<'a>(operator: &str, operand: &'a Bson) -> crate::Result<&'a Vec<Bson>> {
+    match operand {
+        Bson::Array(items) => Ok(items),
+        _ => Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("{operator} requires an array."))),
+    }
+}
+
+fn matches_operators(values: &[Bson], operators: &Document) -> crate::Result<bool> {
+    for (operator, operand) in operators {
+        let matched = match operator.as_str() {
+            "$eq" => matches_value(values, operand),
+            "$ne" => !matches_value(values, operand),
+            "$gt" | "$gte" | "$lt" | "$lte" => candidates(values).into_iter().any(|value| {
+                if type_rank(value) != type_rank(operand) {
+                    return false;
+                }
+                let ordering = compare(value, operand);
+                match operator.as_str() {
+                    "$gt" => ordering == Ordering::Greater,
+                    "$gte" => ordering != Ordering::Less,
+                    "$lt" => ordering == Ordering::Less,
+                    _ => ordering != Ordering::Greater,
+                }
+            }),
+            "$in" => as_array(operator, operand)?.iter().any(|item| matches_value(values, item)),
+            "$nin" => !as_array(operator, operand)?.iter().any(|item| matches_value(values, item)),
+            "$exists" => operand.as_bool().unwrap_or(true) != values.is_empty(),
+            "$regex" => {
+                let regex = compile_regex(operand, operators.get_str("$options").ok())?;
+                candidates(values).into_iter().any(|value| matches!(value, Bson::String(s) if regex.is_match(s)))
+            }
+            "$options" => true,
+            "$not" => match operand {
+                Bson::Document(inner) => !matches_operators(values, inner)?,
+                Bson::RegularExpression(_) => {
+                    let regex = compile_regex(operand, None)?;
+                    !candidates(values).into_iter().any(|value| matches!(value, Bson::String(s) if regex.is_match(s)))
+                }
+                _ => return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "$not requires an operator document.")),
+            },
+            "$size" => {
+                let size = as_i64(operand).ok_or_else(|| crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "$size requires an integer."))?;
+                values.iter().any(|value| matches!(value, Bson::Array(items) if items.len() as i64 == size))
+            }
+            "$elemMatch" => {
+                let Bson::Document(filter) = operand else {
+                    return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "$elemMatch requires a document."));
+                };
+                let mut found = false;
+                for item in values.iter().filter_map(|value| value.as_array()).flatten() {
+                    found = match item {
+                        Bson::Document(document) if !is_operator_document(operand) => matches(document, filter)?,
+                        other => matches_operators(std::slice::from_ref(other), filter)?,
+                    };
+                    if found {
+                        break;
+                    }
+                }
+                found
+            }
+            other => return Err(unsupported("query", other)),
+        };
+
+        if !matched {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn sub_filters<'a>(operator: &str, operand: &'a Bson) -> crate::Result<Vec<&'a Document>> {
+    as_array(operator, operand)?
+        .iter()
+        .map(|item| match item {
+            Bson::Document(filter) => Ok(filter),
+            _ => Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("{operator} requires an array of documents."))),
+        })
+        .collect()
+}
+
+/// Whether `document` matches a MongoDB-style `filter`.
+pub fn matches(document: &Document, filter: &Document) -> crate::Result<bool> {
+    for (key, condition) in filter {
+        let matched = match key.as_str() {
+            "$and" => {
+                let mut all = true;
+                for filter in sub_filters(key, condition)? {
+                    all = all && matches(document, filter)?;
+                }
+                all
+            }
+            "$or" | "$nor" => {
+                let mut any = false;
+                for filter in sub_filters(key, condition)? {
+                    any = any || matches(document, filter)?;
+                }
+                any == (key == "$or")
+            }
+            other if other.starts_with('$') => return Err(unsupported("query", other)),
+            path => {
+                let values = field(document, path);
+                match condition {
+                    Bson::Document(operators) if is_operator_document(condition) => matches_operators(&values, operators)?,
+                    Bson::RegularExpression(_) => matches_operators(&values, &bson::doc! { "$regex": condition.clone() })?,
+                    operand => matches_value(&values, operand),
+                }
+            }
+        };
+
+        if !matched {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Sorts documents by a MongoDB-style sort specification (`{ field: 1 | -1, ... }`). Missing fields sort first.
+pub fn sort(documents: Vec<Document>, specification: &Document) -> Vec<Document> {
+    let keys: Vec<(&str, bool)> = specification
+        .iter()
+        .map(|(key, direction)| (key.as_str(), as_f64(direction).unwrap_or(1.0) < 0.0))
+        .collect();
+    let mut decorated: Vec<(Vec<Bson>, Document)> = documents
+        .into_iter()
+        .map(|document| {
+            let values = keys.iter().map(|(key, _)| field(&document, key).into_iter().next().unwrap_or(Bson::Null)).collect();
+            (values, document)
+        })
+        .collect();
+
+    decorated.sort_by(|(a, _), (b, _)| {
+        for (index, (_, descending)) in keys.iter().enumerate() {
+            let ordering = compare(&a[index], &b[index]);
+            if ordering != Ordering::Equal {
+                return if *descending { ordering.reverse() } else { ordering };
+            }
+        }
+        Ordering::Equal
+    });
+    decorated.into_iter().map(|(_, document)| document).collect()
+}
+
+/// Returns the document holding the last segment of `path`, creating intermediate documents if `create` is set.
+fn parent<'a, 'p>(document: &'a mut Document, path: &'p str, create: bool) -> crate::Result<Option<(&'a mut Document, &'p str)>> {
+    let Some((head, rest)) = path.split_once('.') else {
+        return Ok(Some((document, path)));
+    };
+
+    if !document.contains_key(head) {
+        if !create {
+            return Ok(None);
+        }
+        document.insert(head, Document::new());
+    }
+
+    match document.get_mut(head) {
+        Some(Bson::Document(child)) => parent(child, rest, create),
+        _ if !create => Ok(None),
+        _ => Err(crate::Error::database_code(DatabaseErrorCode::TypeMismatch, format!("Cannot create field {rest} in non-document field {head}."))),
+    }
+}
+
+fn arithmetic(current: &Bson, operand: &Bson, multiply: bool) -> crate::Result<Bson> {
+    if let (Some(a), Some(b)) = (as_i64(current), as_i64(operand)) {
+        let result = if multiply { a.checked_mul(b) } else { a.checked_add(b) }
+            .ok_or_else(|| crate::Error::database_code(DatabaseErrorCode::TypeMismatch, "Integer overflow in update."))?;
+        if let (Bson::Int32(_), Bson::Int32(_), Ok(result)) = (current, operand, i32::try_from(result)) {
+            return Ok(Bson::Int32(result));
+        }
+        return Ok(Bson::Int64(result));
+    }
+
+    match (as_f64(current), as_f64(operand)) {
+        (Some(a), Some(b)) => Ok(Bson::Double(if multiply { a * b } else { a + b })),
+        _ => Err(crate::Error::database_code(DatabaseErrorCode::TypeMismatch, "Arithmetic update operators require numeric values.")),
+    }
+}
+
+fn each(operand: &Bson) -> Vec<Bson> {
+    match operand {
+        Bson::Document(document) if document.contains_key("$each") => match document.get("$each") {
+            Some(Bson::Array(items)) => items.clone(),
+            Some(other) => vec![other.clone()],
+            None => vec![],
+        },
+        other => vec![other.clone()],
+    }
+}
+
+fn apply_operator(document: &mut Document, operator: &str, path: &str, operand: &Bson) -> crate::Result<()> {
+    if operator == "$unset" {
+        if let Some((parent, key)) = parent(document, path, false)? {
+            parent.remove(key);
+        }
+        return Ok(());
+    }
+
+    if operator == "$rename" {
+        let target = operand.as_str().ok_or_else(|| crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "$rename requires a string."))?;
+        let value = match parent(document, path, false)? {
+            Some((parent, key)) => parent.remove(key),
+            None => None,
+        };
+        if let Some(value) = value {
+            apply_operator(document, "$set", target, &value)?;
+        }
+        return Ok(());
+    }
+
+    let (parent, key) = parent(document, path, true)?.ok_or_else(|| crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "Invalid update path."))?;
+    let current = parent.get(key).cloned();
+    let updated = match operator {
+        "$set" => operand.clone(),
+        "$inc" => arithmetic(current.as_ref().unwrap_or(&Bson::Int32(0)), operand, false)?,
+        "$mul" => arithmetic(current.as_ref().unwrap_or(&Bson::Int32(0)), operand, true)?,
+        "$min" | "$max" => match current {
+            Some(current) => {
+                let ordering = compare(operand, &current);
+                if (operator == "$min" && ordering == Ordering::Less) || (operator == "$max" && ordering == Ordering::Greater) {
+                    operand.clone()
+                } else {
+                    current
+                }
+            }
+            None => operand.clone(),
+        },
+        "$push" | "$addToSet" => {
+            let mut items = match current {
+                Some(Bson::Array(items)) => items,
+                None => vec![],
+                Some(_) => return Err(crate::Error::database_code(DatabaseErrorCode::TypeMismatch, format!("{operator} requires an array field."))),
+            };
+            for item in each(operand) {
+                if operator == "$push" || !items.iter().any(|existing| equals(existing, &item)) {
+                    items.push(item);
+                }
+            }
+            Bson::Array(items)
+        }
+        "$pop" => match current {
+            Some(Bson::Array(mut items)) => {
+                if !items.is_empty() {
+                    if as_f64(operand).unwrap_or(1.0) < 0.0 {
+                        items.remove(0);
+                    } else {
+                        items.pop();
+                    }
+                }
+                Bson::Array(items)
+            }
+            None => return Ok(()),
+            Some(_) => return Err(crate::Error::database_code(DatabaseErrorCode::TypeMismatch, "$pop requires an array field.")),
+        },
+        other => return Err(unsupported("update", other)),
+    };
+
+    parent.insert(key, updated);
+    Ok(())
+}
+
+/// Applies an update document, either a set of update operators or a full replacement, to `document`.
+/// The `_id` field can't be changed. Returns whether the document was modified.
+pub fn apply_update(document: &mut Document, update: &Document) -> crate::Result<bool> {
+    let original = document.clone();
+    let operators = update.keys().filter(|key| key.starts_with('$')).count();
+    if operators == 0 {
+        let mut replacement = update.clone();
+        if let Some(id) = original.get("_id") {
+            replacement.insert("_id", id.clone());
+        }
+        *document = replacement;
+    } else if operators != update.len() {
+        return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "Update documents can't mix operators & plain fields."));
+    } else {
+        for (operator, fields) in update {
+            let fields = match fields {
+                Bson::Document(fields) => fields,
+                _ => return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, format!("{operator} requires a document."))),
+            };
+            for (path, operand) in fields {
+                if path == "_id" || path.starts_with("_id.") {
+                    return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "The _id field can't be updated."));
+                }
+                apply_operator(document, operator, path, operand)?;
+            }
+        }
+    }
+
+    if document.get("_id") != original.get("_id") {
+        return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "The _id field can't be updated."));
+    }
+
+    Ok(*document != original)
+}
+
+/// Builds the document inserted by an upsert: the filter's equality conditions, with the update applied on top.
+pub fn upsert_document(filter: &Document, update: &Document) -> crate::Result<Document> {
+    let mut document = Document::new();
+    for (key, condition) in filter {
+        if key.starts_with('$') || is_operator_document(condition) || matches!(condition, Bson::RegularExpression(_)) {
+            continue;
+        }
+        apply_operator(&mut document, "$set", key, condition)?;
+    }
+
+    let mut upserted = Document::new();
+    if let Some(id) = document.remove("_id") {
+        upserted.insert("_id", id);
+    }
+    apply_update(&mut document, update)?;
+    upserted.extend(document);
+
+    Ok(upserted)
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::VecDeque,
+    path::{Component, Path, PathBuf},
+};
+
+use super::types::PathResolution;
+
+/// Maximum number of symlinks followed while resolving a single path, matching the usual OS limit.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ResolveError {
+    Absolute,
+    Escapes,
+    SymlinkDenied,
+    TooManySymlinks,
+}
+
+impl ResolveError {
+    pub(crate) fn into_error(self, path: impl AsRef<str>) -> crate::Error {
+        match self {
+            Self::Absolute => crate::Error::no_absolute_path(path),
+            Self::Escapes => crate::Error::path_escapes_context(path),
+            Self::SymlinkDenied => crate::Error::symlink_denied(path),
+            Self::TooManySymlinks => crate::Error::invalid_path(path),
+        }
+    }
+}
+
+/// Resolves `requested` (relative to the canonicalized `base`) one component at a time.
+///
+/// `..` components are applied to the path resolved so far rather than lexically, and symlinks in
+/// the existing prefix are handled according to `policy`. Components that don't exist yet are
+/// joined as-is, so paths about to be created resolve the same way as existing ones.
+pub(crate) fn resolve_path(base: &Path, requested: &Path, policy: PathResolution) -> Result<PathBuf, ResolveError> {
+    if requested.has_root() || requested.components().any(|c| matches!(c, Component::Prefix(_))) {
+        return Err(ResolveError::Absolute);
+    }
+
+    // With the follow policy, symlinks may point anywhere, but the requested path itself must not climb out of the root.
+    if policy == PathResolution::Follow && escapes_lexically(requested) {
+        return Err(ResolveError::Escapes);
+    }
+
+    let mut resolved = base.to_path_buf();
+    let mut pending: VecDeque<PathBuf> = requested.components().map(|c| PathBuf::from(c.as_os_str())).collect();
+    let mut hops = 0;
+
+    while let Some(component) = pending.pop_front() {
+        match component.components().next() {
+            None | Some(Component::CurDir) => {}
+            Some(Component::ParentDir) => {
+                if policy != PathResolution::Follow && resolved == base {
+                    return Err(ResolveError::Escapes);
+                }
+                resolved.pop();
+            }
+            Some(Component::RootDir) | Some(Component::Prefix(_)) => {
+                resolved = component;
+            }
+            Some(Component::Normal(name)) => {
+                let candidate = resolved.join(name);
+                match std::fs::symlink_metadata(&candidate) {
+                    Ok(meta) if meta.file_type().is_symlink() => {
+                        if policy == PathResolution::Deny {
+                            return Err(ResolveError::SymlinkDenied);
+                        }
+                        hops += 1;
+                        if hops > MAX_SYMLINK_HOPS {
+                            return Err(ResolveError::TooManySymlinks);
+                        }
+
+                        let target = std::fs::read_link(&candidate).or(Err(ResolveError::TooManySymlinks))?;
+                        for part in target.components().rev() {
+                            pending.push_front(PathBuf::from(part.as_os_str()));
+                        }
+                    }
+                    _ => resolved = candidate,
+                }
+            }
+        }
+    }
+
+    if policy != PathResolution::Follow && !resolved.starts_with(base) {
+        return Err(ResolveError::Escapes);
+    }
+
+    Ok(resolved)
+}
+
+/// Formats `path` relative to `base` with `/` separators, or returns `None` if it isn't inside `base`.
+pub(crate) fn relative_path(path: &Path, base: &Path) -> Option<String> {
+    path.strip_prefix(base).ok().map(|relative| {
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join("/")
+    })
+}
+
+/// Canonicalizes `path` even if it doesn't exist yet: the nearest existing ancestor is canonicalized, and the
+/// components below it are normalized lexically (they can't be symlinks, since they don't exist).
+pub(crate) fn canonicalize_nearest(path: &Path) -> std::io::Result<PathBuf> {
+    let mut existing = if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir()?.join(path) };
+    let mut missing = Vec::new();
+    let mut canonical = loop {
+        match existing.canonicalize() {
+            Ok(canonical) => break canonical,
+            Err(error) => match existing.components().next_back() {
+                Some(component @ (Component::Normal(_) | Component::CurDir | Component::ParentDir)) => {
+                    missing.push(component.as_os_str().to_os_string());
+                    existing.pop();
+                }
+                _ => return Err(error),
+            },
+        }
+    };
+
+    for component in missing.iter().rev() {
+        match Path::new(component).components().next() {
+            Some(Component::ParentDir) => {
+                canonical.pop();
+            }
+            Some(Component::Normal(name)) => canonical.push(name),
+            _ => {}
+        }
+    }
+
+    Ok(canonical)
+}
+
+fn escapes_lexically(requested: &Path) -> bool {
+    let mut depth: usize = 0;
+    for component in requested.components() {
+        match component {
+            Component::ParentDir => {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    struct Sandbox {
+        root: PathBuf,
+        base: PathBuf,
+        outside: PathBuf,
+    }
+
+    impl Sandbox {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("persistence-resolve-{}", bson::Uuid::new()));
+            std::fs::create_dir_all(&root).unwrap();
+            let root = root.canonicalize().unwrap();
+            let base = root.join("base");
+            let outside = root.join("outside");
+            std::fs::create_dir_all(base.join("inside/nested")).unwrap();
+            std::fs::create_dir_all(&outside).unwrap();
+            std::fs::write(base.join("inside/file.txt"), b"inside").unwrap();
+            std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+
+            symlink("inside", base.join("link_in")).unwrap();
+            symlink("../outside", base.join("link_out")).unwrap();
+            symlink(&outside, base.join("abs_out")).unwrap();
+            symlink(base.join("inside"), base.join("abs_in")).unwrap();
+            symlink("..", base.join("inside/up")).unwrap();
+            symlink("../..", base.join("inside/up_twice")).unwrap();
+            symlink("loop_b", base.join("loop_a")).unwrap();
+            symlink("loop_a", base.join("loop_b")).unwrap();
+            symlink("missing/target", base.join("dangling")).unwrap();
+
+            Self { root, base, outside }
+        }
+
+        fn resolve(&self, path: &str, policy: PathResolution) -> Result<PathBuf, ResolveError> {
+            resolve_path(&self.base, Path::new(path), policy)
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    const POLICIES: [PathResolution; 3] = [PathResolution::Deny, PathResolution::WithinRoot, PathResolution::Follow];
+
+    const ESCAPES: &[&str] = &[
+        "..",
+        "../",
+        "../outside/secret.txt",
+        "./..",
+        "inside/../..",
+        "inside/nested/../../..",
+        "inside/./../../outside",
+        "missing/../../outside",
+        "missing/../missing/../..",
+        "link_out/secret.txt",
+        "link_out",
+        "abs_out/secret.txt",
+        "link_in/../../outside",
+        "link_out/../base/inside",
+        "inside/up/..",
+        "inside/up_twice",
+        "inside/up_twice/outside/secret.txt",
+        "link_in/up_twice",
+    ];
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let sandbox = Sandbox::new();
+        for policy in POLICIES {
+            assert_eq!(sandbox.resolve("/etc/passwd", policy), Err(ResolveError::Absolute));
+            assert_eq!(sandbox.resolve(sandbox.outside.to_str().unwrap(), policy), Err(ResolveError::Absolute));
+        }
+    }
+
+    #[test]
+    fn rejects_escapes_within_root() {
+        let sandbox = Sandbox::new();
+        for path in ESCAPES {
+            assert!(sandbox.resolve(path, PathResolution::WithinRoot).is_err(), "{path} escaped with within_root");
+            assert!(sandbox.resolve(path, PathResolution::Deny).is_err(), "{path} escaped with deny");
+        }
+    }
+
+    #[test]
+    fn rejects_lexical_escapes_when_following() {
+        let sandbox = Sandbox::new();
+        for path in ["..", "inside/../..", "missing/../../outside", "link_in/../../outside", "./../base"] {
+            assert_eq!(sandbox.resolve(path, PathResolution::Follow), Err(ResolveError::Escapes), "{path}");
+        }
+        assert_eq!(sandbox.resolve("link_out/secret.txt", PathResolution::Follow).unwrap(), sandbox.outside.join("secret.txt"));
+    }
+
+    #[test]
+    fn resolves_paths_inside_root() {
+        let sandbox = Sandbox::new();
+        for policy in POLICIES {
+            assert_eq!(sandbox.resolve("", policy).unwrap(), sandbox.base);
+            assert_eq!(sandbox.resolve("inside/file.txt", policy).unwrap(), sandbox.base.join("inside/file.txt"));
+            assert_eq!(sandbox.resolve("inside/nested/../file.txt", policy).unwrap(), sandbox.base.join("inside/file.txt"));
+            assert_eq!(sandbox.resolve("new/dir/file.json", policy).unwrap(), sandbox.base.join("new/dir/file.json"));
+        }
+        for policy in [PathResolution::WithinRoot, PathResolution::Follow] {
+            assert_eq!(sandbox.resolve("link_in/file.txt", policy).unwrap(), sandbox.base.join("inside/file.txt"));
+            assert_eq!(sandbox.resolve("abs_in/nested", policy).unwrap(), sandbox.base.join("inside/nested"));
+            assert_eq!(sandbox.resolve("inside/up/inside", policy).unwrap(), sandbox.base.join("inside"));
+            assert_eq!(sandbox.resolve("dangling", policy).unwrap(), sandbox.base.join("missing/target"));
+        }
+    }
+
+    #[test]
+    fn deny_rejects_any_symlink() {
+        let sandbox = Sandbox::new();
+        for path in ["link_in", "link_in/file.txt", "abs_in", "inside/up", "dangling", "loop_a"] {
+            assert_eq!(sandbox.resolve(path, PathResolution::Deny), Err(ResolveError::SymlinkDenied), "{path}");
+        }
+    }
+
+    #[test]
+    fn detects_symlink_loops() {
+        let sandbox = Sandbox::new();
+        for policy in [PathResolution::WithinRoot, PathResolution::Follow] {
+            assert_eq!(sandbox.resolve("loop_a", policy), Err(ResolveError::TooManySymlinks));
+            assert_eq!(sandbox.resolve("loop_b/file", policy), Err(ResolveError::TooManySymlinks));
+        }
+    }
+
+    #[test]
+    fn canonicalizes_nearest_existing_ancestor() {
+        let sandbox = Sandbox::new();
+        let canonical = |path: PathBuf| canonicalize_nearest(&path).unwrap();
+        assert_eq!(canonical(sandbox.base.join("inside/../inside/nested")), sandbox.base.join("inside/nested"));
+        assert_eq!(canonical(sandbox.base.join("new/../../outside")), sandbox.outside);
+        assert_eq!(canonical(sandbox.base.join("new/deeper/../../..")), sandbox.root);
+        assert_eq!(canonical(sandbox.base.join("link_out/new")), sandbox.outside.join("new"));
+        assert_eq!(canonical(sandbox.base.join("abs_in/./new")), sandbox.base.join("inside/new"));
+    }
+
+    #[test]
+    fn fuzz_component_sequences() {
+        const PARTS: &[&str] = &["..", ".", "inside", "nested", "link_in", "link_out", "abs_out", "abs_in", "up", "up_twice", "missing", "base", "outside"];
+
+        let sandbox = Sandbox::new();
+        // Deterministic xorshift generator, so failures are reproducible
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..5000 {
+            let length = (next() % 7) as usize + 1;
+            let path = (0..length).map(|_| PARTS[(next() % PARTS.len() as u64) as usize]).collect::<Vec<&str>>().join("/");
+
+            for policy in [PathResolution::Deny, PathResolution::WithinRoot] {
+                if let Ok(resolved) = sandbox.resolve(&path, policy) {
+                    assert!(resolved.starts_with(&sandbox.base), "{path} resolved outside the root with {policy:?}: {resolved:?}");
+                    if let Ok(canonical) = resolved.canonicalize() {
+                        assert!(canonical.starts_with(&sandbox.base), "{path} canonicalized outside the root with {policy:?}: {canonical:?}");
+                    }
+                }
+            }
+
+            if escapes_lexically(Path::new(&path)) {
+                assert_eq!(sandbox.resolve(&path, PathResolution::Follow), Err(ResolveError::Escapes), "{path}");
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Per-context command scopes. Capabilities can attach [ContextScopeEntry] values to any of the plugin's permissions to limit
+//! which contexts a window may reach, by alias and/or root path:
+//!
+//! ```json
+//! {
+//!   "identifier": "persistence:default",
+//!   "allow": [{ "alias": "project-*", "path": "/home/*/Projects/**" }],
+//!   "deny": [{ "alias": "project-secrets" }]
+//! }
+//! ```
+//!
+//! Deny entries win over allow entries, and no allow entries means every context not denied is allowed.
+//! Scopes only apply to commands invoked from JS; the Rust API is never restricted.
+
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Deserializer};
+use specta::{datatype::DataType, function::FunctionArg, TypeCollection};
+use tauri::{
+    ipc::{CommandArg, CommandItem, CommandScope, InvokeError, ScopeObjectMatch},
+    AppHandle, Manager, Runtime,
+};
+
+use super::{resolve::canonicalize_nearest, state::{PluginState, SymlinkFollowing}, types::{ContextSpecifier, PathResolution}};
+
+/// A scope entry matching contexts by glob patterns. Omitted fields match anything.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ContextScopeEntry {
+    /// Pattern for the context alias
+    #[serde(default, deserialize_with = "pattern")]
+    pub alias: Option<Pattern>,
+
+    /// Pattern for the canonicalized context root path. `*` doesn't match `/`, `**` does.
+    #[serde(default, deserialize_with = "pattern")]
+    pub path: Option<Pattern>,
+}
+
+fn pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Pattern>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Pattern::new(&pattern).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// The context a command is about to act on
+#[derive(Clone, Debug)]
+pub struct ScopeTarget {
+    pub alias: String,
+    pub path: String,
+}
+
+impl ScopeObjectMatch for ContextScopeEntry {
+    type Input = ScopeTarget;
+
+    fn matches(&self, input: &ScopeTarget) -> bool {
+        let options = MatchOptions { require_literal_separator: true, ..Default::default() };
+        self.alias.as_ref().map_or(true, |alias| alias.matches(&input.alias))
+            && self.path.as_ref().map_or(true, |path| path.matches_with(&input.path, options))
+    }
+}
+
+/// The context scope granted to the invoking window, extracted in commands like [CommandScope]
+#[derive(Debug)]
+pub struct ContextScope(CommandScope<ContextScopeEntry>);
+
+impl ContextScope {
+    /// Whether the scope reaches a context
+    pub fn allows(&self, target: &ScopeTarget) -> bool {
+        self.0.matches(target)
+    }
+
+    /// Checks a [ContextSpecifier] against the scope before it's resolved, so out-of-scope contexts are never opened.
+    /// Aliases that aren't open are let through, since resolving them fails anyway. Contexts following symlinks
+    /// are denied unless [crate::PluginBuilder::follow_symlinks] was set.
+    pub(crate) async fn check<R: Runtime>(&self, app: &AppHandle<R>, context: &ContextSpecifier) -> crate::Result<()> {
+        let target = match context {
+            ContextSpecifier::Direct { alias, path, resolution, .. } => {
+                if *resolution == PathResolution::Follow && !app.state::<SymlinkFollowing>().0 {
+                    return Err(crate::Error::symlink_denied(path));
+                }
+                ScopeTarget { alias: alias.clone(), path: path.clone() }
+            }
+            ContextSpecifier::Aliased { alias } => {
+                let state = app.state::<PluginState>();
+                let contexts = state.lock().await;
+                match contexts.get(alias) {
+                    Some(context) => ScopeTarget { alias: alias.clone(), path: context.root_path.clone() },
+                    None => return Ok(())
+                }
+            }
+        };
+
+        self.ensure(target)
+    }
+
+    /// Checks a context about to be opened at `target.path`. The path is canonicalized first, so `..` components &
+    /// symlinks can't reach outside an allowed root; if it doesn't exist yet, its nearest existing ancestor is.
+    pub(crate) fn ensure(&self, target: ScopeTarget) -> crate::Result<()> {
+        let target = match canonicalize_nearest(std::path::Path::new(&target.path)) {
+            Ok(canonical) => ScopeTarget { path: canonical.to_string_lossy().to_string(), ..target },
+            Err(_) => return Err(crate::Error::scope_denied(target.alias, target.path))
+        };
+
+        if self.allows(&target) {
+            Ok(())
+        } else {
+            Err(crate::Error::scope_denied(target.alias, target.path))
+        }
+    }
+}
+
+impl<'a, R: Runtime> CommandArg<'a, R> for ContextScope {
+    fn from_command(command: CommandItem<'a, R>) -> Result<Self, InvokeError> {
+        CommandScope::from_command(command).map(Self)
+    }
+}
+
+// Injected by tauri rather than passed from JS, so it's left out of the generated bindings
+impl FunctionArg for ContextScope {
+    fn to_datatype(_: &mut TypeCollection) -> Option<DataType> {
+        None
+    }
+}
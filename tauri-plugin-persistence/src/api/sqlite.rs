@@ -0,0 +1,426 @@
+//! SQLite storage backend. Each collection is a table of `(_id, document)` rows, with documents stored as relaxed
+//! extended JSON so they stay readable from raw SQL through SQLite's JSON functions.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use bson::{oid::ObjectId, Bson, Document};
+use polodb_core::{
+    options::UpdateOptions,
+    results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult},
+    IndexModel,
+};
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    limits::Limit,
+    params, params_from_iter,
+    types::{Value, ValueRef},
+    Connection, DatabaseName, OptionalExtension,
+};
+
+use super::{
+    error::DatabaseErrorCode,
+    backend::{index_name, DocumentCollection, DocumentStore, FindOptions, SqlStore, StorageBackend, StoreTransaction},
+    query,
+    types::{SqlRows, SqlValue},
+};
+
+/// Tracks which collections exist, since collection tables are created lazily
+const COLLECTIONS_TABLE: &str = "_persistence_collections";
+
+/// How long a connection waits on a lock held by another connection (ie an open transaction) before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type SharedConnection = Arc<Mutex<Connection>>;
+
+fn quote(identifier: impl AsRef<str>) -> String {
+    format!("\"{}\"", identifier.as_ref().replace('"', "\"\""))
+}
+
+fn table(collection: &str) -> String {
+    quote(format!("collection:{collection}"))
+}
+
+fn lock(connection: &SharedConnection) -> crate::Result<MutexGuard<'_, Connection>> {
+    connection.lock().or_else(|_| Err(crate::Error::database("SQLite connection was poisoned by a panic.")))
+}
+
+fn connect(path: &Path) -> crate::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.pragma_update(None, "foreign_keys", true)?;
+
+    // Raw SQL from the frontend mustn't reach files outside the store, so attaching databases (which `VACUUM INTO` also does) is denied
+    connection.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)?;
+    connection.authorizer(Some(|context: AuthContext<'_>| match context.action {
+        AuthAction::Attach { .. } | AuthAction::Detach { .. } => Authorization::Deny,
+        _ => Authorization::Allow
+    }));
+    Ok(connection)
+}
+
+/// [StorageBackend] implementation for SQLite
+pub struct SqliteBackend;
+
+impl StorageBackend for SqliteBackend {
+    fn open(&self, path: &Path) -> crate::Result<Box<dyn DocumentStore>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = connect(path)?;
+        connection.execute_batch(&format!("CREATE TABLE IF NOT EXISTS {} (name TEXT PRIMARY KEY NOT NULL);", quote(COLLECTIONS_TABLE)))?;
+        Ok(Box::new(SqliteStore { path: path.to_path_buf(), connection: Arc::new(Mutex::new(connection)) }))
+    }
+}
+
+struct SqliteStore {
+    path: PathBuf,
+    connection: SharedConnection,
+}
+
+impl DocumentStore for SqliteStore {
+    fn collection_names(&self) -> crate::Result<Vec<String>> {
+        let connection = lock(&self.connection)?;
+        let mut statement = connection.prepare(&format!("SELECT name FROM {} ORDER BY name", quote(COLLECTIONS_TABLE)))?;
+        let names = statement.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>()?;
+        Ok(names)
+    }
+
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(SqliteCollection { name: name.to_string(), connection: self.connection.clone() })
+    }
+
+    /// Transactions get their own connection, so their changes stay invisible to the store until committed.
+    fn start_transaction(&self) -> crate::Result<Box<dyn StoreTransaction>> {
+        let connection = connect(&self.path)?;
+        connection.execute_batch("BEGIN")?;
+        Ok(Box::new(SqliteTransaction(Arc::new(Mutex::new(connection)))))
+    }
+
+    fn backup(&self, destination: &Path) -> crate::Result<()> {
+        Ok(lock(&self.connection)?.backup(DatabaseName::Main, destination, None)?)
+    }
+
+    fn sql(&self) -> Option<&dyn SqlStore> {
+        Some(self)
+    }
+}
+
+impl From<SqlValue> for Value {
+    fn from(value: SqlValue) -> Self {
+        match value {
+            SqlValue::Null => Value::Null,
+            SqlValue::Integer(value) => Value::Integer(value),
+            SqlValue::Real(value) => Value::Real(value),
+            SqlValue::Text(value) => Value::Text(value),
+            SqlValue::Blob(value) => Value::Blob(value),
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for SqlValue {
+    fn from(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Null => SqlValue::Null,
+            ValueRef::Integer(value) => SqlValue::Integer(value),
+            ValueRef::Real(value) => SqlValue::Real(value),
+            ValueRef::Text(value) => SqlValue::Text(String::from_utf8_lossy(value).to_string()),
+            ValueRef::Blob(value) => SqlValue::Blob(value.to_vec()),
+        }
+    }
+}
+
+impl SqlStore for SqliteStore {
+    fn execute(&self, sql: &str, params: Vec<SqlValue>) -> crate::Result<usize> {
+        let connection = lock(&self.connection)?;
+        Ok(connection.execute(sql, params_from_iter(params.into_iter().map(Value::from)))?)
+    }
+
+    fn query(&self, sql: &str, params: Vec<SqlValue>) -> crate::Result<SqlRows> {
+        let connection = lock(&self.connection)?;
+        let mut statement = connection.prepare(sql)?;
+        let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+        let mut rows = statement.query(params_from_iter(params.into_iter().map(Value::from)))?;
+        let mut result = SqlRows { columns, rows: Vec::new() };
+        while let Some(row) = rows.next()? {
+            let mut values = Vec::with_capacity(result.columns.len());
+            for index in 0..result.columns.len() {
+                values.push(SqlValue::from(row.get_ref(index)?));
+            }
+            result.rows.push(values);
+        }
+
+        Ok(result)
+    }
+}
+
+struct SqliteTransaction(SharedConnection);
+
+impl StoreTransaction for SqliteTransaction {
+    fn collection(&self, name: &str) -> Box<dyn DocumentCollection> {
+        Box::new(SqliteCollection { name: name.to_string(), connection: self.0.clone() })
+    }
+
+    fn commit(&self) -> crate::Result<()> {
+        Ok(lock(&self.0)?.execute_batch("COMMIT")?)
+    }
+
+    fn rollback(&self) -> crate::Result<()> {
+        Ok(lock(&self.0)?.execute_batch("ROLLBACK")?)
+    }
+}
+
+/// A collection emulated on top of a table. Filters, sorting & updates are evaluated in memory by [query].
+struct SqliteCollection {
+    name: String,
+    connection: SharedConnection,
+}
+
+fn encode(value: &Bson) -> crate::Result<String> {
+    serde_json::to_string(&value.clone().into_relaxed_extjson())
+        .or_else(|e| Err(crate::Error::SerializationError { reason: e.to_string() }))
+}
+
+fn decode(data: &str) -> crate::Result<Document> {
+    let json: serde_json::Value =
+        serde_json::from_str(data).or_else(|e| Err(crate::Error::DeserializationError { reason: e.to_string() }))?;
+    match Bson::try_from(json) {
+        Ok(Bson::Document(document)) => Ok(document),
+        Ok(_) => Err(crate::Error::DeserializationError { reason: "Stored value is not a document.".to_string() }),
+        Err(e) => Err(crate::Error::DeserializationError { reason: e.to_string() }),
+    }
+}
+
+impl SqliteCollection {
+    fn exists(&self, connection: &Connection) -> crate::Result<bool> {
+        Ok(connection
+            .query_row(&format!("SELECT 1 FROM {} WHERE name = ?1", quote(COLLECTIONS_TABLE)), params![self.name], |_| Ok(()))
+            .optional()?
+            .is_some())
+    }
+
+    fn ensure_table(&self, connection: &Connection) -> crate::Result<()> {
+        if !self.exists(connection)? {
+            connection.execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (_id TEXT PRIMARY KEY NOT NULL, document TEXT NOT NULL);",
+                table(&self.name)
+            ))?;
+            connection.execute(&format!("INSERT OR IGNORE INTO {} (name) VALUES (?1)", quote(COLLECTIONS_TABLE)), params![self.name])?;
+        }
+        Ok(())
+    }
+
+    /// Loads every document matching `filter`, keyed by its encoded `_id`. Plain `_id` equality filters are looked up directly.
+    fn load(&self, connection: &Connection, filter: &Document, limit: Option<usize>) -> crate::Result<Vec<(String, Document)>> {
+        if !self.exists(connection)? {
+            return Ok(vec![]);
+        }
+
+        let by_id = match filter.get("_id") {
+            Some(Bson::Document(condition)) if condition.keys().any(|k| k.starts_with('$')) => None,
+            Some(id) if filter.len() == 1 => Some(encode(id)?),
+            _ => None,
+        };
+        let mut statement = match by_id {
+            Some(_) => connection.prepare(&format!("SELECT _id, document FROM {} WHERE _id = ?1", table(&self.name)))?,
+            None => connection.prepare(&format!("SELECT _id, document FROM {} ORDER BY rowid", table(&self.name)))?,
+        };
+        let mut rows = match &by_id {
+            Some(id) => statement.query(params![id])?,
+            None => statement.query([])?,
+        };
+
+        let mut documents = Vec::new();
+        while let Some(row) = rows.next()? {
+            let document = decode(&row.get::<_, String>(1)?)?;
+            if query::matches(&document, filter)? {
+                documents.push((row.get::<_, String>(0)?, document));
+                if limit.is_some_and(|limit| documents.len() >= limit) {
+                    break;
+                }
+            }
+        }
+
+        Ok(documents)
+    }
+
+    fn insert(&self, connection: &Connection, mut document: Document) -> crate::Result<Bson> {
+        let id = match document.get("_id") {
+            Some(id) => id.clone(),
+            None => {
+                let id = Bson::ObjectId(ObjectId::new());
+                let mut with_id = Document::new();
+                with_id.insert("_id", id.clone());
+                with_id.extend(document);
+                document = with_id;
+                id
+            }
+        };
+
+        connection.execute(
+            &format!("INSERT INTO {} (_id, document) VALUES (?1, ?2)", table(&self.name)),
+            params![encode(&id)?, encode(&Bson::Document(document))?],
+        )?;
+        Ok(id)
+    }
+
+    fn update(&self, query: Document, update: Document, options: Option<UpdateOptions>, limit: Option<usize>) -> crate::Result<UpdateResult> {
+        let mut connection = lock(&self.connection)?;
+        let savepoint = connection.savepoint()?;
+        self.ensure_table(&savepoint)?;
+
+        let documents = self.load(&savepoint, &query, limit)?;
+        let mut result = UpdateResult { matched_count: documents.len() as u64, modified_count: 0 };
+        for (id, mut document) in documents {
+            if query::apply_update(&mut document, &update)? {
+                savepoint.execute(
+                    &format!("UPDATE {} SET document = ?1 WHERE _id = ?2", table(&self.name)),
+                    params![encode(&Bson::Document(document))?, id],
+                )?;
+                result.modified_count += 1;
+            }
+        }
+
+        if result.matched_count == 0 && options.and_then(|o| o.upsert).unwrap_or(false) {
+            self.insert(&savepoint, query::upsert_document(&query, &update)?)?;
+        }
+
+        savepoint.commit()?;
+        Ok(result)
+    }
+
+    fn delete(&self, query: Document, limit: Option<usize>) -> crate::Result<DeleteResult> {
+        let mut connection = lock(&self.connection)?;
+        let savepoint = connection.savepoint()?;
+        let documents = self.load(&savepoint, &query, limit)?;
+        for (id, _) in &documents {
+            savepoint.execute(&format!("DELETE FROM {} WHERE _id = ?1", table(&self.name)), params![id])?;
+        }
+
+        savepoint.commit()?;
+        Ok(DeleteResult { deleted_count: documents.len() as u64 })
+    }
+}
+
+impl DocumentCollection for SqliteCollection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count_documents(&self) -> crate::Result<u64> {
+        let connection = lock(&self.connection)?;
+        if !self.exists(&connection)? {
+            return Ok(0);
+        }
+
+        let count: i64 = connection.query_row(&format!("SELECT COUNT(*) FROM {}", table(&self.name)), [], |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    fn update_one(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        self.update(query, update, options, Some(1))
+    }
+
+    fn update_many(&self, query: Document, update: Document, options: Option<UpdateOptions>) -> crate::Result<UpdateResult> {
+        self.update(query, update, options, None)
+    }
+
+    fn delete_one(&self, query: Document) -> crate::Result<DeleteResult> {
+        self.delete(query, Some(1))
+    }
+
+    fn delete_many(&self, query: Document) -> crate::Result<DeleteResult> {
+        self.delete(query, None)
+    }
+
+    /// Indexes are expression indexes over the document JSON, so unique indexes are enforced by SQLite itself.
+    fn create_index(&self, index: IndexModel) -> crate::Result<()> {
+        let name = index_name(&index);
+        let options = index.options.unwrap_or_default();
+        let columns = index
+            .keys
+            .iter()
+            .map(|(key, direction)| {
+                let descending = matches!(direction, Bson::Int32(d) if *d < 0) || matches!(direction, Bson::Int64(d) if *d < 0);
+                format!("json_extract(document, '$.{}'){}", key.replace('\'', "''"), if descending { " DESC" } else { "" })
+            })
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            return Err(crate::Error::database_code(DatabaseErrorCode::InvalidQuery, "Indexes require at least one key."));
+        }
+
+        let connection = lock(&self.connection)?;
+        self.ensure_table(&connection)?;
+        connection.execute_batch(&format!(
+            "CREATE {}INDEX IF NOT EXISTS {} ON {} ({});",
+            if options.unique.unwrap_or(false) { "UNIQUE " } else { "" },
+            quote(format!("index:{}:{}", self.name, name)),
+            table(&self.name),
+            columns.join(", ")
+        ))?;
+        Ok(())
+    }
+
+    fn drop_index(&self, name: &str) -> crate::Result<()> {
+        let connection = lock(&self.connection)?;
+        connection.execute_batch(&format!("DROP INDEX {};", quote(format!("index:{}:{}", self.name, name))))?;
+        Ok(())
+    }
+
+    fn drop(&self) -> crate::Result<()> {
+        let mut connection = lock(&self.connection)?;
+        let savepoint = connection.savepoint()?;
+        savepoint.execute_batch(&format!("DROP TABLE IF EXISTS {};", table(&self.name)))?;
+        savepoint.execute(&format!("DELETE FROM {} WHERE name = ?1", quote(COLLECTIONS_TABLE)), params![self.name])?;
+        savepoint.commit()?;
+        Ok(())
+    }
+
+    fn insert_one(&self, document: Document) -> crate::Result<InsertOneResult> {
+        let connection = lock(&self.connection)?;
+        self.ensure_table(&connection)?;
+        Ok(InsertOneResult { inserted_id: self.insert(&connection, document)? })
+    }
+
+    fn insert_many(&self, documents: Vec<Document>) -> crate::Result<InsertManyResult> {
+        let mut connection = lock(&self.connection)?;
+        let savepoint = connection.savepoint()?;
+        self.ensure_table(&savepoint)?;
+        let mut inserted_ids = HashMap::new();
+        for (index, document) in documents.into_iter().enumerate() {
+            inserted_ids.insert(index, self.insert(&savepoint, document)?);
+        }
+
+        savepoint.commit()?;
+        Ok(InsertManyResult { inserted_ids })
+    }
+
+    fn find(&self, filter: Document, options: FindOptions) -> crate::Result<Vec<Document>> {
+        let connection = lock(&self.connection)?;
+        let limit = match options.sort {
+            Some(_) => None,
+            None => options.limit.map(|limit| (limit + options.skip.unwrap_or(0)) as usize),
+        };
+        let mut documents: Vec<Document> = self.load(&connection, &filter, limit)?.into_iter().map(|(_, document)| document).collect();
+        if let Some(sort) = options.sort {
+            documents = query::sort(documents, &sort);
+        }
+
+        Ok(documents
+            .into_iter()
+            .skip(options.skip.unwrap_or(0) as usize)
+            .take(options.limit.map(|limit| limit as usize).unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    fn find_one(&self, filter: Document) -> crate::Result<Option<Document>> {
+        let connection = lock(&self.connection)?;
+        Ok(self.load(&connection, &filter, Some(1))?.into_iter().next().map(|(_, document)| document))
+    }
+}
@@ -1,85 +1,186 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, io::{Read, Write}, path::{Path, PathBuf}, sync::Arc};
 
-use polodb_core::{Database, Transaction};
+use fs4::fs_std::FileExt;
+use notify_debouncer_full::{notify::RecommendedWatcher, Debouncer, RecommendedCache};
 use serde::{Deserialize, Serialize};
 use specta::Type;
+use tauri::async_runtime::JoinHandle;
 use tokio::{fs::{File, OpenOptions}, sync::Mutex};
 
+use super::{backend::{DocumentStore, StoreTransaction}, compression::{Compressor, Decompressor}, crypto::{Keyring, StreamReader, StreamWriter}, kv::KvDatabase, resolve::canonicalize_nearest, types::{ContextLockOwner, FileCompression, PathResolution, StorageBackendKind}};
+
+/// Open transactions on a database, by id
+pub type TransactionMap = Arc<Mutex<HashMap<bson::Uuid, Arc<Mutex<Box<dyn StoreTransaction>>>>>>;
+
 #[derive(Clone)]
 pub struct ContextDB {
+    pub name: String,
+    pub path: String,
+    pub backend: StorageBackendKind,
+    /// `None` while the database files are being replaced by [crate::Database::restore_from]
+    pub database: Arc<Mutex<Option<Box<dyn DocumentStore>>>>,
+    pub transactions: TransactionMap,
+    pub snapshots: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+#[derive(Clone)]
+pub struct ContextKv {
     #[allow(dead_code)]
     pub name: String,
     pub path: String,
-    pub database: Arc<Mutex<Database>>,
-    pub transactions: Arc<Mutex<HashMap<bson::Uuid, Arc<Mutex<Transaction>>>>>,
+    pub store: Arc<KvDatabase>,
+    pub transactions: Arc<Mutex<HashMap<bson::Uuid, redb::WriteTransaction>>>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, Type)]
 #[serde(rename_all = "snake_case", tag = "mode")]
+/// How a file handle is opened. `read` handles detect gzip & zstd files by their magic bytes & decompress them,
+/// unless `raw` is set.
 pub enum FileHandleMode {
     Create {
         new: bool,
-        overwrite: bool
+        overwrite: bool,
+        #[serde(default)]
+        sync: bool,
+        #[serde(default)]
+        encrypted: bool,
+        #[serde(default)]
+        compression: Option<FileCompression>
     },
     Write {
-        overwrite: bool
+        overwrite: bool,
+        #[serde(default)]
+        sync: bool,
+        #[serde(default)]
+        encrypted: bool,
+        #[serde(default)]
+        compression: Option<FileCompression>
     },
-    Read {}
+    Read {
+        #[serde(default)]
+        encrypted: bool,
+        /// Read gzip or zstd data as-is, rather than decompressing it. Uncompressed files are read as-is either way.
+        #[serde(default)]
+        raw: bool
+    }
 }
 
 impl FileHandleMode {
     pub fn create_new(overwrite: bool) -> Self {
-        Self::Create { new: true, overwrite }
+        Self::Create { new: true, overwrite, sync: false, encrypted: false, compression: None }
     }
 
     pub fn create_or_open(overwrite: bool) -> Self {
-        Self::Create { new: false, overwrite }
+        Self::Create { new: false, overwrite, sync: false, encrypted: false, compression: None }
     }
 
     pub fn append() -> Self {
-        Self::Write { overwrite: false }
+        Self::Write { overwrite: false, sync: false, encrypted: false, compression: None }
     }
 
     pub fn overwrite() -> Self {
-        Self::Write { overwrite: true }
+        Self::Write { overwrite: true, sync: false, encrypted: false, compression: None }
     }
 
-    pub fn read() -> Self {
-        Self::Read {  }
+    /// Makes every write through this handle sync file data & metadata to disk before returning.
+    pub fn synced(mut self) -> Self {
+        if let Self::Create { sync, .. } | Self::Write { sync, .. } = &mut self {
+            *sync = true;
+        }
+        self
     }
 
-    pub fn create(&self) -> bool {
-        if let Self::Create { .. } = self {
-            true
-        } else {
-            false
+    /// Encrypts everything written through this handle, or decrypts everything read, with a key from the plugin's keyring.
+    /// Encrypted files can't be appended to, so writeable modes must overwrite.
+    pub fn encrypted(mut self) -> Self {
+        match &mut self {
+            Self::Create { encrypted, .. } | Self::Write { encrypted, .. } | Self::Read { encrypted, .. } => *encrypted = true
         }
+        self
     }
 
-    pub fn writeable(&self) -> bool {
+    /// Compresses everything written through this handle, or decompresses everything read. Reads detect the format by magic bytes
+    /// (which they do by default), so `compression` only applies to writeable modes. Appending to a compressed file adds another
+    /// compressed stream.
+    pub fn compressed(mut self, compression: FileCompression) -> Self {
+        match &mut self {
+            Self::Create { compression: current, .. } | Self::Write { compression: current, .. } => *current = Some(compression),
+            Self::Read { raw, .. } => *raw = false
+        }
+        self
+    }
+
+    /// Makes a readable handle return compressed files as-is, instead of decompressing them.
+    pub fn raw(mut self) -> Self {
+        if let Self::Read { raw, .. } = &mut self {
+            *raw = true;
+        }
+        self
+    }
+
+    pub fn is_encrypted(&self) -> bool {
         match self {
-            Self::Create {..} | Self::Write {..} => true,
+            Self::Create { encrypted, .. } | Self::Write { encrypted, .. } | Self::Read { encrypted, .. } => *encrypted
+        }
+    }
+
+    /// Compression applied to writes
+    pub fn compression(&self) -> Option<FileCompression> {
+        match self {
+            Self::Create { compression, .. } | Self::Write { compression, .. } => *compression,
+            _ => None
+        }
+    }
+
+    pub fn decompresses(&self) -> bool {
+        match self {
+            Self::Read { raw, .. } => !*raw,
             _ => false
         }
     }
 
-    pub fn readable(&self) -> bool {
+    pub fn overwrites(&self) -> bool {
         match self {
-            Self::Read {} => true,
+            Self::Create { overwrite, .. } | Self::Write { overwrite, .. } => *overwrite,
             _ => false
         }
     }
+
+    pub fn sync_on_write(&self) -> bool {
+        match self {
+            Self::Create { sync, .. } | Self::Write { sync, .. } => *sync,
+            _ => false
+        }
+    }
+
+    pub fn read() -> Self {
+        Self::Read { encrypted: false, raw: false }
+    }
+
+    pub fn create(&self) -> bool {
+        matches!(self, Self::Create { .. })
+    }
+
+    pub fn writeable(&self) -> bool {
+        matches!(self, Self::Create { .. } | Self::Write { .. })
+    }
+
+    pub fn readable(&self) -> bool {
+        matches!(self, Self::Read { .. })
+    }
 }
 
 impl Into<OpenOptions> for FileHandleMode {
     fn into(self) -> OpenOptions {
         let mut base = OpenOptions::new();
         match self {
-            Self::Create { new, overwrite: true } => base.create(true).write(true).create_new(new),
-            Self::Create {new, overwrite: false} => base.create(true).append(true).create_new(new),
-            Self::Write { overwrite: true } => base.write(true),
-            Self::Write { overwrite: false } => base.append(true),
-            Self::Read {  } => base.read(true)
+            // Leftover bytes past the end of a shorter stream would fail authentication or be decoded as another compressed stream,
+            // so encrypted & compressed files are truncated
+            Self::Create { new, overwrite: true, encrypted, compression, .. } => base.create(true).write(true).truncate(encrypted || compression.is_some()).create_new(new),
+            Self::Create {new, overwrite: false, ..} => base.create(true).append(true).create_new(new),
+            Self::Write { overwrite: true, encrypted, compression, .. } => base.write(true).truncate(encrypted || compression.is_some()),
+            Self::Write { overwrite: false, .. } => base.append(true),
+            Self::Read { .. } => base.read(true)
         }.clone()
     }
 }
@@ -89,7 +190,99 @@ pub struct ContextFileHandle {
     pub id: bson::Uuid,
     pub path: String,
     pub handle: async_dup::Arc<async_dup::Mutex<File>>,
-    pub mode: FileHandleMode
+    pub mode: FileHandleMode,
+    pub opened: chrono::DateTime<chrono::Utc>,
+    pub temporary: bool,
+    /// Set for handles opened in an encrypted mode
+    pub cipher: Option<Arc<Mutex<FileCipher>>>,
+    /// Set for handles that compress or decompress
+    pub codec: Option<Arc<Mutex<FileCodec>>>
+}
+
+/// Compression state of a file handle. Data is compressed before it's encrypted.
+pub enum FileCodec {
+    /// `None` once the compressed stream has been finished
+    Compressor(Option<Compressor>),
+    Decompressor(Decompressor)
+}
+
+/// Encryption state of an encrypted file handle, depending on whether it reads or writes
+pub enum FileCipher {
+    Writer(StreamWriter),
+    Reader(StreamReader)
+}
+
+/// The keyring registered on [crate::PluginBuilder], if any
+pub struct KeyringState(pub Option<Keyring>);
+
+/// Whether JS may open contexts that follow symlinks, set with [crate::PluginBuilder::follow_symlinks]
+pub struct SymlinkFollowing(pub bool);
+
+/// Directories registered with [crate::PluginBuilder::archive_directory]
+pub struct ArchiveDirectories(pub Vec<PathBuf>);
+
+impl ArchiveDirectories {
+    /// Checks that an archive path given from JS is within one of the directories, after canonicalizing it
+    pub fn ensure(&self, path: impl AsRef<str>) -> crate::Result<PathBuf> {
+        if self.0.is_empty() {
+            return Err(crate::Error::archive("Archives can't be accessed from JS unless an archive directory is configured on the PluginBuilder."));
+        }
+
+        let canonical = canonicalize_nearest(Path::new(path.as_ref())).or_else(|e| Err(crate::Error::archive(e.to_string())))?;
+        if self.0.iter().filter_map(|directory| directory.canonicalize().ok()).any(|directory| canonical.starts_with(directory)) {
+            Ok(canonical)
+        } else {
+            Err(crate::Error::archive(format!("{} is outside of the allowed archive directories.", path.as_ref())))
+        }
+    }
+}
+
+/// An exclusive, advisory lock on a context's root directory.
+/// The lock is held by the OS for as long as the lockfile is open, so a lockfile left behind by a crashed process is detected as stale and taken over.
+pub struct ContextLock {
+    pub path: PathBuf,
+    file: std::fs::File,
+}
+
+impl ContextLock {
+    pub const FILE_NAME: &'static str = ".persistence.lock";
+
+    pub fn acquire(root: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = root.as_ref().join(Self::FILE_NAME);
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        if !FileExt::try_lock_exclusive(&file)? {
+            let mut contents = String::new();
+            let owner = file.read_to_string(&mut contents).ok().and_then(|_| serde_json::from_str::<ContextLockOwner>(&contents).ok());
+            return Err(crate::Error::context_locked(root.as_ref().to_string_lossy(), owner));
+        }
+
+        // Any owner info still in the file is stale, since its owner no longer holds the OS lock.
+        let owner = serde_json::to_vec_pretty(&ContextLockOwner::current()).or_else(|e| Err(crate::Error::serialization(e.to_string())))?;
+        file.set_len(0)?;
+        file.write_all(&owner)?;
+        file.sync_all()?;
+        Ok(Self { path, file })
+    }
+
+    pub fn owner(&self) -> Option<ContextLockOwner> {
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str::<ContextLockOwner>(&contents).ok()
+    }
+}
+
+impl Drop for ContextLock {
+    fn drop(&mut self) {
+        // The lockfile is truncated rather than removed, so another process can't end up locking an unlinked file.
+        let _ = self.file.set_len(0);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+pub struct ContextWatcher {
+    pub path: String,
+    pub recursive: bool,
+    #[allow(dead_code)]
+    pub(crate) debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
 }
 
 #[derive(Clone)]
@@ -98,7 +291,12 @@ pub struct ContextState {
     pub name: String,
     pub root_path: String,
     pub databases: Arc<Mutex<HashMap<String, ContextDB>>>,
+    pub kv_stores: Arc<Mutex<HashMap<String, ContextKv>>>,
     pub files: Arc<Mutex<HashMap<bson::Uuid, ContextFileHandle>>>,
+    pub temporary_directories: Arc<Mutex<Vec<String>>>,
+    pub lock: Option<Arc<ContextLock>>,
+    pub watchers: Arc<Mutex<HashMap<bson::Uuid, ContextWatcher>>>,
+    pub resolution: PathResolution,
 }
 
 pub type PluginState = Mutex<HashMap<String, ContextState>>;
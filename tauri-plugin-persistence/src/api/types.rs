@@ -1,19 +1,26 @@
-use std::fs::{FileType, Metadata};
+use std::{fmt::Display, fs::{FileType, Metadata}, path::Path};
 
 use chrono::Utc;
 use mime_guess::MimeGuess;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use specta::Type;
 use tokio::fs::DirEntry;
 
-use super::state::FileHandleMode;
+use super::{resolve::relative_path, state::FileHandleMode};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 #[serde(untagged)]
 /// A model used to specify an existing or closed context
 pub enum ContextSpecifier {
-    /// Open a new context
-    Direct { alias: String, path: String },
+    /// Open a new context, optionally taking an exclusive lock on its root directory
+    Direct {
+        alias: String,
+        path: String,
+        #[serde(default)]
+        lock: bool,
+        #[serde(default)]
+        resolution: PathResolution
+    },
 
     /// Return an existing context
     Aliased { alias: String },
@@ -27,14 +34,80 @@ pub struct ContextInfo {
 
     /// Context path
     pub path: String,
+
+    /// Lock owner, if the context was opened with a lock
+    pub lock: Option<ContextLockOwner>,
+
+    /// How symlinks are treated when resolving paths in this context
+    pub resolution: PathResolution,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, Type)]
+#[serde(rename_all = "snake_case")]
+/// How symlinks are treated when resolving paths inside a context
+pub enum PathResolution {
+    /// Any symlink in a path is rejected with [crate::Error::SymlinkDenied]
+    Deny,
+
+    /// Symlinks are resolved, but the final path must remain inside the context root
+    #[default]
+    WithinRoot,
+
+    /// Symlinks are resolved and may point anywhere. `..` components still can't climb out of the root.
+    /// Contexts opened from JS can only use this if [crate::PluginBuilder::follow_symlinks] was set.
+    Follow
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Type)]
+/// Options used when opening a context
+pub struct ContextOptions {
+    /// Whether to take an exclusive lock on the context root
+    #[serde(default)]
+    pub lock: bool,
+
+    /// How symlinks are treated when resolving paths
+    #[serde(default)]
+    pub resolution: PathResolution
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// Owner information written to the lockfile of a locked context
+pub struct ContextLockOwner {
+    /// Process ID of the owner
+    pub pid: u32,
+
+    /// Hostname of the machine the owner is running on
+    pub hostname: String,
+
+    /// When the lock was acquired
+    pub acquired: chrono::DateTime<Utc>,
+}
+
+impl ContextLockOwner {
+    /// Describes the current process
+    pub fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            hostname: gethostname::gethostname().to_string_lossy().to_string(),
+            acquired: Utc::now()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 #[serde(untagged)]
 /// A model used to specify an existing or closed database
 pub enum DatabaseSpecifier {
-    /// Open a new database
-    Direct { alias: String, path: String },
+    /// Open a new database, optionally with a storage backend other than PoloDB
+    Direct {
+        alias: String,
+        path: String,
+        #[serde(default)]
+        backend: StorageBackendKind
+    },
+
+    /// Open a new in-memory database. `memory` must be `true` (`false` is rejected), it only distinguishes this from [DatabaseSpecifier::Aliased].
+    Memory { alias: String, memory: bool },
 
     /// Return an existing database
     Aliased { alias: String },
@@ -48,6 +121,100 @@ pub struct DatabaseInfo {
 
     /// Database path
     pub path: String,
+
+    /// Storage backend the database was opened with
+    pub backend: StorageBackendKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, Type)]
+#[serde(rename_all = "snake_case")]
+/// Storage engine backing a database
+pub enum StorageBackendKind {
+    ///
+    #[default]
+    Polodb,
+
+    /// SQLite, with collections emulated as tables of JSON documents. Requires the `sqlite` feature.
+    Sqlite,
+
+    /// Kept in memory & discarded on close. Opened with [DatabaseSpecifier::Memory] rather than a path.
+    Memory,
+
+    /// Kept in memory & written to a single encrypted file after every change, using a key from the plugin's keyring
+    Encrypted
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+#[serde(untagged)]
+/// A model used to specify an existing or closed key-value store
+pub enum KvSpecifier {
+    /// Open a new key-value store
+    Direct {
+        alias: String,
+        path: String,
+        #[serde(default)]
+        encoding: KvEncoding
+    },
+
+    /// Return an existing key-value store
+    Aliased { alias: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, Type)]
+#[serde(rename_all = "snake_case")]
+/// How values in a key-value store are serialized. Fixed when the store is created.
+pub enum KvEncoding {
+    ///
+    #[default]
+    Json,
+    ///
+    Bson
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// A model containing serializable information about a [crate::KvStore]
+pub struct KvStoreInfo {
+    /// Store name
+    pub name: String,
+
+    /// Store path
+    pub path: String,
+
+    /// Value encoding of the store
+    pub encoding: KvEncoding,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// A single key & its value, as returned by prefix listings
+pub struct KvEntry<T> {
+    pub key: String,
+    pub value: T,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Type)]
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+/// A typed SQL value, used for both statement parameters & result rows
+pub enum SqlValue {
+    ///
+    Null,
+    ///
+    Integer(#[specta(type = i32)] i64),
+    ///
+    Real(f64),
+    ///
+    Text(String),
+    ///
+    Blob(Vec<u8>)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// Result of a SQL query
+pub struct SqlRows {
+    /// Column names, in order
+    pub columns: Vec<String>,
+
+    /// Rows, each holding one value per column
+    pub rows: Vec<Vec<SqlValue>>
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
@@ -61,6 +228,38 @@ pub enum FileHandleSpecifier {
     Direct { path: String, mode: FileHandleMode },
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+/// Kind of change reported by a context watcher
+pub enum WatchEventKind {
+    ///
+    Create,
+    ///
+    Modify,
+    ///
+    Remove,
+    /// Paths are `[from, to]` when both ends of the rename were observed
+    Rename,
+    /// Any other change (permissions, access, or an unclassified event)
+    Other
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type, tauri_specta::Event)]
+/// Emitted (debounced) when paths inside a watched context directory change
+pub struct ContextWatchEvent {
+    /// Name of the context the watcher belongs to
+    pub context: String,
+
+    /// ID of the watcher that observed the change
+    pub watcher: bson::Uuid,
+
+    /// Kind of change
+    pub kind: WatchEventKind,
+
+    /// Affected paths, relative to the context root
+    pub paths: Vec<String>
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 /// A model containing serializable information about a [crate::FileHandle]
 pub struct FileHandleInfo {
@@ -72,6 +271,23 @@ pub struct FileHandleInfo {
 
     /// Open mode
     pub mode: FileHandleMode,
+
+    /// Current cursor position, if it could be determined
+    #[specta(type = Option<u32>)]
+    pub position: Option<u64>,
+
+    /// Current file size, if it could be determined
+    #[specta(type = Option<u32>)]
+    pub size: Option<u64>,
+
+    /// When the handle was opened
+    pub opened: chrono::DateTime<Utc>,
+
+    /// Whether the file will be deleted when the handle is closed
+    pub temporary: bool,
+
+    /// Compression applied by the handle. For handles that decompress, this is only known once the first bytes are read.
+    pub compression: Option<FileCompression>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
@@ -118,6 +334,46 @@ pub enum OperationCount {
     Many,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// The changes applied by an update, & how many documents they apply to
+pub struct DocumentUpdate {
+    /// Update operators (ie `{"$set": {...}}`)
+    pub update: JsonDocument,
+    pub operations: OperationCount,
+
+    /// Insert a document if none match the query
+    #[serde(default)]
+    pub upsert: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Type)]
+/// Paging & ordering of a document search
+pub struct FindDocumentsOptions {
+    #[serde(default)]
+    pub skip: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub sort: Option<JsonDocument>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Type)]
+/// Options for a new index
+pub struct CreateIndexOptions {
+    /// Defaults to a name derived from the index's keys
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub unique: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// A compare-and-swap on a key-value entry. `None` means the key is absent, or is to be deleted.
+pub struct KvSwap {
+    pub expected: Option<serde_json::Value>,
+    pub value: Option<serde_json::Value>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 /// Serializable version of [polodb_core::results::UpdateResult]
 pub struct UpdateResult {
@@ -197,7 +453,9 @@ pub enum PathFileType {
     ///
     File,
     ///
-    Symlink
+    Symlink,
+    /// Sockets, FIFOs, and device files
+    Other
 }
 
 impl From<FileType> for PathFileType {
@@ -206,8 +464,10 @@ impl From<FileType> for PathFileType {
             Self::Directory
         } else if value.is_file() {
             Self::File
-        } else {
+        } else if value.is_symlink() {
             Self::Symlink
+        } else {
+            Self::Other
         }
     }
 }
@@ -220,34 +480,328 @@ pub struct PathMetadata {
     pub last_modified: Option<chrono::DateTime<Utc>>,
     pub last_accessed: Option<chrono::DateTime<Utc>>,
     pub created: Option<chrono::DateTime<Utc>>,
+
+    /// Whether the path is marked read-only
+    pub readonly: bool,
+
+    /// Unix permission bits (ie `0o644`), if available on this platform
+    pub mode: Option<u32>,
+
+    /// Target of the path, if the path itself is a symlink
+    pub symlink_target: Option<String>,
+
+    /// Whether the path is hidden (dotfiles on Unix, the hidden attribute on Windows)
+    pub hidden: bool,
+
+    /// Inode number, on Unix
+    #[specta(type = Option<u32>)]
+    pub inode: Option<u64>,
+
+    /// ID of the containing device, on Unix
+    #[specta(type = Option<u32>)]
+    pub device: Option<u64>,
 }
 
 impl From<Metadata> for PathMetadata {
     fn from(value: Metadata) -> Self {
+        #[cfg(unix)]
+        let (mode, inode, device) = {
+            use std::os::unix::fs::MetadataExt;
+            (Some(value.mode() & 0o7777), Some(value.ino()), Some(value.dev()))
+        };
+        #[cfg(not(unix))]
+        let (mode, inode, device) = (None, None, None);
+
+        #[cfg(windows)]
+        let hidden = {
+            use std::os::windows::fs::MetadataExt;
+            value.file_attributes() & 0x2 != 0
+        };
+        #[cfg(not(windows))]
+        let hidden = false;
+
         Self {
             file_type: PathFileType::from(value.file_type()),
             size: value.len(),
             last_modified: value.modified().and_then(|systime| Ok(Some(chrono::DateTime::<Utc>::from(systime)))).unwrap_or(None),
             last_accessed: value.accessed().and_then(|systime| Ok(Some(chrono::DateTime::<Utc>::from(systime)))).unwrap_or(None),
-            created: value.created().and_then(|systime| Ok(Some(chrono::DateTime::<Utc>::from(systime)))).unwrap_or(None)
+            created: value.created().and_then(|systime| Ok(Some(chrono::DateTime::<Utc>::from(systime)))).unwrap_or(None),
+            readonly: value.permissions().readonly(),
+            mode,
+            symlink_target: None,
+            hidden,
+            inode,
+            device
         }
     }
 }
 
+impl PathMetadata {
+    /// Builds metadata for a specific path, additionally filling in the symlink target & dotfile hidden flag.
+    pub fn for_path(path: impl AsRef<Path>, metadata: Metadata) -> Self {
+        let mut result = Self::from(metadata);
+        result.symlink_target = std::fs::read_link(path.as_ref()).ok().map(|target| target.to_string_lossy().to_string());
+        result.hidden = result.hidden || path.as_ref().file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        result
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Type)]
 /// General info about a path
 pub struct PathInformation {
     pub file_name: String,
     pub absolute_path: String,
+
+    /// Path relative to the context root
+    pub relative_path: String,
     pub media_type: String,
 }
 
-impl From<DirEntry> for PathInformation {
-    fn from(value: DirEntry) -> Self {
+impl PathInformation {
+    /// Builds path info for a directory entry, relative to a (canonicalized) context root
+    pub fn from_entry(value: &DirEntry, base: impl AsRef<Path>) -> Self {
+        let path = value.path();
         Self {
             file_name: String::from_utf8_lossy(value.file_name().as_encoded_bytes()).to_string(),
-            absolute_path: String::from_utf8_lossy(value.path().into_os_string().as_encoded_bytes()).to_string(),
-            media_type: MimeGuess::from_path(value.path()).first_or_octet_stream().to_string()
+            absolute_path: String::from_utf8_lossy(path.as_os_str().as_encoded_bytes()).to_string(),
+            relative_path: relative_path(&path, base.as_ref()).unwrap_or(path.to_string_lossy().to_string()),
+            media_type: MimeGuess::from_path(&path).first_or_octet_stream().to_string()
+        }
+    }
+}
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, Type)]
+#[serde(rename_all = "snake_case")]
+/// How symbolic links are treated while walking a directory
+pub enum SymlinkPolicy {
+    /// Symlinks are left out of the results entirely
+    #[default]
+    Skip,
+
+    /// Symlinks are reported with their own metadata, but never descended into
+    Include,
+
+    /// Symlinks are resolved and descended into, as long as their targets stay inside the context
+    Follow
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Type)]
+/// Options controlling a recursive directory walk
+pub struct WalkOptions {
+    /// Maximum depth to descend to, where direct children of the walked directory have a depth of 1
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+
+    /// Glob patterns (relative to the walked directory) that entries must match to be reported. If empty, all entries are reported.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Glob patterns (relative to the walked directory) of entries to skip. Excluded directories are not descended into.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// How to treat symbolic links
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// A single entry found while walking a directory
+pub struct WalkEntry {
+    /// Path of the entry, relative to the walked directory
+    pub path: String,
+
+    /// Depth of the entry, where direct children of the walked directory have a depth of 1
+    pub depth: u32,
+
+    /// General info about the entry
+    pub info: PathInformation,
+
+    /// Metadata of the entry
+    pub metadata: PathMetadata
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+/// Serialization format of a structured document file
+pub enum DocumentFormat {
+    ///
+    Json,
+    ///
+    Toml,
+    ///
+    Yaml,
+    ///
+    Bson
+}
+
+impl DocumentFormat {
+    /// Infers the document format from a path's extension, if possible.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match MimeGuess::from_path(path.as_ref()).first_raw() {
+            Some("application/json") => Some(Self::Json),
+            Some("text/x-toml") => Some(Self::Toml),
+            Some("text/x-yaml") => Some(Self::Yaml),
+            _ => match path.as_ref().extension().and_then(|e| e.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("bson") => Some(Self::Bson),
+                _ => None
+            }
+        }
+    }
+
+    /// Parses a document from raw bytes. `path` is only used for error reporting.
+    pub fn parse<T: DeserializeOwned>(&self, data: &[u8], path: impl AsRef<str>) -> crate::Result<T> {
+        match self {
+            Self::Json => serde_json::from_slice::<T>(data).or_else(|e| Err(crate::Error::parse(path, *self, Some(e.line()), Some(e.column()), e.to_string()))),
+            Self::Toml => {
+                let text = std::str::from_utf8(data).or(Err(crate::Error::string_encoding(data.len())))?;
+                toml::from_str::<T>(text).or_else(|e| {
+                    let (line, column) = match e.span() {
+                        Some(span) => {
+                            let (line, column) = line_column(text, span.start);
+                            (Some(line), Some(column))
+                        },
+                        None => (None, None)
+                    };
+                    Err(crate::Error::parse(path, *self, line, column, e.message()))
+                })
+            },
+            Self::Yaml => serde_yaml::from_slice::<T>(data).or_else(|e| {
+                let location = e.location();
+                Err(crate::Error::parse(path, *self, location.as_ref().and_then(|l| Some(l.line())), location.as_ref().and_then(|l| Some(l.column())), e.to_string()))
+            }),
+            Self::Bson => bson::from_slice::<T>(data).or_else(|e| Err(crate::Error::parse(path, *self, None, None, e.to_string())))
+        }
+    }
+
+    /// Serializes a document to raw bytes
+    pub fn serialize<T: Serialize>(&self, document: &T) -> crate::Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec_pretty(document).or_else(|e| Err(crate::Error::serialization(e.to_string()))),
+            Self::Toml => toml::to_string_pretty(document).and_then(|s| Ok(s.into_bytes())).or_else(|e| Err(crate::Error::serialization(e.to_string()))),
+            Self::Yaml => serde_yaml::to_string(document).and_then(|s| Ok(s.into_bytes())).or_else(|e| Err(crate::Error::serialization(e.to_string()))),
+            Self::Bson => bson::to_vec(document).or_else(|e| Err(crate::Error::from(e)))
+        }
+    }
+}
+
+impl Display for DocumentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+            Self::Bson => "bson"
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+/// Archive format used to export & import whole contexts
+pub enum ArchiveFormat {
+    ///
+    Zip,
+    /// Gzip-compressed tarball
+    TarGz
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+/// Compression applied to data written through a file handle
+pub enum FileCompression {
+    ///
+    Gzip,
+    ///
+    Zstd
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Type)]
+#[serde(rename_all = "snake_case")]
+/// Algorithm used to hash file contents
+pub enum HashAlgorithm {
+    ///
+    Sha256,
+    ///
+    Blake3
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3"
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// Content hash of a single file
+pub struct FileHash {
+    /// Path of the file, relative to the context root
+    pub path: String,
+
+    /// Algorithm used to compute the digest
+    pub algorithm: HashAlgorithm,
+
+    /// Lowercase hex digest
+    pub digest: String,
+
+    /// Size of the file when it was hashed
+    #[specta(type = u32)]
+    pub size: u64,
+
+    /// Whether the digest came from a hash index rather than being recomputed
+    pub cached: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// A point-in-time copy of a database, created by [crate::Database::snapshot]
+pub struct SnapshotInfo {
+    /// Path of the snapshot, relative to the context root
+    pub path: String,
+
+    /// When the snapshot was taken
+    pub created: chrono::DateTime<Utc>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Type)]
+/// Configuration for periodic database snapshots
+pub struct SnapshotSchedule {
+    /// Directory (relative to the context root) that snapshots are written to
+    pub directory: String,
+
+    /// Seconds between snapshots
+    #[specta(type = u32)]
+    pub interval: u64,
+
+    /// Number of snapshots to keep. Older snapshots are removed after each new one is taken.
+    pub keep: u32
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Type)]
+#[serde(rename_all = "snake_case")]
+/// File format of a logical database dump
+pub enum DumpFormat {
+    /// One relaxed Extended JSON document per line (`.ndjson`)
+    Json,
+    /// Concatenated raw BSON documents (`.bson`)
+    Bson
+}
+
+impl DumpFormat {
+    /// File extension used for collection files in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "ndjson",
+            Self::Bson => "bson"
         }
     }
-}
\ No newline at end of file
+}
+
+/// Converts a byte offset into a 1-based (line, column) pair
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').and_then(|i| Some(offset - i)).unwrap_or(offset + 1);
+    (line, column)
+}
@@ -0,0 +1,86 @@
+/// Invokes `$callback!` with the name of every command the plugin registers.
+/// `build.rs` includes this file directly to generate permissions, so the handler & the permission manifests can't drift apart.
+macro_rules! persistence_commands {
+    ($callback:ident) => {
+        $callback! {
+            context,
+            database,
+            file_handle,
+            database_get_collections,
+            database_close,
+            database_start_transaction,
+            database_commit_transaction,
+            database_rollback_transaction,
+            database_backup,
+            database_restore,
+            database_persist,
+            database_snapshot,
+            database_list_snapshots,
+            database_schedule_snapshots,
+            database_dump,
+            database_restore_dump,
+            database_execute_sql,
+            database_query_sql,
+            kv_store,
+            kv_close,
+            kv_get,
+            kv_set,
+            kv_delete,
+            kv_list,
+            kv_compare_and_swap,
+            kv_start_transaction,
+            kv_commit_transaction,
+            kv_rollback_transaction,
+            collection_count_documents,
+            collection_update_documents,
+            collection_delete_documents,
+            collection_create_index,
+            collection_drop_index,
+            collection_drop,
+            collection_insert_documents,
+            collection_find_many_documents,
+            collection_find_one_document,
+            file_close,
+            file_write_text,
+            file_write_bytes,
+            file_read_text,
+            file_read_bytes,
+            file_flush,
+            file_sync,
+            file_lock,
+            file_unlock,
+            temp_file,
+            list_file_handles,
+            close_all_file_handles,
+            get_context_base_path,
+            get_absolute_path_to,
+            create_directory,
+            remove_directory,
+            remove_file,
+            file_metadata,
+            list_directory,
+            close_context,
+            cleanup,
+            read_document,
+            write_document,
+            temp_dir,
+            copy_path,
+            rename_path,
+            walk,
+            walk_stream,
+            watch,
+            unwatch,
+            exists,
+            is_file,
+            is_dir,
+            stat_many,
+            set_permissions,
+            set_readonly,
+            hash_file,
+            hash_directory,
+            find_duplicates,
+            export_context,
+            import_context
+        }
+    };
+}
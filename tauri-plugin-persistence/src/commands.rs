@@ -1,173 +1,538 @@
 use std::collections::HashMap;
 
 use polodb_core::{options::UpdateOptions, IndexModel, IndexOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 use crate::{
     api::types::{
-        CollectionSpecifier, ContextInfo, ContextSpecifier, DatabaseInfo, DatabaseSpecifier,
-        FileHandleInfo, FileHandleSpecifier, OperationCount, UpdateResult,
-    }, types::{PathInformation, PathMetadata}, PersistenceExt
+        CollectionSpecifier, ContextInfo, ContextSpecifier, CreateIndexOptions, DatabaseInfo, DatabaseSpecifier, DocumentUpdate,
+        FileHandleInfo, FileHandleSpecifier, FindDocumentsOptions, KvSwap, OperationCount, UpdateResult,
+    }, state::ArchiveDirectories, types::{ArchiveFormat, DocumentFormat, DumpFormat, FileHash, HashAlgorithm, PathInformation, PathMetadata, KvEntry, KvSpecifier, KvStoreInfo, SnapshotInfo, SnapshotSchedule, SqlRows, SqlValue, StorageBackendKind, WalkEntry, WalkOptions}, ContextScope, PersistenceExt, ScopeTarget
 };
+use tauri::{ipc::Channel, Manager, Runtime};
+
+// Commands in the read-only permission sets use the `existing_*` lookups, so they never open (and create) contexts or stores
 
 // Info commands
 #[tauri::command]
 #[specta::specta]
-pub async fn context(
-    app: tauri::AppHandle,
+pub async fn context<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
 ) -> crate::Result<ContextInfo> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    Ok(ContextInfo {
+        name: context.name(),
+        path: context.path(),
+        lock: context.lock_owner().await?,
+        resolution: context.resolution(),
+    })
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn export_context<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    destination: String,
+    format: ArchiveFormat
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let destination = app.state::<ArchiveDirectories>().ensure(destination)?;
     let context = app.persistence().context(context).await?;
+    context.export(destination, format).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn import_context<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    archive: String,
+    path: String,
+    alias: String
+) -> crate::Result<ContextInfo> {
+    scope.ensure(ScopeTarget { alias: alias.clone(), path: path.clone() })?;
+    let archive = app.state::<ArchiveDirectories>().ensure(archive)?;
+    let context = app.persistence().import_context(archive, path, alias).await?;
     Ok(ContextInfo {
         name: context.name(),
         path: context.path(),
+        lock: context.lock_owner().await?,
+        resolution: context.resolution(),
     })
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn database(
-    app: tauri::AppHandle,
+pub async fn database<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
 ) -> crate::Result<DatabaseInfo> {
+    scope.check(&app, &context).await?;
     let database = app.persistence().database(context, database).await?;
     Ok(DatabaseInfo {
         name: database.name(),
         path: database.path(),
+        backend: database.backend().await?,
     })
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_handle(
-    app: tauri::AppHandle,
+pub async fn file_handle<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier,
 ) -> crate::Result<FileHandleInfo> {
+    scope.check(&app, &context).await?;
     let file_handle = app.persistence().file_handle(context, file_handle).await?;
-    Ok(FileHandleInfo {
-        id: file_handle.id(),
-        path: file_handle.path(),
-        mode: file_handle.mode().await,
-    })
+    file_handle.info().await
 }
 
 // Context commands
 #[tauri::command]
 #[specta::specta]
-pub async fn close_context(
-    app: tauri::AppHandle,
+pub async fn close_context<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     app.persistence().close_context(context).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn cleanup(
-    app: tauri::AppHandle,
+pub async fn cleanup<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
 ) -> crate::Result<()> {
-    app.persistence().cleanup().await
+    // Only closes the contexts within the caller's scope
+    for alias in app.persistence().context_ids().await {
+        let context = ContextSpecifier::Aliased { alias };
+        if scope.check(&app, &context).await.is_ok() {
+            app.persistence().close_context(context).await?;
+        }
+    }
+
+    Ok(())
 }
 
 // Database commands
 #[tauri::command]
 #[specta::specta]
-pub async fn database_get_collections(
-    app: tauri::AppHandle,
+pub async fn database_get_collections<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
 ) -> crate::Result<Vec<String>> {
-    let database = app.persistence().database(context, database).await?;
+    scope.check(&app, &context).await?;
+    let database = app.persistence().existing_database(context, database).await?;
     database.collections().await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn database_close(
-    app: tauri::AppHandle,
+pub async fn database_close<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
 ) -> crate::Result<()> {
-    let database = app.persistence().database(context, database).await?;
+    scope.check(&app, &context).await?;
+    let database = app.persistence().existing_database(context, database).await?;
     database.close().await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn database_start_transaction(
-    app: tauri::AppHandle,
+pub async fn database_start_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
 ) -> crate::Result<bson::Uuid> {
+    scope.check(&app, &context).await?;
     let database = app.persistence().database(context, database).await?;
     Ok(database.start_transaction().await?.id())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn database_commit_transaction(
-    app: tauri::AppHandle,
+pub async fn database_commit_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     transaction: bson::Uuid,
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let database = app.persistence().database(context, database).await?;
     database.commit_transaction(transaction).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn database_rollback_transaction(
-    app: tauri::AppHandle,
+pub async fn database_rollback_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     transaction: bson::Uuid,
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let database = app.persistence().database(context, database).await?;
     database.rollback_transaction(transaction).await
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn database_backup<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    path: String,
+    overwrite: bool,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.backup_to(path, overwrite).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_restore<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    path: String,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.restore_from(path).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_persist<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    path: String,
+    backend: StorageBackendKind,
+) -> crate::Result<DatabaseInfo> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    let persisted = database.persist(path, backend).await?;
+    Ok(DatabaseInfo {
+        name: persisted.name(),
+        path: persisted.path(),
+        backend,
+    })
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_snapshot<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    directory: String,
+    keep: Option<u32>,
+) -> crate::Result<SnapshotInfo> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.snapshot(directory, keep).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_list_snapshots<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    directory: String,
+) -> crate::Result<Vec<SnapshotInfo>> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().existing_database(context, database).await?;
+    database.snapshots(directory).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_schedule_snapshots<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    schedule: Option<SnapshotSchedule>,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.schedule_snapshots(schedule).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_dump<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    directory: String,
+    format: DumpFormat,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.dump(directory, format).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_restore_dump<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    directory: String,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.restore(directory).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_execute_sql<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    sql: String,
+    params: Vec<SqlValue>,
+) -> crate::Result<u64> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    Ok(database.execute_sql(sql, params).await? as u64)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn database_query_sql<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    database: DatabaseSpecifier,
+    sql: String,
+    params: Vec<SqlValue>,
+) -> crate::Result<SqlRows> {
+    scope.check(&app, &context).await?;
+    let database = app.persistence().database(context, database).await?;
+    database.query_sql(sql, params).await
+}
+
+// Key-value store commands
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_store<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+) -> crate::Result<KvStoreInfo> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    Ok(KvStoreInfo {
+        name: store.name(),
+        path: store.path(),
+        encoding: store.encoding().await?,
+    })
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_close<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().existing_kv_store(context, store).await?;
+    store.close().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_get<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    key: String,
+    transaction: Option<bson::Uuid>,
+) -> crate::Result<Option<serde_json::Value>> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().existing_kv_store(context, store).await?;
+    store.get_in(transaction, key).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_set<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    key: String,
+    value: serde_json::Value,
+    transaction: Option<bson::Uuid>,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    store.set_in(transaction, key, &value).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_delete<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    key: String,
+    transaction: Option<bson::Uuid>,
+) -> crate::Result<bool> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    store.delete_in(transaction, key).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_list<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    prefix: String,
+    transaction: Option<bson::Uuid>,
+) -> crate::Result<Vec<KvEntry<serde_json::Value>>> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().existing_kv_store(context, store).await?;
+    store.list_in(transaction, prefix).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_compare_and_swap<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    key: String,
+    swap: KvSwap,
+    transaction: Option<bson::Uuid>,
+) -> crate::Result<bool> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    store.compare_and_swap_in(transaction, key, swap.expected.as_ref(), swap.value.as_ref()).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_start_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+) -> crate::Result<bson::Uuid> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    Ok(store.start_transaction().await?.id())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_commit_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    transaction: bson::Uuid,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    store.commit_transaction(transaction).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn kv_rollback_transaction<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    store: KvSpecifier,
+    transaction: bson::Uuid,
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let store = app.persistence().kv_store(context, store).await?;
+    store.rollback_transaction(transaction).await
+}
+
 // Collection commands
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_count_documents(
-    app: tauri::AppHandle,
+pub async fn collection_count_documents<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
 ) -> crate::Result<u64> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
-        .collection::<bson::Document>(context, database, collection)
+        .existing_collection::<bson::Document>(context, database, collection)
         .await?;
     collection.count_documents().await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_update_documents(
-    app: tauri::AppHandle,
+pub async fn collection_update_documents<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     query: crate::types::JsonDocument,
-    update: crate::types::JsonDocument,
-    operations: OperationCount,
-    upsert: bool,
+    changes: DocumentUpdate,
 ) -> crate::Result<UpdateResult> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
         .await?;
-    match operations {
+    match changes.operations {
         OperationCount::One => {
             collection
                 .update_one_with_options(
                     query.into(),
-                    update.into(),
-                    UpdateOptions::builder().upsert(upsert).build(),
+                    changes.update.into(),
+                    UpdateOptions::builder().upsert(changes.upsert).build(),
                 )
                 .await
         }
@@ -175,8 +540,8 @@ pub async fn collection_update_documents(
             collection
                 .update_many_with_options(
                     query.into(),
-                    update.into(),
-                    UpdateOptions::builder().upsert(upsert).build(),
+                    changes.update.into(),
+                    UpdateOptions::builder().upsert(changes.upsert).build(),
                 )
                 .await
         }
@@ -186,14 +551,16 @@ pub async fn collection_update_documents(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_delete_documents(
-    app: tauri::AppHandle,
+pub async fn collection_delete_documents<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     query: crate::types::JsonDocument,
     operations: OperationCount
 ) -> crate::Result<u64> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
@@ -219,31 +586,34 @@ pub async fn collection_delete_documents(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_create_index(
-    app: tauri::AppHandle,
+pub async fn collection_create_index<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     keys: crate::types::JsonDocument,
-    name: Option<String>,
-    unique: Option<bool>
+    options: CreateIndexOptions
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
         .await?;
-    collection.create_index(IndexModel {keys: keys.into(), options: Some(IndexOptions {name, unique})}).await
+    collection.create_index(IndexModel {keys: keys.into(), options: Some(IndexOptions {name: options.name, unique: options.unique})}).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_drop_index(
-    app: tauri::AppHandle,
+pub async fn collection_drop_index<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     name: String
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
@@ -253,12 +623,14 @@ pub async fn collection_drop_index(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_drop(
-    app: tauri::AppHandle,
+pub async fn collection_drop<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
@@ -268,13 +640,15 @@ pub async fn collection_drop(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_insert_documents(
-    app: tauri::AppHandle,
+pub async fn collection_insert_documents<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     documents: Vec<crate::types::JsonDocument>
 ) -> crate::Result<HashMap<usize, crate::types::JsonBson>> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
         .collection::<bson::Document>(context, database, collection)
@@ -284,35 +658,37 @@ pub async fn collection_insert_documents(
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_find_many_documents(
-    app: tauri::AppHandle,
+pub async fn collection_find_many_documents<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     filter: crate::types::JsonDocument,
-    skip: Option<u64>,
-    limit: Option<u64>,
-    sort: Option<crate::types::JsonDocument>
+    options: FindDocumentsOptions
 ) -> crate::Result<Vec<crate::types::JsonDocument>> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
-        .collection::<bson::Document>(context, database, collection)
+        .existing_collection::<bson::Document>(context, database, collection)
         .await?;
-    collection.find(filter.into(), skip, limit, sort.and_then(|i| Some(i.into()))).await.and_then(|r| Ok(r.iter().map(|i| crate::types::JsonDocument::from(i.clone())).collect()))
+    collection.find(filter.into(), options.skip, options.limit, options.sort.and_then(|i| Some(i.into()))).await.and_then(|r| Ok(r.iter().map(|i| crate::types::JsonDocument::from(i.clone())).collect()))
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn collection_find_one_document(
-    app: tauri::AppHandle,
+pub async fn collection_find_one_document<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     database: DatabaseSpecifier,
     collection: CollectionSpecifier,
     filter: crate::types::JsonDocument,
 ) -> crate::Result<Option<crate::types::JsonDocument>> {
+    scope.check(&app, &context).await?;
     let collection = app
         .persistence()
-        .collection::<bson::Document>(context, database, collection)
+        .existing_collection::<bson::Document>(context, database, collection)
         .await?;
     collection.find_one(filter.into()).await.and_then(|r| Ok(r.and_then(|s| Some(s.into()))))
 }
@@ -320,164 +696,527 @@ pub async fn collection_find_one_document(
 // File handle commands
 #[tauri::command]
 #[specta::specta]
-pub async fn file_close(
-    app: tauri::AppHandle,
+pub async fn file_close<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let file = app.persistence().file_handle(context, file_handle).await?;
     file.close().await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_write_text(
-    app: tauri::AppHandle,
+pub async fn file_write_text<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier,
     data: String
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let file = app.persistence().file_handle(context, file_handle).await?;
-    let mutex_handle = file.handle().await;
-    let mut handle = mutex_handle.lock();
-    handle.write_all(data.as_bytes()).await.or_else(|e| Err(crate::Error::from(e)))
+    file.write(data.as_bytes()).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_write_bytes(
-    app: tauri::AppHandle,
+pub async fn file_write_bytes<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier,
     data: Vec<u8>
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let file = app.persistence().file_handle(context, file_handle).await?;
-    let mutex_handle = file.handle().await;
-    let mut handle = mutex_handle.lock();
-    handle.write_all(&data).await.or_else(|e| Err(crate::Error::from(e)))
+    file.write(&data).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_read_text(
-    app: tauri::AppHandle,
+pub async fn file_read_text<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier,
     size: Option<usize>
 ) -> crate::Result<String> {
+    scope.check(&app, &context).await?;
     let file = app.persistence().file_handle(context, file_handle).await?;
-    let mutex_handle = file.handle().await;
-    let mut handle = mutex_handle.lock();
-    let output: Vec<u8> = if let Some(sz) = size {
-        let mut buffer: Box<[u8]> = vec![0; sz].into_boxed_slice();
-        handle.read(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
-        buffer.to_vec()
-    } else {
-        let mut buffer: Vec<u8> = Vec::new();
-        handle.read_to_end(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
-        buffer
-    };
+    let output = file.read(size).await?;
     let output_size = output.len();
     String::from_utf8(output).or_else(|_| Err(crate::Error::string_encoding(output_size)))
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_read_bytes(
-    app: tauri::AppHandle,
+pub async fn file_read_bytes<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     file_handle: FileHandleSpecifier,
     size: Option<usize>
 ) -> crate::Result<Vec<u8>> {
+    scope.check(&app, &context).await?;
     let file = app.persistence().file_handle(context, file_handle).await?;
-    let mutex_handle = file.handle().await;
-    let mut handle = mutex_handle.lock();
-    let output: Vec<u8> = if let Some(sz) = size {
-        let mut buffer: Box<[u8]> = vec![0; sz].into_boxed_slice();
-        handle.read(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
-        buffer.to_vec()
+    file.read(size).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn file_flush<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    file_handle: FileHandleSpecifier
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let file = app.persistence().file_handle(context, file_handle).await?;
+    file.flush().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn file_sync<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    file_handle: FileHandleSpecifier
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let file = app.persistence().file_handle(context, file_handle).await?;
+    file.sync().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn file_lock<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    file_handle: FileHandleSpecifier,
+    exclusive: bool
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let file = app.persistence().file_handle(context, file_handle).await?;
+    if exclusive {
+        file.lock_exclusive().await
     } else {
-        let mut buffer: Vec<u8> = Vec::new();
-        handle.read_to_end(&mut buffer).await.or_else(|e| Err(crate::Error::from(e)))?;
-        buffer
-    };
-    Ok(output)
+        file.lock_shared().await
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn file_unlock<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    file_handle: FileHandleSpecifier
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let file = app.persistence().file_handle(context, file_handle).await?;
+    file.unlock().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn temp_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier
+) -> crate::Result<FileHandleInfo> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.temp_file().await?.info().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn list_file_handles<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier
+) -> crate::Result<Vec<FileHandleInfo>> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.file_handles().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn close_all_file_handles<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.close_all_file_handles().await
 }
 
 // Filesystem commands
 #[tauri::command]
 #[specta::specta]
-pub async fn get_context_base_path(
-    app: tauri::AppHandle,
+pub async fn get_context_base_path<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier
 ) -> crate::Result<String> {
-    let context = app.persistence().context(context).await?;
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
     context.base_path_canonicalized().and_then(|p| Ok(String::from_utf8_lossy(p.into_os_string().as_encoded_bytes()).to_string()))
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn create_directory(
-    app: tauri::AppHandle,
+pub async fn create_directory<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String,
     parents: bool
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let context = app.persistence().context(context).await?;
     context.create_directory(path, parents).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn remove_directory(
-    app: tauri::AppHandle,
+pub async fn remove_directory<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let context = app.persistence().context(context).await?;
     context.remove_directory(path).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn remove_file(
-    app: tauri::AppHandle,
+pub async fn remove_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String
 ) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
     let context = app.persistence().context(context).await?;
     context.remove_file(path).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn get_absolute_path_to(
-    app: tauri::AppHandle,
+pub async fn get_absolute_path_to<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String
 ) -> crate::Result<String> {
-    let context = app.persistence().context(context).await?;
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
     Ok(String::from_utf8_lossy(context.get_path(path)?.into_os_string().as_encoded_bytes()).to_string())
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn file_metadata(
-    app: tauri::AppHandle,
+pub async fn set_permissions<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    mode: u32
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.set_permissions(path, mode).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn set_readonly<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    readonly: bool
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.set_readonly(path, readonly).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn hash_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    algorithm: HashAlgorithm,
+    cache: Option<String>
+) -> crate::Result<FileHash> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    match cache {
+        Some(alias) => context.hash_file_cached(path, algorithm, &context.database(alias).await?).await,
+        None => context.hash_file(path, algorithm).await
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn hash_directory<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    algorithm: HashAlgorithm,
+    options: Option<WalkOptions>,
+    cache: Option<String>
+) -> crate::Result<Vec<FileHash>> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    let cache = match cache {
+        Some(alias) => Some(context.database(alias).await?),
+        None => None
+    };
+    context.hash_directory(path, algorithm, options.unwrap_or_default(), cache.as_ref()).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn find_duplicates<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    algorithm: HashAlgorithm,
+    options: Option<WalkOptions>,
+    cache: Option<String>
+) -> crate::Result<Vec<Vec<FileHash>>> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    let cache = match cache {
+        Some(alias) => Some(context.database(alias).await?),
+        None => None
+    };
+    context.find_duplicates(path, algorithm, options.unwrap_or_default(), cache.as_ref()).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn exists<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String
+) -> crate::Result<bool> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.exists(path).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn is_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String
+) -> crate::Result<bool> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.is_file(path).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn is_dir<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String
+) -> crate::Result<bool> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.is_dir(path).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn stat_many<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    paths: Vec<String>
+) -> crate::Result<Vec<Option<PathMetadata>>> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.stat_many(paths).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn file_metadata<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String
 ) -> crate::Result<PathMetadata> {
-    let context = app.persistence().context(context).await?;
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
     context.file_metadata(path).await
 }
 
 #[tauri::command]
 #[specta::specta]
-pub async fn list_directory(
-    app: tauri::AppHandle,
+pub async fn list_directory<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
     context: ContextSpecifier,
     path: String
 ) -> crate::Result<Vec<PathInformation>> {
-    let context = app.persistence().context(context).await?;
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
     context.list_directory(path).await
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn walk<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    options: Option<WalkOptions>
+) -> crate::Result<Vec<WalkEntry>> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.walk(path, options.unwrap_or_default()).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn walk_stream<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    options: Option<WalkOptions>,
+    channel: Channel<WalkEntry>
+) -> crate::Result<u32> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    let mut count: u32 = 0;
+    context.walk_with(path, options.unwrap_or_default(), |entry| {
+        channel.send(entry).or_else(|e| Err(crate::Error::from(e)))?;
+        count += 1;
+        Ok(())
+    }).await?;
+    Ok(count)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn copy_path<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    from: String,
+    to: String,
+    recursive: bool,
+    overwrite: bool
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.copy(from, to, recursive, overwrite).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn rename_path<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    from: String,
+    to: String
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.rename(from, to).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn temp_dir<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier
+) -> crate::Result<String> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.temp_dir().await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn watch<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    recursive: bool
+) -> crate::Result<bson::Uuid> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.watch(path, recursive).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn unwatch<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    id: bson::Uuid
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.unwatch(id).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn read_document<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    format: Option<DocumentFormat>
+) -> crate::Result<serde_json::Value> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().existing_context(context).await?;
+    context.read_document::<serde_json::Value>(path, format).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn write_document<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    scope: ContextScope,
+    context: ContextSpecifier,
+    path: String,
+    document: serde_json::Value,
+    format: Option<DocumentFormat>
+) -> crate::Result<()> {
+    scope.check(&app, &context).await?;
+    let context = app.persistence().context(context).await?;
+    context.write_document(path, &document, format).await
+}
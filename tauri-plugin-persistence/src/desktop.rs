@@ -4,19 +4,23 @@ use serde::{de::DeserializeOwned, Serialize};
 use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime, State};
 use tokio::sync::Mutex;
 
-use crate::{api::types::{CollectionSpecifier, ContextSpecifier, DatabaseSpecifier, FileHandleSpecifier}, state::{ContextState, PluginState}};
+use crate::{api::types::{ArchiveFormat, CollectionSpecifier, ContextOptions, ContextSpecifier, DatabaseSpecifier, FileHandleSpecifier, KvSpecifier}, state::{ContextLock, ContextState, PluginState}};
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
   app: &AppHandle<R>,
   _api: PluginApi<R, C>,
 ) -> crate::Result<Persistence<R>> {
-  Ok(Persistence(app.clone()))
+  Ok(Persistence::new(app.clone()))
 }
 
 /// Access to the persistence APIs.
 pub struct Persistence<R: Runtime>(AppHandle<R>);
 
 impl<R: Runtime> Persistence<R> {
+    pub(crate) fn new(app: AppHandle<R>) -> Self {
+        Self(app)
+    }
+
     fn contexts(&self) -> State<'_, PluginState> {
         self.0.state::<PluginState>().clone()
     }
@@ -25,7 +29,7 @@ impl<R: Runtime> Persistence<R> {
         self.0.clone()
     }
 
-    async fn context_ids(&self) -> Vec<String> {
+    pub(crate) async fn context_ids(&self) -> Vec<String> {
         let ctx = self.contexts();
         let contexts = ctx.lock().await;
         contexts.keys().map(|k| k.clone()).collect()
@@ -34,36 +38,102 @@ impl<R: Runtime> Persistence<R> {
     /// Opens a context at a path, or returns the existing context if it's already open at that path.
     /// Attmepting to open an existing context at a new path will fail.
     pub async fn open_context(&self, name: impl AsRef<str>, path: impl AsRef<str>) -> crate::Result<crate::Context<R>> {
+        self.open_context_with_options(name, path, ContextOptions::default()).await
+    }
+
+    /// Opens a context like [Persistence::open_context], additionally taking an exclusive lock on its root directory.
+    /// Fails with [crate::Error::ContextLocked] if another process (or another context in this process) holds the lock.
+    /// Since nothing else can be using the context, temporary files left over from earlier sessions are cleared.
+    pub async fn open_locked_context(&self, name: impl AsRef<str>, path: impl AsRef<str>) -> crate::Result<crate::Context<R>> {
+        self.open_context_with_options(name, path, ContextOptions {lock: true, ..Default::default()}).await
+    }
+
+    /// Opens a context like [Persistence::open_context], using the specified [ContextOptions].
+    /// If the context is already open, its existing options are kept, although asking to lock a context that was opened
+    /// without a lock fails.
+    pub async fn open_context_with_options(&self, name: impl AsRef<str>, path: impl AsRef<str>, options: ContextOptions) -> crate::Result<crate::Context<R>> {
         let ctx = self.contexts();
         let resolved_path = std::path::PathBuf::from_str(path.as_ref()).or(Err(crate::Error::invalid_path(path.as_ref())))?;
-        let mut contexts = ctx.lock().await;
+        if let Some(ctx) = ctx.lock().await.get(&name.as_ref().to_string()) {
+            return self.reopen_context(name, path, options, ctx);
+        }
 
-        if let Some(ctx) = contexts.get(&name.as_ref().to_string()) {
-            if ctx.root_path == path.as_ref().to_string() {
-                Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), path.as_ref().to_string()))
-            } else {
-                Err(crate::Error::open_context(name, path, "Context already open at a different path."))
+        if resolved_path.exists() {
+            if !resolved_path.is_dir() {
+                return Err(crate::Error::open_context(name, path, "Specified path is not a directory."));
             }
         } else {
-            if resolved_path.exists() {
-                if resolved_path.is_dir() {
-                    let _ = contexts.insert(name.as_ref().to_string(), ContextState {name: name.as_ref().to_string(), root_path: path.as_ref().to_string(), databases: Arc::new(Mutex::new(HashMap::new())), files: Arc::new(Mutex::new(HashMap::new()))});
-                    Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), path.as_ref().to_string()))
-                } else {
-                    Err(crate::Error::open_context(name, path, "Specified path is not a directory."))
-                }
-            } else {
-                tokio::fs::create_dir_all(resolved_path).await.or_else(|e| Err(crate::Error::open_context(name.as_ref(), path.as_ref(), format!("Failed to create context directory: {e:?}"))))?;
-                let _ = contexts.insert(name.as_ref().to_string(), ContextState {name: name.as_ref().to_string(), root_path: path.as_ref().to_string(), databases: Arc::new(Mutex::new(HashMap::new())), files: Arc::new(Mutex::new(HashMap::new()))});
-                Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), path.as_ref().to_string()))
+            tokio::fs::create_dir_all(&resolved_path).await.or_else(|e| Err(crate::Error::open_context(name.as_ref(), path.as_ref(), format!("Failed to create context directory: {e:?}"))))?;
+        }
+
+        // Acquiring the lock touches the filesystem, so it's done before taking the (async) state lock
+        let lock = if options.lock {
+            let root = resolved_path.clone();
+            let acquired = tokio::task::spawn_blocking(move || ContextLock::acquire(root)).await.or_else(|e| Err(crate::Error::open_context(name.as_ref(), path.as_ref(), e.to_string())))?;
+            Some(Arc::new(acquired?))
+        } else {
+            None
+        };
+
+        let mut contexts = ctx.lock().await;
+        if let Some(ctx) = contexts.get(&name.as_ref().to_string()) {
+            // Opened concurrently while the lock was being acquired
+            return self.reopen_context(name, path, options, ctx);
+        }
+
+        let _ = contexts.insert(name.as_ref().to_string(), ContextState {name: name.as_ref().to_string(), root_path: path.as_ref().to_string(), databases: Arc::new(Mutex::new(HashMap::new())), kv_stores: Arc::new(Mutex::new(HashMap::new())), files: Arc::new(Mutex::new(HashMap::new())), temporary_directories: Arc::new(Mutex::new(Vec::new())), lock, watchers: Arc::new(Mutex::new(HashMap::new())), resolution: options.resolution});
+        drop(contexts);
+
+        let context = crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), path.as_ref().to_string(), options.resolution);
+        // Without the lock, another process may still be using the temporary directory
+        if options.lock {
+            let _ = context.remove_temporary_directory().await;
+        }
+        Ok(context)
+    }
+
+    /// Returns a context that's already open, if it matches the path & options it's being opened with
+    fn reopen_context(&self, name: impl AsRef<str>, path: impl AsRef<str>, options: ContextOptions, ctx: &ContextState) -> crate::Result<crate::Context<R>> {
+        if ctx.root_path != path.as_ref() {
+            Err(crate::Error::open_context(name, path, "Context already open at a different path."))
+        } else if options.lock && ctx.lock.is_none() {
+            Err(crate::Error::open_context(name, path, "Context already open without a lock."))
+        } else {
+            Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), path.as_ref().to_string(), ctx.resolution))
+        }
+    }
+
+    /// Exports an open context to an archive at `destination`. See [crate::Context::export].
+    pub async fn export_context(&self, alias: impl AsRef<str>, destination: impl AsRef<std::path::Path>, format: ArchiveFormat) -> crate::Result<()> {
+        self.aliased_context(alias).await?.export(destination, format).await
+    }
+
+    /// Unpacks an archive (zip or tar.gz, detected automatically) into a new context at `path`, which must be empty or not yet exist.
+    /// Every entry is validated like any other context path, so archives can't write outside the new context.
+    pub async fn import_context(&self, archive: impl AsRef<std::path::Path>, path: impl AsRef<str>, alias: impl AsRef<str>) -> crate::Result<crate::Context<R>> {
+        let target = std::path::PathBuf::from_str(path.as_ref()).or(Err(crate::Error::invalid_path(path.as_ref())))?;
+        let created = !target.exists();
+        if !created && std::fs::read_dir(&target).or_else(|e| Err(crate::Error::open_context(alias.as_ref(), path.as_ref(), e.to_string())))?.next().is_some() {
+            return Err(crate::Error::open_context(alias, path, "Import destination must be an empty directory."));
+        }
+
+        let context = self.open_context(alias.as_ref(), path.as_ref()).await?;
+        if let Err(error) = context.import_archive(archive).await {
+            let _ = self.close_context(ContextSpecifier::Aliased { alias: alias.as_ref().to_string() }).await;
+            let _ = tokio::fs::remove_dir_all(&target).await;
+            if !created {
+                let _ = tokio::fs::create_dir(&target).await;
             }
+            return Err(error);
         }
+
+        Ok(context)
     }
 
     /// Returns an already-open context
     pub async fn aliased_context(&self, name: impl AsRef<str>) -> crate::Result<crate::Context<R>> {
         if let Some(ctx) = self.contexts().lock().await.get(&name.as_ref().to_string()) {
-            Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), ctx.root_path.clone()))
+            Ok(crate::Context::<R>::create(self.handle(), name.as_ref().to_string(), ctx.root_path.clone(), ctx.resolution))
         } else {
             Err(crate::Error::unknown_context(name))
         }
@@ -73,7 +143,22 @@ impl<R: Runtime> Persistence<R> {
     pub async fn context(&self, context: ContextSpecifier) -> crate::Result<crate::Context<R>> {
         match context {
             ContextSpecifier::Aliased { alias } => self.aliased_context(alias).await,
-            ContextSpecifier::Direct { alias, path } => self.open_context(alias, path).await
+            ContextSpecifier::Direct { alias, path, lock, resolution } => self.open_context_with_options(alias, path, ContextOptions {lock, resolution}).await
+        }
+    }
+
+    /// Like [Persistence::context], but never opens a context. A [ContextSpecifier::Direct] must name one that's already open at that path.
+    pub async fn existing_context(&self, context: ContextSpecifier) -> crate::Result<crate::Context<R>> {
+        match context {
+            ContextSpecifier::Aliased { alias } => self.aliased_context(alias).await,
+            ContextSpecifier::Direct { alias, path, .. } => {
+                let context = self.aliased_context(&alias).await?;
+                if context.path() == path {
+                    Ok(context)
+                } else {
+                    Err(crate::Error::open_context(alias, path, "Context already open at a different path."))
+                }
+            }
         }
     }
 
@@ -82,7 +167,51 @@ impl<R: Runtime> Persistence<R> {
         let context = self.context(context).await?;
         match database {
             DatabaseSpecifier::Aliased { alias } => context.database(alias).await,
-            DatabaseSpecifier::Direct { alias, path } => context.open_database(alias, path).await
+            DatabaseSpecifier::Memory { alias, memory: true } => context.open_memory_database(alias).await,
+            DatabaseSpecifier::Memory { alias, .. } => Err(crate::Error::open_database(alias, context.name(), "", "`memory` must be true.")),
+            DatabaseSpecifier::Direct { alias, path, backend } => context.open_database_with_backend(alias, path, backend).await
+        }
+    }
+
+    /// Like [Persistence::database], but never opens a context or database, so nothing is created on disk
+    pub async fn existing_database(&self, context: ContextSpecifier, database: DatabaseSpecifier) -> crate::Result<crate::Database<R>> {
+        let context = self.existing_context(context).await?;
+        match database {
+            DatabaseSpecifier::Aliased { alias } | DatabaseSpecifier::Memory { alias, memory: true } => context.database(alias).await,
+            DatabaseSpecifier::Memory { alias, .. } => Err(crate::Error::open_database(alias, context.name(), "", "`memory` must be true.")),
+            DatabaseSpecifier::Direct { alias, path, .. } => {
+                let database = context.database(&alias).await?;
+                if database.path() == path {
+                    Ok(database)
+                } else {
+                    Err(crate::Error::open_database(alias, context.name(), path, "Database is already open at another path."))
+                }
+            }
+        }
+    }
+
+    /// Returns a key-value store based on a [ContextSpecifier] and a [KvSpecifier]
+    pub async fn kv_store(&self, context: ContextSpecifier, store: KvSpecifier) -> crate::Result<crate::KvStore<R>> {
+        let context = self.context(context).await?;
+        match store {
+            KvSpecifier::Aliased { alias } => context.kv_store(alias).await,
+            KvSpecifier::Direct { alias, path, encoding } => context.open_kv_store_with_encoding(alias, path, encoding).await
+        }
+    }
+
+    /// Like [Persistence::kv_store], but never opens a context or store, so nothing is created on disk
+    pub async fn existing_kv_store(&self, context: ContextSpecifier, store: KvSpecifier) -> crate::Result<crate::KvStore<R>> {
+        let context = self.existing_context(context).await?;
+        match store {
+            KvSpecifier::Aliased { alias } => context.kv_store(alias).await,
+            KvSpecifier::Direct { alias, path, .. } => {
+                let store = context.kv_store(&alias).await?;
+                if store.path() == path {
+                    Ok(store)
+                } else {
+                    Err(crate::Error::open_kv_store(alias, context.name(), path, "Key-value store is already open at another path."))
+                }
+            }
         }
     }
 
@@ -113,18 +242,27 @@ impl<R: Runtime> Persistence<R> {
         }
     }
 
+    /// Like [Persistence::collection], but never opens a context or database, so nothing is created on disk
+    pub async fn existing_collection<T: Serialize + DeserializeOwned + Sync + Send>(&self, context: ContextSpecifier, database: DatabaseSpecifier, collection: CollectionSpecifier) -> crate::Result<crate::Collection<T, R>> {
+        let database = self.existing_database(context, database).await?;
+        match collection {
+            CollectionSpecifier::Global { name } => Ok(database.collection(name).await),
+            CollectionSpecifier::Transaction { transaction, name } => {
+                let trn = database.get_transaction(transaction).await?;
+                Ok(trn.collection(name))
+            }
+        }
+    }
+
     /// Closes a context based on a [ContextSpecifier]
     pub async fn close_context(&self, context: ContextSpecifier) -> crate::Result<()> {
-        let ctx = match context {
-            ContextSpecifier::Aliased { alias } => self.aliased_context(alias).await,
-            ContextSpecifier::Direct { alias, path } => self.open_context(alias, path).await
-        }?;
+        let ctx = self.context(context).await?;
         let cid = ctx.name();
-        ctx.close().await?;
+        let result = ctx.close().await;
         let ctxs = self.contexts();
         let mut contexts = ctxs.lock().await;
         let _ = contexts.remove(&cid);
-        Ok(())
+        result
     }
 
     /// Closes all active contexts and removes them from tracking.
@@ -1,8 +1,10 @@
 //! High-level abstractions for project contexts & database access for Tauri applications.
 //! 
 //! Primary features:
-//! - Database creation & management with PoloDB
-//! - Management of open file handles
+//! - Database creation & management with PoloDB, or SQLite with the `sqlite` feature, plus in-memory databases that can be persisted later
+//! - Embedded key-value stores for simple settings
+//! - Encryption at rest for databases & file handles, with keys supplied from Rust through [PluginBuilder::keyring]
+//! - Management of open file handles, with optional gzip/zstd compression
 //! - Basic filesystem operations within the context
 //! - Automatic prevention of context escapes
 
@@ -13,17 +15,21 @@ use tauri::{
     Manager, Runtime,
 };
 
+#[macro_use]
+mod command_list;
 mod api;
 mod commands;
 #[cfg(desktop)]
 mod desktop;
 
-pub use api::{Collection, Context, Database, Error, FileHandle, Result, Transaction, types, ContextDB, ContextFileHandle, ContextState, FileHandleMode};
+pub use api::{Collection, Context, ContextScope, ContextScopeEntry, ScopeTarget, Database, DatabaseErrorCode, Error, IoErrorKind, FileHandle, KvStore, KvTransaction, Result, Transaction, types, ContextDB, ContextFileHandle, ContextKv, ContextState, ContextWatcher, FileHandleMode, DocumentCollection, DocumentStore, FindOptions, MemoryBackend, EncryptedBackend, EncryptionKey, KeyRequest, Keyring, PoloBackend, SqlStore, StorageBackend, StoreTransaction};
+#[cfg(feature = "sqlite")]
+pub use api::SqliteBackend;
 pub(crate) use api::state;
 
 #[cfg(desktop)]
 pub use desktop::Persistence;
-use tauri_specta::collect_commands;
+use tauri_specta::{collect_commands, collect_events};
 use tokio::sync::Mutex;
 
 /// Extensions to [`tauri::App`], [`tauri::AppHandle`] and [`tauri::Window`] to access the persistence APIs.
@@ -38,60 +44,84 @@ impl<R: Runtime, T: Manager<R>> crate::PersistenceExt<R> for T {
     }
 }
 
+// Commands are generic over the runtime. The handler infers it, specta only needs a concrete one to read the signatures.
+macro_rules! register_commands {
+    ($($command:ident),* $(,)?) => {
+        collect_commands![$(commands::$command::<tauri::Wry>),*]
+    };
+}
+
 #[doc(hidden)]
-fn builder() -> tauri_specta::Builder<tauri::Wry> {
-    tauri_specta::Builder::<tauri::Wry>::new()
+fn builder<R: Runtime>() -> tauri_specta::Builder<R> {
+    tauri_specta::Builder::<R>::new()
     .plugin_name("persistence")
-    .commands(collect_commands![
-        commands::context,
-        commands::database,
-        commands::file_handle,
-        commands::database_get_collections,
-        commands::database_close,
-        commands::database_start_transaction,
-        commands::database_commit_transaction,
-        commands::database_rollback_transaction,
-        commands::collection_count_documents,
-        commands::collection_update_documents,
-        commands::collection_delete_documents,
-        commands::collection_create_index,
-        commands::collection_drop_index,
-        commands::collection_drop,
-        commands::collection_insert_documents,
-        commands::collection_find_many_documents,
-        commands::collection_find_one_document,
-        commands::file_close,
-        commands::file_write_text,
-        commands::file_write_bytes,
-        commands::file_read_text,
-        commands::file_read_bytes,
-        commands::get_context_base_path,
-        commands::get_absolute_path_to,
-        commands::create_directory,
-        commands::remove_directory,
-        commands::remove_file,
-        commands::file_metadata,
-        commands::list_directory,
-        commands::close_context,
-        commands::cleanup
-    ])
+    .commands(persistence_commands!(register_commands))
+    // Registering events also strips tauri's placeholder Channel type from the exported bindings
+    .events(collect_events![types::ContextWatchEvent])
+}
+
+/// Configures the plugin before it's initialized
+#[derive(Default)]
+pub struct PluginBuilder {
+    keyring: Option<Keyring>,
+    archive_directories: Vec<std::path::PathBuf>,
+    follow_symlinks: bool,
+}
+
+impl PluginBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the callback supplying keys for encrypted databases & file handles. It's given the context & path the key is for,
+    /// & returning `None` denies access. Keys never pass through JS.
+    pub fn keyring(mut self, keyring: impl Fn(&KeyRequest) -> Option<EncryptionKey> + Send + Sync + 'static) -> Self {
+        self.keyring = Some(std::sync::Arc::new(keyring));
+        self
+    }
+
+    /// Allows the `export_context` & `import_context` commands to write & read archives within `directory`.
+    /// Archives are Rust-only unless at least one directory is allowed, since they live outside of any context.
+    pub fn archive_directory(mut self, directory: impl Into<std::path::PathBuf>) -> Self {
+        self.archive_directories.push(directory.into());
+        self
+    }
+
+    /// Allows JS to open contexts with [types::PathResolution::Follow], letting symlinks inside them point anywhere on the host.
+    /// Without it, such contexts can only be opened from Rust.
+    pub fn follow_symlinks(mut self) -> Self {
+        self.follow_symlinks = true;
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        let builder = builder::<R>();
+
+        Builder::new("persistence")
+            .invoke_handler(builder.invoke_handler())
+            .setup(move |app, api| {
+                #[cfg(desktop)]
+                let persistence = desktop::init(app, api)?;
+                app.manage(persistence);
+                manage_state(app, self);
+                builder.mount_events(app);
+                Ok(())
+            })
+            .build()
+    }
+}
+
+/// Registers the state shared by every context on the app, as configured on a [PluginBuilder]
+pub(crate) fn manage_state<R: Runtime>(app: &tauri::AppHandle<R>, config: PluginBuilder) {
+    app.manage::<state::PluginState>(Mutex::new(HashMap::new()));
+    app.manage(state::KeyringState(config.keyring));
+    app.manage(state::ArchiveDirectories(config.archive_directories));
+    app.manage(state::SymlinkFollowing(config.follow_symlinks));
 }
 
-/// Initializes the plugin.
-pub fn init() -> TauriPlugin<tauri::Wry> {
-    let builder = builder();
-
-    Builder::new("persistence")
-        .invoke_handler(builder.invoke_handler())
-        .setup(move |app, api| {
-            #[cfg(desktop)]
-            let persistence = desktop::init(app, api)?;
-            app.manage(persistence);
-            app.manage::<state::PluginState>(Mutex::new(HashMap::new()));
-            builder.mount_events(app);
-            Ok(())
-        })
-        .build()
+/// Initializes the plugin without a keyring. Use [PluginBuilder] to enable encryption.
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    PluginBuilder::new().build()
 }
 
 #[cfg(test)]
@@ -100,7 +130,7 @@ mod test {
 
     #[test]
     fn export_types() {
-        builder()
+        builder::<tauri::Wry>()
             .export(
                 specta_typescript::Typescript::default()
                     .formatter(specta_typescript::formatter::prettier)
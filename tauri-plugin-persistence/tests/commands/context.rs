@@ -0,0 +1,246 @@
+use serde_json::{json, Value};
+use tauri_plugin_persistence::{types::ContextSpecifier, PersistenceExt, PluginBuilder};
+
+use crate::{kind, Harness};
+
+#[test]
+fn opens_context() {
+    let harness = Harness::new();
+    let info: Value = harness.call("context", json!({"context": harness.context()}));
+    assert_eq!(info["name"], "test");
+    assert_eq!(info["lock"], Value::Null);
+    assert!(harness.root.is_dir());
+
+    let aliased: Value = harness.call("context", json!({"context": {"alias": "test"}}));
+    assert_eq!(aliased["path"], info["path"]);
+}
+
+#[test]
+fn rejects_context_at_another_path() {
+    let harness = Harness::new();
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    let error = harness.fail("context", json!({"context": {"alias": "test", "path": harness.path("elsewhere")}}));
+    assert_eq!(kind(&error), "open_context");
+    assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "missing"}}))), "unknown_context");
+}
+
+#[test]
+fn locks_context() {
+    let harness = Harness::new();
+    let info: Value = harness.call("context", json!({"context": {"alias": "test", "path": harness.root, "lock": true}}));
+    assert_eq!(info["lock"]["pid"], std::process::id());
+    let info: Value = harness.call("context", json!({"context": {"alias": "test", "path": harness.root, "lock": true}}));
+    assert_eq!(info["lock"]["pid"], std::process::id());
+
+    // Asking for a lock on a context already open without one mustn't silently leave it unlocked
+    harness.call::<()>("close_context", json!({"context": {"alias": "test"}}));
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    let error = harness.fail("context", json!({"context": {"alias": "test", "path": harness.root, "lock": true}}));
+    assert_eq!(kind(&error), "open_context");
+}
+
+#[test]
+fn clears_temporary_files_only_when_locked() {
+    let harness = Harness::new();
+    std::fs::create_dir_all(harness.path(".tmp/stale")).unwrap();
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    assert!(harness.path(".tmp/stale").exists());
+
+    harness.call::<()>("close_context", json!({"context": {"alias": "test"}}));
+    harness.call::<Value>("context", json!({"context": {"alias": "test", "path": harness.root, "lock": true}}));
+    assert!(!harness.path(".tmp/stale").exists());
+}
+
+#[test]
+fn follows_symlinks_only_when_allowed() {
+    let harness = Harness::new();
+    let context = json!({"alias": "test", "path": harness.root, "resolution": "follow"});
+    assert_eq!(kind(&harness.fail("context", json!({"context": context}))), "symlink_denied");
+    assert!(!harness.root.exists());
+
+    let harness = Harness::with_plugin(PluginBuilder::new().follow_symlinks(), |capability| capability.permission("persistence:default"));
+    let context = json!({"alias": "test", "path": harness.root, "resolution": "follow"});
+    let info: Value = harness.call("context", json!({"context": context}));
+    assert_eq!(info["resolution"], "follow");
+}
+
+#[test]
+fn resolves_paths() {
+    let harness = Harness::new();
+    let context = harness.context();
+    harness.call::<Value>("context", json!({"context": context}));
+    let base: String = harness.call("get_context_base_path", json!({"context": context}));
+    let canonical = harness.root.canonicalize().unwrap();
+    assert_eq!(base, canonical.to_string_lossy());
+
+    let absolute: String = harness.call("get_absolute_path_to", json!({"context": context, "path": "nested/file.txt"}));
+    assert_eq!(absolute, canonical.join("nested/file.txt").to_string_lossy());
+    assert_eq!(kind(&harness.fail("get_absolute_path_to", json!({"context": context, "path": "../outside"}))), "path_escapes_context");
+    assert_eq!(kind(&harness.fail("get_absolute_path_to", json!({"context": context, "path": "/etc/passwd"}))), "no_absolute_paths");
+}
+
+#[test]
+fn exports_and_imports_context() {
+    let archives = std::env::temp_dir().join(format!("persistence-archives-{}", bson::Uuid::new()));
+    std::fs::create_dir_all(&archives).unwrap();
+    let harness = Harness::with_plugin(PluginBuilder::new().archive_directory(&archives), |capability| {
+        capability.permission("persistence:default").permission("persistence:allow-archive-operations")
+    });
+    let context = harness.context();
+    harness.call::<()>("write_document", json!({"context": context, "path": "data/settings.json", "document": {"theme": "dark"}}));
+    let database = json!({"alias": "main", "path": "main.db"});
+    let items = json!({"name": "items"});
+    harness.call::<Value>("collection_insert_documents", json!({"context": context, "database": database, "collection": items, "documents": [{"a": 1}]}));
+
+    for (format, file) in [("zip", "export.zip"), ("tar_gz", "export.tar.gz")] {
+        let archive = archives.join(file);
+        harness.call::<()>("export_context", json!({"context": context, "destination": archive, "format": format}));
+        assert!(archive.is_file());
+        // PoloDB is closed while the archive is written, then reopened
+        let count: u64 = harness.call("collection_count_documents", json!({"context": context, "database": database, "collection": items}));
+        assert_eq!(count, 1);
+
+        let alias = format!("imported_{format}");
+        let target = format!("{}-{format}", harness.root.display());
+        let info: Value = harness.call("import_context", json!({"archive": archive, "path": target, "alias": alias}));
+        assert_eq!(info["name"], alias);
+        let document: Value = harness.call("read_document", json!({"context": {"alias": alias}, "path": "data/settings.json"}));
+        assert_eq!(document, json!({"theme": "dark"}));
+        assert!(std::path::Path::new(&target).join("main.db/CURRENT").is_file());
+        let _ = std::fs::remove_dir_all(&target);
+    }
+
+    // Archives outside of the configured directories are refused, including through `..`
+    let inside = harness.path("export.zip");
+    assert_eq!(kind(&harness.fail("export_context", json!({"context": context, "destination": inside, "format": "zip"}))), "archive_error");
+    let escape = archives.join("../persistence-escape.zip");
+    assert_eq!(kind(&harness.fail("export_context", json!({"context": context, "destination": escape, "format": "zip"}))), "archive_error");
+    assert!(!escape.exists());
+    let error = harness.fail("import_context", json!({"archive": escape, "path": format!("{}-escape", harness.root.display()), "alias": "escape"}));
+    assert_eq!(kind(&error), "archive_error");
+    let _ = std::fs::remove_dir_all(&archives);
+
+    // Without any directories, archives are Rust-only
+    let harness = Harness::with_permissions(&["persistence:default", "persistence:allow-archive-operations"]);
+    let destination = std::env::temp_dir().join(format!("persistence-unconfigured-{}.zip", bson::Uuid::new()));
+    assert_eq!(kind(&harness.fail("export_context", json!({"context": harness.context(), "destination": destination, "format": "zip"}))), "archive_error");
+    assert!(!destination.exists());
+}
+
+#[test]
+fn denies_commands_without_permission() {
+    let harness = Harness::with_permissions(&[]);
+    assert!(harness.invoke("context", json!({"context": harness.context()})).is_err());
+    assert!(!harness.root.exists());
+
+    // Archives can read & write outside the context, so they're left out of the defaults
+    let harness = Harness::new();
+    let destination = std::env::temp_dir().join(format!("persistence-denied-{}.zip", bson::Uuid::new()));
+    assert!(harness.invoke("export_context", json!({"context": harness.context(), "destination": destination, "format": "zip"})).is_err());
+    assert!(!destination.exists());
+}
+
+#[test]
+fn grants_single_permissions() {
+    let harness = Harness::with_permissions(&["persistence:allow-context", "persistence:allow-exists"]);
+    let context = harness.context();
+    harness.call::<Value>("context", json!({"context": context}));
+    assert!(!harness.call::<bool>("exists", json!({"context": context, "path": "file.txt"})));
+    assert!(harness.invoke("is_file", json!({"context": context, "path": "file.txt"})).is_err());
+}
+
+#[test]
+fn closes_contexts() {
+    let harness = Harness::new();
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    harness.call::<()>("close_context", json!({"context": {"alias": "test"}}));
+    assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "test"}}))), "unknown_context");
+
+    // Closing everything at once isn't part of the defaults
+    assert!(harness.invoke("cleanup", json!({})).is_err());
+    let harness = Harness::with_permissions(&["persistence:allow-context-operations"]);
+    let other = format!("{}-other", harness.root.display());
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    harness.call::<Value>("context", json!({"context": {"alias": "other", "path": other}}));
+    harness.call::<()>("cleanup", json!({}));
+    for alias in ["test", "other"] {
+        assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": alias}}))), "unknown_context");
+    }
+    let _ = std::fs::remove_dir_all(&other);
+}
+
+#[test]
+fn scopes_contexts() {
+    let harness = Harness::with_capability(|capability| {
+        capability.permission_scoped("persistence:default", vec![json!({"alias": "test"}), json!({"alias": "shared-*"})], vec![json!({"path": "**/*-secret"})])
+    });
+    let context = harness.context();
+    harness.call::<Value>("context", json!({"context": context}));
+    assert!(!harness.call::<bool>("exists", json!({"context": context, "path": "file.txt"})));
+
+    let outside = format!("{}-outside", harness.root.display());
+    let error = harness.fail("context", json!({"context": {"alias": "outside", "path": outside}}));
+    assert_eq!(kind(&error), "scope_denied");
+    assert_eq!(error["alias"], "outside");
+    assert!(!std::path::Path::new(&outside).exists());
+
+    // Deny entries win over matching allow entries
+    let secret = format!("{}-secret", harness.root.display());
+    assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "shared-secret", "path": secret}}))), "scope_denied");
+    assert!(!std::path::Path::new(&secret).exists());
+
+    let shared = format!("{}-shared", harness.root.display());
+    harness.call::<Value>("context", json!({"context": {"alias": "shared-notes", "path": shared}}));
+    harness.call::<()>("close_context", json!({"context": {"alias": "shared-notes"}}));
+    let _ = std::fs::remove_dir_all(&shared);
+}
+
+#[test]
+fn scopes_open_contexts() {
+    // Aliased specifiers are checked against the path the context was opened at, even if it was opened from Rust
+    let deny = || vec![json!({"path": "**/*-private"})];
+    let harness = Harness::with_capability(|capability| {
+        capability
+            .permission_scoped("persistence:default", Vec::<Value>::new(), deny())
+            .permission_scoped("persistence:allow-cleanup", Vec::<Value>::new(), deny())
+    });
+    let persistence = harness.app.persistence();
+    let private = format!("{}-private", harness.root.display());
+    tauri::async_runtime::block_on(persistence.open_context("private", &private)).unwrap();
+    assert_eq!(kind(&harness.fail("exists", json!({"context": {"alias": "private"}, "path": "file.txt"}))), "scope_denied");
+    assert_eq!(kind(&harness.fail("close_context", json!({"context": {"alias": "private"}}))), "scope_denied");
+
+    // Cleanup only closes the contexts in scope
+    harness.call::<Value>("context", json!({"context": harness.context()}));
+    harness.call::<()>("cleanup", json!({}));
+    assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "test"}}))), "unknown_context");
+    assert!(tauri::async_runtime::block_on(persistence.aliased_context("private")).is_ok());
+    let _ = tauri::async_runtime::block_on(persistence.close_context(ContextSpecifier::Aliased { alias: "private".to_string() }));
+    let _ = std::fs::remove_dir_all(&private);
+}
+
+#[test]
+fn scopes_canonical_roots() {
+    // Roots are canonicalized before matching, so `..` & symlinks can't climb out of an allowed directory
+    let allowed = std::env::temp_dir().join(format!("persistence-scoped-{}", bson::Uuid::new()));
+    std::fs::create_dir_all(&allowed).unwrap();
+    let allowed = allowed.canonicalize().unwrap();
+    let pattern = format!("{}/**", glob::Pattern::escape(&allowed.to_string_lossy()));
+    let harness = Harness::with_capability(|capability| capability.permission_scoped("persistence:default", vec![json!({"path": pattern})], Vec::<Value>::new()));
+
+    let inside = allowed.join("inside");
+    harness.call::<Value>("context", json!({"context": {"alias": "inside", "path": inside}}));
+    harness.call::<()>("close_context", json!({"context": {"alias": "inside"}}));
+
+    let escape = format!("{}/new/../../escape-{}", allowed.display(), bson::Uuid::new());
+    assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "escape", "path": escape}}))), "scope_denied");
+    assert!(!std::path::Path::new(&escape).exists());
+
+    #[cfg(unix)]
+    {
+        std::fs::create_dir_all(&harness.root).unwrap();
+        std::os::unix::fs::symlink(&harness.root, allowed.join("link")).unwrap();
+        assert_eq!(kind(&harness.fail("context", json!({"context": {"alias": "linked", "path": allowed.join("link")}}))), "scope_denied");
+    }
+    let _ = std::fs::remove_dir_all(&allowed);
+}
@@ -0,0 +1,319 @@
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use tauri_plugin_persistence::{types::{ContextSpecifier, DatabaseSpecifier}, PersistenceExt};
+
+use crate::{kind, Harness};
+
+struct Fixture {
+    harness: Rc<Harness>,
+    context: Value,
+    database: Value,
+}
+
+impl Fixture {
+    /// A PoloDB database on disk
+    fn new() -> Self {
+        let harness = Harness::new();
+        let context = harness.context();
+        Self { context, database: json!({"alias": "main", "path": "main.db"}), harness: Rc::new(harness) }
+    }
+
+    fn memory() -> Self {
+        Self::new().with_database(json!({"alias": "main", "memory": true}))
+    }
+
+    /// Another database in the same context
+    fn with_database(&self, database: Value) -> Self {
+        Self { harness: self.harness.clone(), context: self.context.clone(), database }
+    }
+
+    fn call<T: serde::de::DeserializeOwned>(&self, command: &str, mut args: Value) -> T {
+        args["context"] = self.context.clone();
+        args["database"] = self.database.clone();
+        self.harness.call(command, args)
+    }
+
+    fn fail(&self, command: &str, mut args: Value) -> Value {
+        args["context"] = self.context.clone();
+        args["database"] = self.database.clone();
+        self.harness.fail(command, args)
+    }
+
+    fn insert(&self, collection: Value, documents: Value) {
+        self.call::<Value>("collection_insert_documents", json!({"collection": collection, "documents": documents}));
+    }
+
+    fn count(&self, collection: Value) -> u64 {
+        self.call("collection_count_documents", json!({"collection": collection}))
+    }
+}
+
+fn global(name: &str) -> Value {
+    json!({"name": name})
+}
+
+#[test]
+fn opens_databases() {
+    let fixture = Fixture::new();
+    let info: Value = fixture.harness.call("database", json!({"context": fixture.context, "database": fixture.database}));
+    assert_eq!(info, json!({"name": "main", "path": "main.db", "backend": "polodb"}));
+    assert!(fixture.harness.path("main.db").exists());
+
+    let memory: Value = fixture.harness.call("database", json!({"context": fixture.context, "database": {"alias": "scratch", "memory": true}}));
+    assert_eq!(memory["backend"], "memory");
+    let error = fixture.harness.fail("database", json!({"context": fixture.context, "database": {"alias": "scratch", "memory": false}}));
+    assert_eq!(kind(&error), "open_database");
+    assert_eq!(kind(&fixture.harness.fail("database", json!({"context": fixture.context, "database": {"alias": "missing"}}))), "unknown_database");
+}
+
+#[test]
+fn closes_databases() {
+    let fixture = Fixture::memory();
+    fixture.insert(global("items"), json!([{"a": 1}]));
+    fixture.call::<()>("database_close", json!({}));
+    let error = fixture.harness.fail("database_get_collections", json!({"context": fixture.context, "database": {"alias": "main"}}));
+    assert_eq!(kind(&error), "unknown_database");
+}
+
+#[test]
+fn manages_documents() {
+    let fixture = Fixture::memory();
+    let items = global("items");
+    let ids: Value = fixture.call("collection_insert_documents", json!({"collection": items, "documents": [
+        {"name": "a", "value": 1},
+        {"name": "b", "value": 2},
+        {"name": "c", "value": 3}
+    ]}));
+    assert_eq!(ids.as_object().unwrap().len(), 3);
+    assert_eq!(fixture.count(items.clone()), 3);
+    assert!(fixture.call::<Vec<String>>("database_get_collections", json!({})).contains(&"items".to_string()));
+
+    let found: Vec<Value> = fixture.call("collection_find_many_documents", json!({
+        "collection": items, "filter": {"value": {"$gte": 2}}, "options": {"sort": {"value": -1}, "limit": 1}
+    }));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0]["name"], "c");
+    let one: Value = fixture.call("collection_find_one_document", json!({"collection": items, "filter": {"name": "a"}}));
+    assert_eq!(one["value"], 1);
+    let none: Value = fixture.call("collection_find_one_document", json!({"collection": items, "filter": {"name": "z"}}));
+    assert_eq!(none, Value::Null);
+
+    let updated: Value = fixture.call("collection_update_documents", json!({
+        "collection": items, "query": {}, "changes": {"update": {"$inc": {"value": 10}}, "operations": "many", "upsert": false}
+    }));
+    assert_eq!(updated, json!({"matched": 3, "modified": 3}));
+    let upserted: Value = fixture.call("collection_update_documents", json!({
+        "collection": items, "query": {"name": "d"}, "changes": {"update": {"$set": {"value": 4}}, "operations": "one", "upsert": true}
+    }));
+    assert_eq!(upserted["matched"], 0);
+    assert_eq!(fixture.count(items.clone()), 4);
+
+    let deleted: u64 = fixture.call("collection_delete_documents", json!({"collection": items, "query": {"value": {"$gt": 10}}, "operations": "many"}));
+    assert_eq!(deleted, 3);
+    let deleted: u64 = fixture.call("collection_delete_documents", json!({"collection": items, "query": {}, "operations": "one"}));
+    assert_eq!(deleted, 1);
+    assert_eq!(fixture.count(items.clone()), 0);
+
+    fixture.call::<()>("collection_drop", json!({"collection": items}));
+    assert!(!fixture.call::<Vec<String>>("database_get_collections", json!({})).contains(&"items".to_string()));
+}
+
+#[test]
+fn enforces_unique_indexes() {
+    let fixture = Fixture::memory();
+    let users = global("users");
+    fixture.call::<()>("collection_create_index", json!({"collection": users, "keys": {"email": 1}, "options": {"name": "email", "unique": true}}));
+    fixture.insert(users.clone(), json!([{"email": "a@example.com"}]));
+
+    let error = fixture.fail("collection_insert_documents", json!({"collection": users, "documents": [{"email": "a@example.com"}]}));
+    assert_eq!(kind(&error), "database_error");
+    assert_eq!(error["code"], "duplicate_key");
+    assert_eq!(error["collection"], "users");
+
+    fixture.call::<()>("collection_drop_index", json!({"collection": users, "name": "email"}));
+    fixture.insert(users.clone(), json!([{"email": "a@example.com"}]));
+    assert_eq!(fixture.count(users), 2);
+}
+
+#[test]
+fn names_indexes_after_every_key() {
+    let fixture = Fixture::memory();
+    let items = global("items");
+    fixture.call::<()>("collection_create_index", json!({"collection": items, "keys": {"a": 1, "b": -1}, "options": {"unique": true}}));
+    fixture.call::<()>("collection_create_index", json!({"collection": items, "keys": {"a": -1}, "options": {}}));
+    fixture.insert(items.clone(), json!([{"a": 1, "b": 1}, {"a": 1, "b": 2}]));
+
+    fixture.call::<()>("collection_drop_index", json!({"collection": items, "name": "a_1_b_-1"}));
+    fixture.call::<()>("collection_drop_index", json!({"collection": items, "name": "a_-1"}));
+    assert!(fixture.harness.invoke("collection_drop_index", json!({"context": fixture.context, "database": fixture.database, "collection": items, "name": "a_1"})).is_err());
+}
+
+#[test]
+fn commits_and_rolls_back_transactions() {
+    let fixture = Fixture::memory();
+    let transaction: String = fixture.call("database_start_transaction", json!({}));
+    let scoped = json!({"name": "items", "transaction": transaction});
+    fixture.insert(scoped.clone(), json!([{"a": 1}]));
+    assert_eq!(fixture.count(scoped), 1);
+    assert_eq!(fixture.count(global("items")), 0);
+    fixture.call::<()>("database_commit_transaction", json!({"transaction": transaction}));
+    assert_eq!(fixture.count(global("items")), 1);
+
+    let transaction: String = fixture.call("database_start_transaction", json!({}));
+    fixture.insert(json!({"name": "items", "transaction": transaction}), json!([{"a": 2}]));
+    fixture.call::<()>("database_rollback_transaction", json!({"transaction": transaction}));
+    assert_eq!(fixture.count(global("items")), 1);
+
+    let error = fixture.fail("database_commit_transaction", json!({"transaction": transaction}));
+    assert_eq!(kind(&error), "unknown_transaction");
+}
+
+#[test]
+fn backs_up_and_restores() {
+    let fixture = Fixture::new();
+    fixture.insert(global("items"), json!([{"a": 1}]));
+    fixture.call::<()>("database_backup", json!({"path": "backups/main.db", "overwrite": false}));
+    assert!(fixture.harness.path("backups/main.db").exists());
+    let error = fixture.fail("database_backup", json!({"path": "backups/main.db", "overwrite": false}));
+    assert_eq!(error["io_kind"], "already_exists");
+
+    fixture.insert(global("items"), json!([{"a": 2}]));
+    assert_eq!(fixture.count(global("items")), 2);
+
+    // PoloDB is closed while its files are copied, which open transactions would prevent
+    let transaction: String = fixture.call("database_start_transaction", json!({}));
+    let error = fixture.fail("database_backup", json!({"path": "backups/busy.db", "overwrite": false}));
+    assert_eq!(kind(&error), "path_in_use");
+    fixture.call::<()>("database_rollback_transaction", json!({"transaction": transaction}));
+
+    fixture.call::<()>("database_restore", json!({"path": "backups/main.db"}));
+    assert_eq!(fixture.count(global("items")), 1);
+}
+
+#[test]
+fn persists_memory_databases() {
+    let fixture = Fixture::memory();
+    fixture.insert(global("items"), json!([{"a": 1}, {"a": 2}]));
+    let info: Value = fixture.call("database_persist", json!({"path": "persisted.db", "backend": "polodb"}));
+    assert_eq!(info, json!({"name": "main", "path": "persisted.db", "backend": "polodb"}));
+    assert!(fixture.harness.path("persisted.db").exists());
+
+    let persisted = fixture.with_database(json!({"alias": "main"}));
+    assert_eq!(persisted.count(global("items")), 2);
+}
+
+#[test]
+fn takes_snapshots() {
+    let fixture = Fixture::new();
+    fixture.insert(global("items"), json!([{"a": 1}]));
+    for _ in 0..3 {
+        fixture.call::<Value>("database_snapshot", json!({"directory": "snapshots", "keep": 2}));
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+    }
+    let snapshots: Vec<Value> = fixture.call("database_list_snapshots", json!({"directory": "snapshots"}));
+    assert_eq!(snapshots.len(), 2);
+
+    fixture.call::<()>("database_schedule_snapshots", json!({"schedule": {"directory": "scheduled", "interval": 3600, "keep": 1}}));
+    fixture.call::<()>("database_schedule_snapshots", json!({"schedule": null}));
+}
+
+#[test]
+fn dumps_and_restores_dumps() {
+    let fixture = Fixture::memory();
+    fixture.call::<()>("collection_create_index", json!({"collection": global("items"), "keys": {"a": 1}, "options": {"name": "a", "unique": true}}));
+    fixture.insert(global("items"), json!([{"a": 1}, {"a": 2}]));
+    fixture.insert(global("other"), json!([{"b": 1}]));
+
+    for format in ["json", "bson"] {
+        fixture.call::<()>("database_dump", json!({"directory": format!("dump-{format}"), "format": format}));
+        let copy = fixture.with_database(json!({"alias": format!("copy_{format}"), "memory": true}));
+        copy.call::<()>("database_restore_dump", json!({"directory": format!("dump-{format}")}));
+        assert_eq!(copy.count(global("items")), 2);
+        assert_eq!(copy.count(global("other")), 1);
+        let error = copy.fail("collection_insert_documents", json!({"collection": global("items"), "documents": [{"a": 1}]}));
+        assert_eq!(error["code"], "duplicate_key");
+    }
+}
+
+#[test]
+fn keeps_dump_files_inside_the_dump_directory() {
+    let fixture = Fixture::memory();
+    fixture.insert(global("../escape"), json!([{"a": 1}]));
+    fixture.call::<()>("database_dump", json!({"directory": "dumps/inner", "format": "json"}));
+    assert!(fixture.harness.path("dumps/inner/..%2Fescape.ndjson").exists());
+    assert!(!fixture.harness.path("dumps/escape.ndjson").exists());
+
+    let copy = fixture.with_database(json!({"alias": "copy", "memory": true}));
+    copy.call::<()>("database_restore_dump", json!({"directory": "dumps/inner"}));
+    assert_eq!(copy.count(global("../escape")), 1);
+
+    std::fs::write(fixture.harness.path("dumps/outside.ndjson"), "{\"a\": 2}\n").unwrap();
+    std::fs::write(fixture.harness.path("dumps/inner/manifest.json"), json!({"format": "json", "indexes": [], "collections": ["../outside"]}).to_string()).unwrap();
+    let error = copy.fail("database_restore_dump", json!({"directory": "dumps/inner"}));
+    assert_eq!(error["io_kind"], "not_found");
+    assert_eq!(copy.count(global("../outside")), 0);
+}
+
+#[test]
+fn rejects_sql_without_sql_backend() {
+    let fixture = Fixture::memory();
+    let error = fixture.fail("database_execute_sql", json!({"sql": "SELECT 1", "params": []}));
+    assert_eq!(error["code"], "unsupported");
+    let error = fixture.fail("database_query_sql", json!({"sql": "SELECT 1", "params": []}));
+    assert_eq!(error["code"], "unsupported");
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn runs_sql() {
+    let fixture = Fixture::new().with_database(json!({"alias": "notes", "path": "notes.sqlite", "backend": "sqlite"}));
+    fixture.call::<u64>("database_execute_sql", json!({"sql": "CREATE TABLE notes (id INTEGER PRIMARY KEY, body TEXT)", "params": []}));
+    let changed: u64 = fixture.call("database_execute_sql", json!({
+        "sql": "INSERT INTO notes (body) VALUES (?1)", "params": [{"type": "text", "value": "hello"}]
+    }));
+    assert_eq!(changed, 1);
+    let rows: Value = fixture.call("database_query_sql", json!({"sql": "SELECT id, body FROM notes", "params": []}));
+    assert_eq!(rows["columns"], json!(["id", "body"]));
+    assert_eq!(rows["rows"][0][1], json!({"type": "text", "value": "hello"}));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn keeps_sql_inside_the_store() {
+    let fixture = Fixture::new().with_database(json!({"alias": "notes", "path": "notes.sqlite", "backend": "sqlite"}));
+    let outside = fixture.harness.path("outside.sqlite").to_string_lossy().to_string();
+    for sql in [format!("ATTACH DATABASE '{outside}' AS outside"), format!("VACUUM INTO '{outside}'"), "DETACH DATABASE main".to_string()] {
+        assert!(fixture.harness.invoke("database_execute_sql", json!({"context": fixture.context, "database": fixture.database, "sql": sql, "params": []})).is_err());
+    }
+    assert!(!fixture.harness.path("outside.sqlite").exists());
+}
+
+#[test]
+fn grants_read_only_access() {
+    let harness = Harness::with_permissions(&["persistence:allow-context", "persistence:allow-database-read"]);
+    let context = harness.context();
+    let database = json!({"alias": "main", "path": "main.db"});
+    harness.call::<Value>("context", json!({"context": context}));
+
+    // Read commands never open databases, so nothing can be created through them
+    assert!(harness.invoke("database", json!({"context": context, "database": database})).is_err());
+    let error = harness.fail("collection_find_many_documents", json!({"context": context, "database": database, "collection": global("items"), "filter": {}, "options": {}}));
+    assert_eq!(kind(&error), "unknown_database");
+    assert!(!harness.path("main.db").exists());
+
+    let persistence = harness.app.persistence();
+    let specifier = DatabaseSpecifier::Direct { alias: "main".to_string(), path: "main.db".to_string(), backend: Default::default() };
+    tauri::async_runtime::block_on(persistence.database(ContextSpecifier::Aliased { alias: "test".to_string() }, specifier)).unwrap();
+    let found: Vec<Value> = harness.call("collection_find_many_documents", json!({"context": context, "database": database, "collection": global("items"), "filter": {}, "options": {}}));
+    assert!(found.is_empty());
+
+    assert!(harness.invoke("collection_insert_documents", json!({"context": context, "database": database, "collection": global("items"), "documents": [{"a": 1}]})).is_err());
+    assert!(harness.invoke("database_query_sql", json!({"context": context, "database": database, "sql": "SELECT 1", "params": []})).is_err());
+    assert!(harness.invoke("database_backup", json!({"context": context, "database": database, "path": "backup.db", "overwrite": false})).is_err());
+    assert!(!harness.path("backup.db").exists());
+    assert!(harness.invoke("database_close", json!({"context": context, "database": database})).is_err());
+    let found: Vec<Value> = harness.call("collection_find_many_documents", json!({"context": context, "database": database, "collection": global("items"), "filter": {}, "options": {}}));
+    assert!(found.is_empty());
+}
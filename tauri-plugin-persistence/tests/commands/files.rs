@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use serde_json::{json, Value};
+
+use crate::{kind, Harness};
+
+fn open(harness: &Harness, path: &str, mode: Value) -> Value {
+    let info: Value = harness.call("file_handle", json!({"context": harness.context(), "fileHandle": {"path": path, "mode": mode}}));
+    json!({"id": info["id"]})
+}
+
+#[test]
+fn writes_and_reads_text() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let handle = open(&harness, "notes/today.txt", json!({"mode": "create", "new": true, "overwrite": false}));
+    harness.call::<()>("file_write_text", json!({"context": context, "fileHandle": handle, "data": "hello "}));
+    harness.call::<()>("file_write_text", json!({"context": context, "fileHandle": handle, "data": "world"}));
+    harness.call::<()>("file_flush", json!({"context": context, "fileHandle": handle}));
+    harness.call::<()>("file_sync", json!({"context": context, "fileHandle": handle}));
+    let info: Value = harness.call("file_handle", json!({"context": context, "fileHandle": handle}));
+    assert_eq!(info["size"], 11);
+    harness.call::<()>("file_close", json!({"context": context, "fileHandle": handle}));
+    assert_eq!(std::fs::read_to_string(harness.path("notes/today.txt")).unwrap(), "hello world");
+
+    let handle = open(&harness, "notes/today.txt", json!({"mode": "read"}));
+    let start: String = harness.call("file_read_text", json!({"context": context, "fileHandle": handle, "size": 5}));
+    assert_eq!(start, "hello");
+    let rest: String = harness.call("file_read_text", json!({"context": context, "fileHandle": handle}));
+    assert_eq!(rest, " world");
+
+    let error = harness.fail("file_handle", json!({"context": context, "fileHandle": {"path": "notes/today.txt", "mode": {"mode": "create", "new": true, "overwrite": false}}}));
+    assert_eq!(kind(&error), "open_file_handle");
+}
+
+#[test]
+fn writes_and_reads_bytes() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let error = harness.fail("file_handle", json!({"context": context, "fileHandle": {"path": "data.bin", "mode": {"mode": "write", "overwrite": true}}}));
+    assert_eq!(kind(&error), "open_file_handle");
+
+    let handle = open(&harness, "data.bin", json!({"mode": "create", "new": true, "overwrite": false}));
+    harness.call::<()>("file_write_bytes", json!({"context": context, "fileHandle": handle, "data": [0, 1, 2, 255]}));
+    harness.call::<()>("file_close", json!({"context": context, "fileHandle": handle}));
+    let handle = open(&harness, "data.bin", json!({"mode": "write", "overwrite": false}));
+    harness.call::<()>("file_write_bytes", json!({"context": context, "fileHandle": handle, "data": [3]}));
+    harness.call::<()>("file_close", json!({"context": context, "fileHandle": handle}));
+
+    let handle = open(&harness, "data.bin", json!({"mode": "read"}));
+    let data: Vec<u8> = harness.call("file_read_bytes", json!({"context": context, "fileHandle": handle}));
+    assert_eq!(data, vec![0, 1, 2, 255, 3]);
+}
+
+#[test]
+fn compresses_files() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let text = "compressible ".repeat(100);
+    std::fs::create_dir_all(&harness.root).unwrap();
+    for compression in ["gzip", "zstd"] {
+        // Overwrites a longer file, whose leftover bytes mustn't end up after the compressed stream
+        let path = format!("data.{compression}");
+        std::fs::write(harness.path(&path), "incompressible ".repeat(1000)).unwrap();
+        let handle = open(&harness, &path, json!({"mode": "create", "new": false, "overwrite": true, "compression": compression}));
+        harness.call::<()>("file_write_text", json!({"context": context, "fileHandle": handle, "data": text}));
+
+        // Synced data must be readable before the stream is finished
+        harness.call::<()>("file_sync", json!({"context": context, "fileHandle": handle}));
+        let synced = std::fs::read(harness.path(&path)).unwrap();
+        let decoded = match compression {
+            "gzip" => {
+                let mut decoder = flate2::write::GzDecoder::new(Vec::new());
+                decoder.write_all(&synced).unwrap();
+                decoder.flush().unwrap();
+                decoder.get_ref().clone()
+            }
+            _ => {
+                let mut decoder = zstd::stream::write::Decoder::new(Vec::new()).unwrap();
+                decoder.write_all(&synced).unwrap();
+                decoder.flush().unwrap();
+                decoder.get_ref().clone()
+            }
+        };
+        assert_eq!(String::from_utf8(decoded).unwrap(), text);
+
+        harness.call::<()>("file_close", json!({"context": context, "fileHandle": handle}));
+        assert!(std::fs::metadata(harness.path(&path)).unwrap().len() < text.len() as u64);
+
+        let handle = open(&harness, &path, json!({"mode": "read", "raw": true}));
+        let raw: Vec<u8> = harness.call("file_read_bytes", json!({"context": context, "fileHandle": handle}));
+        assert_eq!(raw, std::fs::read(harness.path(&path)).unwrap());
+
+        let handle = open(&harness, &path, json!({"mode": "read"}));
+        let read: String = harness.call("file_read_text", json!({"context": context, "fileHandle": handle}));
+        assert_eq!(read, text);
+        let info: Value = harness.call("file_handle", json!({"context": context, "fileHandle": handle}));
+        assert_eq!(info["compression"], compression);
+    }
+}
+
+#[test]
+fn locks_files() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let first = open(&harness, "locked.txt", json!({"mode": "create", "new": false, "overwrite": false}));
+    let second = open(&harness, "locked.txt", json!({"mode": "read"}));
+    harness.call::<()>("file_lock", json!({"context": context, "fileHandle": first, "exclusive": true}));
+    assert_eq!(kind(&harness.fail("file_lock", json!({"context": context, "fileHandle": second, "exclusive": false}))), "file_locked");
+    harness.call::<()>("file_unlock", json!({"context": context, "fileHandle": first}));
+    harness.call::<()>("file_lock", json!({"context": context, "fileHandle": second, "exclusive": false}));
+}
+
+#[test]
+fn tracks_handles() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let temporary: Value = harness.call("temp_file", json!({"context": context}));
+    assert_eq!(temporary["temporary"], true);
+    let temporary_path = harness.path(temporary["path"].as_str().unwrap());
+    assert!(temporary_path.exists());
+    open(&harness, "a.txt", json!({"mode": "create", "new": false, "overwrite": false}));
+
+    let handles: Vec<Value> = harness.call("list_file_handles", json!({"context": context}));
+    assert_eq!(handles.len(), 2);
+    harness.call::<()>("close_all_file_handles", json!({"context": context}));
+    let handles: Vec<Value> = harness.call("list_file_handles", json!({"context": context}));
+    assert!(handles.is_empty());
+    assert!(!temporary_path.exists());
+
+    let stale = json!({"id": temporary["id"]});
+    assert_eq!(kind(&harness.fail("file_read_text", json!({"context": context, "fileHandle": stale}))), "unknown_file_handle");
+    assert_eq!(kind(&harness.fail("file_close", json!({"context": context, "fileHandle": stale}))), "unknown_file_handle");
+}
@@ -0,0 +1,208 @@
+use serde_json::{json, Value};
+
+use crate::{kind, Harness};
+
+/// A context with `docs/a.txt`, `docs/b.txt` (same contents) & `docs/nested/c.md`
+fn populated() -> Harness {
+    let harness = Harness::new();
+    let context = harness.context();
+    harness.call::<()>("create_directory", json!({"context": context, "path": "docs/nested", "parents": true}));
+    std::fs::write(harness.path("docs/a.txt"), "same").unwrap();
+    std::fs::write(harness.path("docs/b.txt"), "same").unwrap();
+    std::fs::write(harness.path("docs/nested/c.md"), "different").unwrap();
+    harness
+}
+
+#[test]
+fn creates_and_removes_paths() {
+    let harness = populated();
+    let context = harness.context();
+    let error = harness.fail("create_directory", json!({"context": context, "path": "a/b/c", "parents": false}));
+    assert_eq!(kind(&error), "filesystem_error");
+    assert_eq!(error["io_kind"], "not_found");
+
+    assert!(harness.call::<bool>("exists", json!({"context": context, "path": "docs/a.txt"})));
+    assert!(harness.call::<bool>("is_file", json!({"context": context, "path": "docs/a.txt"})));
+    assert!(harness.call::<bool>("is_dir", json!({"context": context, "path": "docs/nested"})));
+    assert!(!harness.call::<bool>("is_dir", json!({"context": context, "path": "docs/a.txt"})));
+
+    harness.call::<()>("remove_file", json!({"context": context, "path": "docs/a.txt"}));
+    assert!(!harness.path("docs/a.txt").exists());
+    let error = harness.fail("remove_file", json!({"context": context, "path": "docs/a.txt"}));
+    assert_eq!(error["path"], "docs/a.txt");
+    assert_eq!(error["io_kind"], "not_found");
+
+    harness.call::<()>("remove_directory", json!({"context": context, "path": "docs"}));
+    assert!(!harness.path("docs").exists());
+}
+
+#[test]
+fn reads_metadata() {
+    let harness = populated();
+    let context = harness.context();
+    let metadata: Value = harness.call("file_metadata", json!({"context": context, "path": "docs/nested/c.md"}));
+    assert_eq!(metadata["file_type"], "file");
+    assert_eq!(metadata["size"], 9);
+
+    let stats: Vec<Value> = harness.call("stat_many", json!({"context": context, "paths": ["docs", "missing"]}));
+    assert_eq!(stats[0]["file_type"], "directory");
+    assert_eq!(stats[1], Value::Null);
+
+    let entries: Vec<Value> = harness.call("list_directory", json!({"context": context, "path": "docs"}));
+    let mut names = entries.iter().map(|entry| entry["file_name"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["a.txt", "b.txt", "nested"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn sets_permissions() {
+    let harness = populated();
+    let context = harness.context();
+    harness.call::<()>("set_permissions", json!({"context": context, "path": "docs/a.txt", "mode": 0o600}));
+    let metadata: Value = harness.call("file_metadata", json!({"context": context, "path": "docs/a.txt"}));
+    assert_eq!(metadata["mode"].as_u64().unwrap() & 0o777, 0o600);
+    harness.call::<()>("set_permissions", json!({"context": context, "path": "docs/a.txt", "mode": 0o4755}));
+    let metadata: Value = harness.call("file_metadata", json!({"context": context, "path": "docs/a.txt"}));
+    assert_eq!(metadata["mode"], 0o755);
+
+    harness.call::<()>("set_readonly", json!({"context": context, "path": "docs/a.txt", "readonly": true}));
+    let metadata: Value = harness.call("file_metadata", json!({"context": context, "path": "docs/a.txt"}));
+    assert_eq!(metadata["readonly"], true);
+    harness.call::<()>("set_readonly", json!({"context": context, "path": "docs/a.txt", "readonly": false}));
+}
+
+#[cfg(unix)]
+#[test]
+fn reports_symlinks() {
+    let harness = populated();
+    let context = harness.context();
+    std::os::unix::fs::symlink("a.txt", harness.path("docs/link.txt")).unwrap();
+    let metadata: Value = harness.call("file_metadata", json!({"context": context, "path": "docs/link.txt"}));
+    assert_eq!(metadata["file_type"], "symlink");
+    assert_eq!(metadata["symlink_target"], "a.txt");
+
+    let metadata: Vec<Value> = harness.call("stat_many", json!({"context": context, "paths": ["docs/link.txt", "docs/a.txt", "missing"]}));
+    assert_eq!(metadata[0]["file_type"], "symlink");
+    assert_eq!(metadata[1]["symlink_target"], Value::Null);
+    assert_eq!(metadata[2], Value::Null);
+}
+
+#[test]
+fn walks_directories() {
+    let harness = populated();
+    let context = harness.context();
+    let entries: Vec<Value> = harness.call("walk", json!({"context": context, "path": "docs"}));
+    let mut paths = entries.iter().map(|entry| entry["path"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, ["a.txt", "b.txt", "nested", "nested/c.md"]);
+
+    let shallow: Vec<Value> = harness.call("walk", json!({"context": context, "path": "docs", "options": {"max_depth": 1, "include": ["*.txt"]}}));
+    assert_eq!(shallow.len(), 2);
+
+    let count: u32 = harness.call("walk_stream", json!({"context": context, "path": "docs", "channel": "__CHANNEL__:1"}));
+    assert_eq!(count, 4);
+}
+
+#[test]
+fn hashes_files() {
+    let harness = populated();
+    let context = harness.context();
+    let a: Value = harness.call("hash_file", json!({"context": context, "path": "docs/a.txt", "algorithm": "sha256"}));
+    let b: Value = harness.call("hash_file", json!({"context": context, "path": "docs/b.txt", "algorithm": "sha256"}));
+    assert_eq!(a["digest"], b["digest"]);
+    assert_eq!(a["cached"], false);
+
+    harness.call::<Value>("database", json!({"context": context, "database": {"alias": "hashes", "memory": true}}));
+    harness.call::<Value>("hash_file", json!({"context": context, "path": "docs/a.txt", "algorithm": "blake3", "cache": "hashes"}));
+    let cached: Value = harness.call("hash_file", json!({"context": context, "path": "docs/a.txt", "algorithm": "blake3", "cache": "hashes"}));
+    assert_eq!(cached["cached"], true);
+
+    let hashes: Vec<Value> = harness.call("hash_directory", json!({"context": context, "path": "docs", "algorithm": "blake3", "cache": "hashes"}));
+    assert_eq!(hashes.len(), 3);
+    let duplicates: Vec<Vec<Value>> = harness.call("find_duplicates", json!({"context": context, "path": "docs", "algorithm": "blake3"}));
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].len(), 2);
+}
+
+#[test]
+fn copies_and_renames_paths() {
+    let harness = populated();
+    let context = harness.context();
+    let error = harness.fail("copy_path", json!({"context": context, "from": "docs", "to": "copy", "recursive": false, "overwrite": false}));
+    assert_eq!(error["io_kind"], "invalid_input");
+    harness.call::<()>("copy_path", json!({"context": context, "from": "docs", "to": "copy", "recursive": true, "overwrite": false}));
+    assert_eq!(std::fs::read_to_string(harness.path("copy/nested/c.md")).unwrap(), "different");
+    let error = harness.fail("copy_path", json!({"context": context, "from": "docs/a.txt", "to": "copy/b.txt", "recursive": false, "overwrite": false}));
+    assert_eq!(error["io_kind"], "already_exists");
+    for to in ["copy/b.txt", "copy/nested/../b.txt"] {
+        let error = harness.fail("copy_path", json!({"context": context, "from": "copy/b.txt", "to": to, "recursive": false, "overwrite": true}));
+        assert_eq!(error["io_kind"], "invalid_input");
+    }
+    assert!(!std::fs::read_to_string(harness.path("copy/b.txt")).unwrap().is_empty());
+
+    harness.call::<()>("rename_path", json!({"context": context, "from": "copy", "to": "moved"}));
+    assert!(!harness.path("copy").exists());
+    assert!(harness.path("moved/a.txt").is_file());
+}
+
+#[test]
+fn reads_and_writes_documents() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let document = json!({"name": "project", "tags": ["a", "b"], "nested": {"count": 3}});
+    for path in ["doc.json", "doc.toml", "doc.yaml"] {
+        harness.call::<()>("write_document", json!({"context": context, "path": path, "document": document}));
+        let read: Value = harness.call("read_document", json!({"context": context, "path": path}));
+        assert_eq!(read, document);
+    }
+    harness.call::<()>("write_document", json!({"context": context, "path": "doc.data", "document": document, "format": "bson"}));
+    let read: Value = harness.call("read_document", json!({"context": context, "path": "doc.data", "format": "bson"}));
+    assert_eq!(read, document);
+
+    std::fs::write(harness.path("broken.json"), "{\"name\":").unwrap();
+    assert_eq!(kind(&harness.fail("read_document", json!({"context": context, "path": "broken.json"}))), "parse_error");
+}
+
+#[test]
+fn creates_temporary_directories() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let path: String = harness.call("temp_dir", json!({"context": context}));
+    assert!(harness.path(&path).is_dir());
+    harness.call::<Value>("context", json!({"context": context}));
+    assert!(harness.path(&path).is_dir());
+}
+
+#[test]
+fn watches_paths() {
+    let harness = populated();
+    let context = harness.context();
+    let id: String = harness.call("watch", json!({"context": context, "path": "docs", "recursive": true}));
+    harness.call::<()>("unwatch", json!({"context": context, "id": id}));
+    assert_eq!(kind(&harness.fail("unwatch", json!({"context": context, "id": id}))), "unknown_watcher");
+    let error = harness.fail("watch", json!({"context": context, "path": "missing", "recursive": false}));
+    assert_eq!(error["io_kind"], "not_found");
+}
+
+#[test]
+fn grants_read_only_access() {
+    let harness = Harness::with_permissions(&["persistence:allow-context", "persistence:allow-fs-read"]);
+    let context = harness.context();
+    harness.call::<Value>("context", json!({"context": context}));
+    std::fs::write(harness.path("doc.json"), "{\"name\":\"project\"}").unwrap();
+    let read: Value = harness.call("read_document", json!({"context": context, "path": "doc.json"}));
+    assert_eq!(read, json!({"name": "project"}));
+    assert!(harness.call::<bool>("is_file", json!({"context": context, "path": "doc.json"})));
+
+    assert!(harness.invoke("write_document", json!({"context": context, "path": "doc.json", "document": {}})).is_err());
+    assert!(harness.invoke("remove_file", json!({"context": context, "path": "doc.json"})).is_err());
+    assert!(harness.invoke("create_directory", json!({"context": context, "path": "new", "parents": false})).is_err());
+    assert_eq!(std::fs::read_to_string(harness.path("doc.json")).unwrap(), "{\"name\":\"project\"}");
+    assert!(!harness.path("new").exists());
+
+    // Contexts that aren't open yet can't be created through read commands
+    let other = format!("{}-other", harness.root.display());
+    assert_eq!(kind(&harness.fail("exists", json!({"context": {"alias": "other", "path": other}, "path": "file.txt"}))), "unknown_context");
+    assert!(!std::path::Path::new(&other).exists());
+}
@@ -0,0 +1,129 @@
+//! A mock app with the plugin registered & its permission manifests loaded, driven through the same IPC path as a webview.
+
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tauri::{
+    ipc::{CallbackFn, CapabilityBuilder, InvokeBody, RuntimeAuthority},
+    test::{get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY},
+    webview::InvokeRequest,
+    App, Manager, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_plugin_persistence::{types::ContextSpecifier, PersistenceExt, PluginBuilder};
+use tauri_utils::acl::{manifest::{Manifest, PermissionFile}, resolved::Resolved};
+
+/// Loads the plugin's permission files, as tauri-build does for an app depending on it
+fn manifest() -> Manifest {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("permissions");
+    let mut paths = vec![root.join("default.toml")];
+    for directory in ["sets", "autogenerated/commands"] {
+        for entry in std::fs::read_dir(root.join(directory)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "toml") {
+                paths.push(path);
+            }
+        }
+    }
+
+    let files = paths
+        .iter()
+        .map(|path| toml::from_str::<PermissionFile>(&std::fs::read_to_string(path).unwrap()).unwrap())
+        .collect();
+    Manifest::new(files, None)
+}
+
+pub struct Harness {
+    pub app: App<MockRuntime>,
+    webview: WebviewWindow<MockRuntime>,
+    pub root: PathBuf,
+}
+
+impl Harness {
+    /// A harness granted the plugin's default permissions
+    pub fn new() -> Self {
+        Self::with_permissions(&["persistence:default"])
+    }
+
+    pub fn with_permissions(permissions: &[&str]) -> Self {
+        Self::with_capability(|capability| permissions.iter().fold(capability, |capability, permission| capability.permission(*permission)))
+    }
+
+    /// A harness granted the permissions & scopes added to the main window's capability by `grant`
+    pub fn with_capability(grant: impl FnOnce(CapabilityBuilder) -> CapabilityBuilder) -> Self {
+        Self::with_plugin(PluginBuilder::new(), grant)
+    }
+
+    /// A harness running the plugin as configured by `plugin`
+    pub fn with_plugin(plugin: PluginBuilder, grant: impl FnOnce(CapabilityBuilder) -> CapabilityBuilder) -> Self {
+        let mut context = mock_context(noop_assets());
+        *context.runtime_authority_mut() = RuntimeAuthority::new([("persistence".to_string(), manifest())].into(), Resolved::default());
+        let app = mock_builder().plugin(plugin.build()).build(context).unwrap();
+        app.add_capability(grant(CapabilityBuilder::new("test").window("main"))).unwrap();
+
+        let webview = WebviewWindowBuilder::new(&app, "main", Default::default()).build().unwrap();
+        let root = std::env::temp_dir().join(format!("persistence-commands-{}", bson::Uuid::new()));
+        Self { app, webview, root }
+    }
+
+    /// Invokes a plugin command, returning its response or the serialized error
+    pub fn invoke(&self, command: &str, args: Value) -> Result<Value, Value> {
+        get_ipc_response(
+            &self.webview,
+            InvokeRequest {
+                cmd: format!("plugin:persistence|{command}"),
+                callback: CallbackFn(0),
+                error: CallbackFn(1),
+                url: "tauri://localhost".parse().unwrap(),
+                body: InvokeBody::Json(args),
+                headers: Default::default(),
+                invoke_key: INVOKE_KEY.to_string(),
+            },
+        )
+        .map(|response| response.deserialize::<Value>().unwrap())
+    }
+
+    /// Invokes a command that's expected to succeed
+    pub fn call<T: DeserializeOwned>(&self, command: &str, args: Value) -> T {
+        match self.invoke(command, args) {
+            Ok(response) => serde_json::from_value(response).unwrap(),
+            Err(error) => panic!("{command} failed: {error}"),
+        }
+    }
+
+    /// Invokes a command that's expected to fail, returning the error
+    pub fn fail(&self, command: &str, args: Value) -> Value {
+        match self.invoke(command, args) {
+            Ok(response) => panic!("{command} unexpectedly succeeded with {response}"),
+            Err(error) => error,
+        }
+    }
+
+    /// Specifier for the test context, opening it on first use
+    pub fn context(&self) -> Value {
+        json!({"alias": "test", "path": self.root.to_string_lossy()})
+    }
+
+    pub fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.join(relative)
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let persistence = self.app.persistence();
+        let _ = tauri::async_runtime::block_on(persistence.close_context(ContextSpecifier::Aliased { alias: "test".to_string() }));
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// The `kind` of a serialized error
+pub fn kind(error: &Value) -> &str {
+    error["kind"].as_str().unwrap_or_default()
+}
@@ -0,0 +1,115 @@
+use serde_json::{json, Value};
+
+use tauri_plugin_persistence::{types::{ContextSpecifier, KvSpecifier}, PersistenceExt};
+
+use crate::{kind, Harness};
+
+fn store(harness: &Harness, encoding: &str) -> Value {
+    let store = json!({"alias": "settings", "path": "settings.redb", "encoding": encoding});
+    let info: Value = harness.call("kv_store", json!({"context": harness.context(), "store": store}));
+    assert_eq!(info, json!({"name": "settings", "path": "settings.redb", "encoding": encoding}));
+    json!({"alias": "settings"})
+}
+
+#[test]
+fn stores_values() {
+    for encoding in ["json", "bson"] {
+        let harness = Harness::new();
+        let context = harness.context();
+        let store = store(&harness, encoding);
+
+        harness.call::<()>("kv_set", json!({"context": context, "store": store, "key": "theme", "value": "dark"}));
+        harness.call::<()>("kv_set", json!({"context": context, "store": store, "key": "window.size", "value": {"width": 800, "height": 600}}));
+        harness.call::<()>("kv_set", json!({"context": context, "store": store, "key": "window.maximized", "value": false}));
+        let theme: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "theme"}));
+        assert_eq!(theme, "dark");
+        let missing: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "missing"}));
+        assert_eq!(missing, Value::Null);
+
+        let window: Vec<Value> = harness.call("kv_list", json!({"context": context, "store": store, "prefix": "window."}));
+        assert_eq!(window, vec![
+            json!({"key": "window.maximized", "value": false}),
+            json!({"key": "window.size", "value": {"width": 800, "height": 600}})
+        ]);
+
+        assert!(harness.call::<bool>("kv_delete", json!({"context": context, "store": store, "key": "theme"})));
+        assert!(!harness.call::<bool>("kv_delete", json!({"context": context, "store": store, "key": "theme"})));
+    }
+}
+
+#[test]
+fn compares_and_swaps() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let store = store(&harness, "json");
+
+    let swap = |expected: Value, value: Value| -> bool {
+        harness.call("kv_compare_and_swap", json!({"context": context, "store": store, "key": "counter", "swap": {"expected": expected, "value": value}}))
+    };
+    assert!(swap(Value::Null, json!(1)));
+    assert!(!swap(Value::Null, json!(2)));
+    assert!(swap(json!(1), json!(2)));
+    assert!(swap(json!(2), Value::Null));
+    let counter: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "counter"}));
+    assert_eq!(counter, Value::Null);
+}
+
+#[test]
+fn commits_and_rolls_back_transactions() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let store = store(&harness, "json");
+
+    let transaction: String = harness.call("kv_start_transaction", json!({"context": context, "store": store}));
+    harness.call::<()>("kv_set", json!({"context": context, "store": store, "key": "a", "value": 1, "transaction": transaction}));
+    let inside: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "a", "transaction": transaction}));
+    assert_eq!(inside, 1);
+    assert_eq!(kind(&harness.fail("kv_start_transaction", json!({"context": context, "store": store}))), "database_error");
+    harness.call::<()>("kv_commit_transaction", json!({"context": context, "store": store, "transaction": transaction}));
+    let committed: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "a"}));
+    assert_eq!(committed, 1);
+
+    let transaction: String = harness.call("kv_start_transaction", json!({"context": context, "store": store}));
+    harness.call::<bool>("kv_delete", json!({"context": context, "store": store, "key": "a", "transaction": transaction}));
+    harness.call::<()>("kv_rollback_transaction", json!({"context": context, "store": store, "transaction": transaction}));
+    let kept: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "a"}));
+    assert_eq!(kept, 1);
+    let error = harness.fail("kv_commit_transaction", json!({"context": context, "store": store, "transaction": transaction}));
+    assert_eq!(kind(&error), "unknown_transaction");
+}
+
+#[test]
+fn closes_stores() {
+    let harness = Harness::new();
+    let context = harness.context();
+    let store = store(&harness, "json");
+    harness.call::<()>("kv_set", json!({"context": context, "store": store, "key": "a", "value": 1}));
+    harness.call::<()>("kv_close", json!({"context": context, "store": store}));
+    assert_eq!(kind(&harness.fail("kv_get", json!({"context": context, "store": store, "key": "a"}))), "unknown_kv_store");
+
+    // Reads only reach open stores, so it has to be reopened first
+    let reopened = json!({"alias": "settings", "path": "settings.redb"});
+    assert_eq!(kind(&harness.fail("kv_get", json!({"context": context, "store": reopened, "key": "a"}))), "unknown_kv_store");
+    harness.call::<Value>("kv_store", json!({"context": context, "store": reopened}));
+    let value: Value = harness.call("kv_get", json!({"context": context, "store": reopened, "key": "a"}));
+    assert_eq!(value, 1);
+}
+
+#[test]
+fn grants_read_only_access() {
+    let harness = Harness::with_permissions(&["persistence:allow-context", "persistence:allow-kv-read"]);
+    let context = harness.context();
+    harness.call::<Value>("context", json!({"context": context}));
+    let specifier = KvSpecifier::Direct { alias: "settings".to_string(), path: "settings.redb".to_string(), encoding: Default::default() };
+    tauri::async_runtime::block_on(harness.app.persistence().kv_store(ContextSpecifier::Aliased { alias: "test".to_string() }, specifier)).unwrap();
+
+    let store = json!({"alias": "settings"});
+    let value: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "a"}));
+    assert_eq!(value, Value::Null);
+    assert!(harness.invoke("kv_set", json!({"context": context, "store": store, "key": "a", "value": 1})).is_err());
+
+    // Stores opened elsewhere can't be closed out from under their owner
+    assert!(harness.invoke("kv_close", json!({"context": context, "store": store})).is_err());
+    let value: Value = harness.call("kv_get", json!({"context": context, "store": store, "key": "a"}));
+    assert_eq!(value, Value::Null);
+}
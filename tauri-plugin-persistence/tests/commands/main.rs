@@ -0,0 +1,11 @@
+//! Drives the plugin's commands through a mock runtime, checking their behavior & the permission manifests without a GUI.
+
+mod harness;
+
+mod context;
+mod database;
+mod files;
+mod filesystem;
+mod kv;
+
+pub use harness::{kind, Harness};